@@ -0,0 +1,107 @@
+//! Benchmarks of the work done for every packet sent, to measure the cost of
+//! changes to the packet format.
+
+use std::{collections::{BTreeMap, VecDeque}, hint::black_box};
+
+use arowss::{
+    decimation::SampleRange,
+    encode_frame,
+    events::{Event, EventCode},
+    utils::crc8,
+    EnvironmentalInfo, GpsInfo, RawValues, TelemetryPacket, ThermalInfo,
+};
+use bno055::mint;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+/// A packet with nothing but the fields which are always sent.
+fn minimal_packet() -> TelemetryPacket {
+    TelemetryPacket::default()
+}
+
+/// A packet with every field filled in, as large as packets normally get.
+fn full_packet() -> TelemetryPacket {
+    TelemetryPacket {
+        gps: Some(GpsInfo {
+            latitude: 40.820_123_456,
+            longitude: -96.705_654_321,
+            altitude: 1234.5,
+            satellites: 12,
+            hdop: Some(0.9),
+            geoid_separation: Some(-26.3),
+            speed: Some(152.4),
+            course: Some(271.8),
+        }),
+        pressure_altitude: Some(1230.25),
+        environmental_info: Some(EnvironmentalInfo {
+            pressure: 87_512.5,
+            pressure_range: Some(SampleRange { min: 87_490.0, max: 87_530.0 }),
+            temperature: 21.5,
+            temperature_range: Some(SampleRange { min: 21.4, max: 21.6 }),
+            humidity: 35.5,
+        }),
+        orientation_info: Some(mint::Quaternion {
+            v: mint::Vector3 { x: 0.012, y: -0.707, z: 0.003 },
+            s: 0.707,
+        }),
+        info: VecDeque::from([
+            "PONG 1767225600000".to_string(),
+            "192.168.199.2".to_string(),
+            "disk 12.3 GiB free, mem 612 MiB free, cpu 48.2°C".to_string(),
+        ]),
+        events: VecDeque::from([
+            Event::new(EventCode::RelayEnabled),
+            Event::with_arg(EventCode::SeaLevelLocked, 101_325),
+            Event::with_arg(EventCode::Echo, 3),
+        ]),
+        safe_mode: false,
+        flight_id: Some("1a2b3c4d".to_string()),
+        time: Some(1_767_225_600_000),
+        thermal: Some(ThermalInfo { cpu: Some(48.2) }),
+        raw: Some(RawValues {
+            bmp_pressure: Some(5_600_800),
+            bmp_temperature: Some(1_409_024),
+            hts_humidity: Some(-4_321),
+        }),
+        computed: BTreeMap::from([("dens_alt".to_string(), 1502.75)]),
+        ..TelemetryPacket::default()
+    }
+}
+
+fn packets() -> [(&'static str, TelemetryPacket); 2] {
+    [("minimal", minimal_packet()), ("full", full_packet())]
+}
+
+fn bench_vec_crc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("vec_crc");
+    for (name, packet) in packets() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &packet, |b, packet| {
+            b.iter(|| black_box(packet).vec_crc());
+        });
+    }
+    group.finish();
+}
+
+fn bench_crc8(c: &mut Criterion) {
+    let mut group = c.benchmark_group("crc8");
+    // From an empty packet up to the most a packet may use
+    for size in [64, 256, 1024] {
+        let data: Vec<u8> = (0..size).map(|i| i as u8).collect();
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| crc8(black_box(data)));
+        });
+    }
+    group.finish();
+}
+
+fn bench_encode_frame(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode_frame");
+    for (name, packet) in packets() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &packet, |b, packet| {
+            b.iter(|| encode_frame(black_box(0), black_box(packet)));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_vec_crc, bench_crc8, bench_encode_frame);
+criterion_main!(benches);
@@ -0,0 +1,231 @@
+//! Barometric altitude, with the sea level reference pressure calibrated
+//! from the GPS while on the pad.
+
+use std::time::{Duration, Instant};
+
+use crate::GpsInfo;
+
+/// Standard sea level pressure in pascals, used until calibrated.
+pub const STANDARD_SEA_LEVEL_PRESSURE: f64 = 101_325.0;
+
+/// GPS altitude is only trusted for calibration with an HDOP at most this.
+const MAX_CALIBRATION_HDOP: f32 = 2.0;
+/// The estimates of the sea level pressure must hold steady for this long
+/// before the calibration is latched.
+const CALIBRATION_WINDOW: Duration = Duration::from_secs(30);
+/// Every estimate in the window must be within this many pascals of their
+/// mean, roughly 10 meters of altitude, which covers the wander of the GPS
+/// altitude.
+const MAX_CALIBRATION_SPREAD: f64 = 120.0;
+/// The window starts over if no estimate is given for this long, such as
+/// when the GPS loses its fix.
+const MAX_CALIBRATION_GAP: Duration = Duration::from_secs(3);
+
+/// Calculate the altitude in meters of a pressure in pascals, relative to
+/// the given sea level pressure, using the international barometric formula.
+pub fn pressure_altitude(pressure: f64, sea_level_pressure: f64) -> f64 {
+    44_330.0 * (1.0 - (pressure / sea_level_pressure).powf(1.0 / 5.255))
+}
+
+/// Calculate the sea level pressure in pascals which makes [`pressure_altitude`]
+/// give `altitude` meters for `pressure`.
+pub fn sea_level_pressure(pressure: f64, altitude: f64) -> f64 {
+    pressure / (1.0 - altitude / 44_330.0).powf(5.255)
+}
+
+/// Calibrates the sea level reference pressure against the GPS once, on the
+/// pad.
+///
+/// Each new GPS fix gives an estimate of the sea level pressure. Once the
+/// estimates have held steady for [`CALIBRATION_WINDOW`], their mean is
+/// latched as the reference and never changed again, so a lagging or
+/// wandering GPS in flight can't move it. Until then the standard sea level
+/// pressure is used.
+#[derive(Debug, Clone, Copy)]
+pub struct SeaLevelCalibration {
+    reference: f64,
+    /// When the current window of steady estimates started
+    window_start: Option<Instant>,
+    /// When the last estimate was added to the window
+    last_estimate: Option<Instant>,
+    sum: f64,
+    count: u32,
+    locked: bool,
+}
+
+impl Default for SeaLevelCalibration {
+    fn default() -> Self {
+        Self {
+            reference: STANDARD_SEA_LEVEL_PRESSURE,
+            window_start: None,
+            last_estimate: None,
+            sum: 0.0,
+            count: 0,
+            locked: false,
+        }
+    }
+}
+
+impl SeaLevelCalibration {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The current sea level reference pressure in pascals.
+    pub fn reference(&self) -> f64 {
+        self.reference
+    }
+
+    /// Whether the reference has been calibrated and latched.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Calculate the altitude in meters of a pressure with the current
+    /// reference.
+    pub fn altitude(&self, pressure: f64) -> f64 {
+        pressure_altitude(pressure, self.reference)
+    }
+
+    /// Add an estimate of the sea level pressure from a pressure reading
+    /// and a GPS fix taken at `now`, if the fix is good enough.
+    ///
+    /// A fix should only be given when it is new, so one fix isn't counted
+    /// many times, and while the payload is stationary, so a payload being
+    /// carried around isn't calibrated from a lagging GPS.
+    ///
+    /// Returns `true` when this estimate caused the reference to latch.
+    pub fn update(&mut self, pressure: f64, gps: Option<&GpsInfo>, now: Instant) -> bool {
+        if self.locked {
+            return false;
+        }
+
+        if self
+            .last_estimate
+            .is_some_and(|last| now.saturating_duration_since(last) > MAX_CALIBRATION_GAP)
+        {
+            self.restart();
+        }
+
+        let Some(gps) = gps.filter(|gps| gps.hdop.is_some_and(|hdop| hdop <= MAX_CALIBRATION_HDOP)) else {
+            return false;
+        };
+
+        let estimate = sea_level_pressure(pressure, f64::from(gps.altitude));
+        if !estimate.is_finite() {
+            return false;
+        }
+
+        if self.count > 0 && (estimate - self.mean()).abs() > MAX_CALIBRATION_SPREAD {
+            self.restart();
+        }
+
+        let window_start = *self.window_start.get_or_insert(now);
+        self.last_estimate = Some(now);
+        self.sum += estimate;
+        self.count += 1;
+
+        if now.saturating_duration_since(window_start) < CALIBRATION_WINDOW {
+            return false;
+        }
+
+        self.reference = self.mean();
+        self.locked = true;
+        true
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum / f64::from(self.count)
+    }
+
+    /// Drop the estimates so far, starting a new window.
+    fn restart(&mut self) {
+        self.window_start = None;
+        self.last_estimate = None;
+        self.sum = 0.0;
+        self.count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fix(altitude: f32, hdop: f32) -> GpsInfo {
+        GpsInfo {
+            latitude: 40.8,
+            longitude: -96.7,
+            altitude,
+            satellites: 10,
+            hdop: Some(hdop),
+            geoid_separation: None,
+            speed: Some(0.0),
+            course: None,
+        }
+    }
+
+    #[test]
+    fn reference_gives_gps_altitude() {
+        let reference = sea_level_pressure(87_500.0, 1200.0);
+
+        assert!((pressure_altitude(87_500.0, reference) - 1200.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn latches_after_steady_window() {
+        let start = Instant::now();
+        let mut calibration = SeaLevelCalibration::new();
+
+        let mut locked_at = None;
+        for second in 0..=40 {
+            let now = start + Duration::from_secs(second);
+            if calibration.update(87_500.0, Some(&fix(1200.0, 1.0)), now) {
+                locked_at = Some(second);
+            }
+        }
+
+        assert_eq!(locked_at, Some(CALIBRATION_WINDOW.as_secs()));
+        assert!((calibration.altitude(87_500.0) - 1200.0).abs() < 0.01);
+
+        // Later fixes, however different, don't move the reference
+        let later = start + Duration::from_secs(60);
+        assert!(!calibration.update(87_500.0, Some(&fix(1500.0, 1.0)), later));
+        assert!((calibration.altitude(87_500.0) - 1200.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn poor_fix_leaves_standard_reference() {
+        let start = Instant::now();
+        let mut calibration = SeaLevelCalibration::new();
+
+        for second in 0..=40 {
+            let now = start + Duration::from_secs(second);
+            assert!(!calibration.update(87_500.0, Some(&fix(1200.0, 5.0)), now));
+        }
+
+        assert!(!calibration.is_locked());
+        assert_eq!(calibration.reference(), STANDARD_SEA_LEVEL_PRESSURE);
+    }
+
+    #[test]
+    fn jump_or_gap_restarts_window() {
+        let start = Instant::now();
+        let mut calibration = SeaLevelCalibration::new();
+
+        for second in 0..20 {
+            calibration.update(87_500.0, Some(&fix(1200.0, 1.0)), start + Duration::from_secs(second));
+        }
+        // A 50 meter jump starts the window over
+        for second in 20..45 {
+            let now = start + Duration::from_secs(second);
+            assert!(!calibration.update(87_500.0, Some(&fix(1250.0, 1.0)), now));
+        }
+        // As does going without a fix
+        for second in 55..80 {
+            let now = start + Duration::from_secs(second);
+            assert!(!calibration.update(87_500.0, Some(&fix(1250.0, 1.0)), now));
+        }
+
+        assert!(!calibration.is_locked());
+    }
+}
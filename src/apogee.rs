@@ -0,0 +1,196 @@
+//! Detection of apogee from both the barometer and the GPS, so a single
+//! faulty sensor can't produce a confident apogee on its own.
+
+use std::time::{Duration, Instant};
+
+/// An altitude must rise this many meters above the lowest seen before its
+/// peak can count as apogee, so noise on the pad is never mistaken for it.
+const MIN_ASCENT: f64 = 100.0;
+/// How many meters the barometric altitude must fall below its peak before
+/// the payload counts as descending.
+const BARO_DESCENT_MARGIN: f64 = 10.0;
+/// How many meters the GPS altitude must fall below its peak before the
+/// payload counts as descending. GPS altitude is noisier than the
+/// barometer, so this is larger.
+const GPS_DESCENT_MARGIN: f64 = 25.0;
+/// The number of consecutive updates an altitude must stay below its peak
+/// for. At the normal send interval this is 1 second.
+const SUSTAINED_UPDATES: u32 = 4;
+/// The peaks of the two altitudes must be within this time of each other to
+/// agree. This covers the lag of the GPS behind the barometer.
+const AGREEMENT_WINDOW: Duration = Duration::from_secs(5);
+/// Once one sensor has found apogee, how long the other has to agree before
+/// apogee is reported with low confidence.
+const CONFIRMATION_WAIT: Duration = Duration::from_secs(5);
+
+/// How much an apogee can be trusted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApogeeConfidence {
+    /// Both the barometer and the GPS found apogee at the same time
+    High,
+    /// Only one sensor found apogee, or the two disagreed on when
+    Low,
+}
+
+/// Apogee, as found by an [`ApogeeDetector`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Apogee {
+    /// The peak altitude in meters, from the barometer if it found apogee
+    pub altitude: f64,
+    pub confidence: ApogeeConfidence,
+}
+
+/// Finds the peak of a single altitude.
+#[derive(Debug, Clone, Default)]
+struct PeakTracker {
+    lowest: Option<f64>,
+    peak: Option<(f64, Instant)>,
+    falling: u32,
+    found: bool,
+}
+
+impl PeakTracker {
+    /// Add the latest altitude, returning the peak if the altitude has only
+    /// now fallen far enough below it for long enough.
+    fn update(&mut self, altitude: f64, now: Instant, margin: f64) -> Option<(f64, Instant)> {
+        if self.found {
+            return None;
+        }
+
+        let lowest = *self.lowest.insert(self.lowest.map_or(altitude, |lowest| lowest.min(altitude)));
+
+        let (peak, at) = match self.peak {
+            Some(peak) if peak.0 > altitude => peak,
+            _ => {
+                self.peak = Some((altitude, now));
+                self.falling = 0;
+                return None;
+            }
+        };
+
+        if peak - lowest >= MIN_ASCENT && peak - altitude >= margin {
+            self.falling += 1;
+        } else {
+            self.falling = 0;
+        }
+
+        if self.falling < SUSTAINED_UPDATES {
+            return None;
+        }
+
+        self.found = true;
+        Some((peak, at))
+    }
+}
+
+/// Combines the barometric and GPS altitudes to find apogee.
+///
+/// Apogee is reported once, with high confidence if both sensors found a
+/// peak at about the same time. If only one sensor finds apogee, such as
+/// when the GPS has lost its fix, it is still reported after a short wait
+/// for the other, but with low confidence.
+#[derive(Debug, Clone, Default)]
+pub struct ApogeeDetector {
+    baro: PeakTracker,
+    gps: PeakTracker,
+    baro_peak: Option<(f64, Instant)>,
+    gps_peak: Option<(f64, Instant)>,
+    /// When the first sensor found apogee
+    first_found: Option<Instant>,
+    reported: bool,
+}
+
+impl ApogeeDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the latest barometric and GPS altitudes in meters, measured at
+    /// `now`, returning apogee once it has been found.
+    pub fn update(&mut self, baro: Option<f64>, gps: Option<f64>, now: Instant) -> Option<Apogee> {
+        if self.reported {
+            return None;
+        }
+
+        if let Some(peak) = baro.and_then(|baro| self.baro.update(baro, now, BARO_DESCENT_MARGIN)) {
+            self.baro_peak = Some(peak);
+            self.first_found.get_or_insert(now);
+        }
+        if let Some(peak) = gps.and_then(|gps| self.gps.update(gps, now, GPS_DESCENT_MARGIN)) {
+            self.gps_peak = Some(peak);
+            self.first_found.get_or_insert(now);
+        }
+
+        let confidence = match (self.baro_peak, self.gps_peak) {
+            (Some((_, baro_at)), Some((_, gps_at))) => {
+                let apart = if baro_at > gps_at { baro_at - gps_at } else { gps_at - baro_at };
+                if apart <= AGREEMENT_WINDOW {
+                    ApogeeConfidence::High
+                } else {
+                    ApogeeConfidence::Low
+                }
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                let waited = self
+                    .first_found
+                    .is_some_and(|found| now.saturating_duration_since(found) >= CONFIRMATION_WAIT);
+                if !waited {
+                    return None;
+                }
+                ApogeeConfidence::Low
+            }
+            (None, None) => return None,
+        };
+
+        let (altitude, _) = self.baro_peak.or(self.gps_peak)?;
+        self.reported = true;
+
+        Some(Apogee { altitude, confidence })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The update interval of the sending loop.
+    const STEP: Duration = Duration::from_millis(250);
+
+    /// An altitude climbing 25m each update until `peak`, then falling 20m
+    /// each update.
+    fn altitude(step: u32, peak: u32) -> f64 {
+        if step <= peak {
+            f64::from(step) * 25.0
+        } else {
+            f64::from(peak) * 25.0 - f64::from(step - peak) * 20.0
+        }
+    }
+
+    /// Fly with the barometer peaking after 10s, and the GPS peaking at
+    /// `gps_peak` updates if it has a fix, until apogee is found.
+    fn fly(gps_peak: Option<u32>) -> Apogee {
+        let start = Instant::now();
+        let mut detector = ApogeeDetector::new();
+
+        (0..200)
+            .find_map(|step| {
+                let gps = gps_peak.map(|peak| altitude(step, peak));
+                detector.update(Some(altitude(step, 40)), gps, start + STEP * step)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn agreement_gives_high_confidence() {
+        assert_eq!(fly(Some(40)), Apogee { altitude: 1000.0, confidence: ApogeeConfidence::High });
+    }
+
+    #[test]
+    fn disagreement_is_flagged() {
+        // The GPS peaks 8s after the barometer
+        assert_eq!(fly(Some(72)), Apogee { altitude: 1000.0, confidence: ApogeeConfidence::Low });
+
+        // The GPS has no fix at all
+        assert_eq!(fly(None), Apogee { altitude: 1000.0, confidence: ApogeeConfidence::Low });
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+
+use tracing::Level;
+
+use crate::{commands::FailsafeAction, recorder::RecordingLimits};
+
+const USAGE: &str = "\
+Usage: arowss [OPTIONS]
+
+Options:
+  -v, --verbose    Log everything, including trace messages
+  -q, --quiet      Only log warnings and errors
+      --raw        Include raw sensor values in telemetry
+      --thermal    Include electronics temperatures in telemetry
+      --computed   Include values computed from the sensors, such as
+                   density altitude, in telemetry
+      --loop-health
+                   Include how long each packet takes to build and send
+                   in telemetry
+      --low-power  Only read sensors once per packet to save power
+      --recovery   Only start the GPS and send position beacons, for
+                   recovering a damaged payload
+      --recording-limit <MIB>
+                   Only keep the most recent MIB mebibytes of the telemetry
+                   recording [default: 256]
+      --log-dir <DIR>
+                   Write log files to DIR rather than /var/log/arowss
+      --failsafe <ACTIONS>
+                   What to do when nothing is received from the ground for
+                   5 minutes, as a comma separated list of high-power and
+                   buzzer, or none [default: high-power]
+      --scenario <FILE>
+                   Send the packets in FILE, one JSON packet per line,
+                   before any live packets, for end to end testing
+  -h, --help       Print this help message";
+
+/// Options given on the command line.
+#[derive(Debug)]
+pub struct Args {
+    /// The maximum level of log messages to output
+    pub log_level: Level,
+    /// Whether raw sensor values are included in telemetry
+    pub raw_values: bool,
+    /// Whether electronics temperatures are included in telemetry
+    pub thermal: bool,
+    /// Whether values computed by virtual sensors are included in telemetry
+    pub computed: bool,
+    /// Whether the timing of the sending loop is included in telemetry
+    pub loop_health: bool,
+    /// Whether sensors are only read once per packet
+    pub low_power: bool,
+    /// Whether to boot in recovery mode, only sending GPS beacons
+    pub recovery: bool,
+    /// What to do when the uplink is lost
+    pub failsafe: Vec<FailsafeAction>,
+    /// A file of packets to send instead of live data until they run out
+    pub scenario: Option<PathBuf>,
+    /// Where to write log files, if not the default directory
+    pub log_dir: Option<PathBuf>,
+    /// How much of the telemetry recording is kept
+    pub recording_limits: RecordingLimits,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            log_level: Level::INFO,
+            raw_values: false,
+            thermal: false,
+            computed: false,
+            loop_health: false,
+            low_power: false,
+            recovery: false,
+            failsafe: vec![FailsafeAction::DisableHighPower],
+            scenario: None,
+            log_dir: None,
+            recording_limits: RecordingLimits::default(),
+        }
+    }
+}
+
+impl Args {
+    /// Parse the arguments given to this process.
+    ///
+    /// Logging is not set up yet when this runs, so problems are printed
+    /// directly to stderr.
+    pub fn parse() -> Self {
+        let mut args = Self::default();
+
+        let mut raw_args = std::env::args().skip(1);
+        while let Some(arg) = raw_args.next() {
+            match arg.as_str() {
+                "-v" | "--verbose" => args.log_level = Level::TRACE,
+                "-q" | "--quiet" => args.log_level = Level::WARN,
+                "--raw" => args.raw_values = true,
+                "--thermal" => args.thermal = true,
+                "--computed" => args.computed = true,
+                "--loop-health" => args.loop_health = true,
+                "--low-power" => args.low_power = true,
+                "--recovery" => args.recovery = true,
+                "--failsafe" => match raw_args.next().as_deref().map(parse_failsafe) {
+                    Some(Some(actions)) => args.failsafe = actions,
+                    _ => eprintln!("--failsafe requires a list of actions\n\n{USAGE}"),
+                },
+                "--scenario" => match raw_args.next() {
+                    Some(path) => args.scenario = Some(path.into()),
+                    None => eprintln!("--scenario requires a file\n\n{USAGE}"),
+                },
+                "--recording-limit" => match raw_args.next().and_then(|mib| mib.parse::<u64>().ok()) {
+                    Some(mib) if mib > 0 => args.recording_limits = RecordingLimits::from_total(mib * 1024 * 1024),
+                    _ => eprintln!("--recording-limit requires a number of mebibytes\n\n{USAGE}"),
+                },
+                "--log-dir" => match raw_args.next() {
+                    Some(dir) => args.log_dir = Some(dir.into()),
+                    None => eprintln!("--log-dir requires a directory\n\n{USAGE}"),
+                },
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    std::process::exit(0);
+                }
+                _ => eprintln!("Ignoring unknown argument {arg:?}\n\n{USAGE}"),
+            }
+        }
+
+        args
+    }
+}
+
+/// Parse a comma separated list of failsafe actions, where `none` is no
+/// actions at all.
+fn parse_failsafe(list: &str) -> Option<Vec<FailsafeAction>> {
+    if list == "none" {
+        return Some(Vec::new());
+    }
+
+    list.split(',').map(FailsafeAction::from_name).collect()
+}
@@ -0,0 +1,49 @@
+//! Compare the telemetry recorded on the payload with what the ground
+//! received, reporting how much was lost and when.
+
+use std::{fs, process::ExitCode, time::Duration};
+
+use arowss::{link_report::LinkReport, recording::read_records, FrameScanner};
+
+const USAGE: &str = "\
+Usage: link_report <RECORDING> <CAPTURE>
+
+Arguments:
+  <RECORDING>  Name of the recording made on the payload, without the
+               segment number, e.g. telemetry_1a2b3c4d_2026-10-16T13:00:00Z
+  <CAPTURE>    File of the raw bytes received from the ground radio";
+
+/// The length of the windows reception is reported over.
+const WINDOW: Duration = Duration::from_secs(10);
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [recording, capture] = args.as_slice() else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    let air = match read_records(recording) {
+        Ok(records) => records,
+        Err(e) => {
+            eprintln!("Could not read recording {recording}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let sent = air.len();
+    let air: Vec<_> = air.into_iter().filter_map(|(seq, packet)| Some((seq?, packet))).collect();
+    if air.len() < sent {
+        eprintln!("Skipped {} packets recorded without sequence numbers", sent - air.len());
+    }
+
+    let ground = match fs::read(capture) {
+        Ok(bytes) => FrameScanner::new().push(&bytes).into_iter().filter_map(Result::ok).collect::<Vec<_>>(),
+        Err(e) => {
+            eprintln!("Could not read capture {capture}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    print!("{}", LinkReport::compare(&air, &ground, WINDOW));
+    ExitCode::SUCCESS
+}
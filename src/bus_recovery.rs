@@ -0,0 +1,113 @@
+//! Detection of a locked up I²C bus, where a device holding SDA low stops
+//! every other device on the bus from responding.
+
+use std::time::{Duration, Instant};
+
+/// Every device on the bus must have been failing for this long before the
+/// bus is assumed to be locked up. This is longer than the sensors take to
+/// start, so a bus is never recovered just because the payload has booted.
+const LOCKUP_TIME: Duration = Duration::from_secs(10);
+/// The time between recovery attempts, so a bus which can't be recovered
+/// isn't reset continually.
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+/// Recovery is given up on after this many attempts, until a device on the
+/// bus responds again.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// A change in the state of the bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusEvent {
+    /// The bus appears locked up, and recovery should be attempted. Holds
+    /// the number of the attempt, starting from 1.
+    Locked(u32),
+    /// A device responded again after the bus was recovered.
+    Recovered,
+    /// The last recovery attempt failed, and no more will be made.
+    GaveUp,
+}
+
+/// Watches the health of the devices on an I²C bus, deciding when the bus
+/// should be recovered.
+///
+/// One failed device is left to its own task to reinitialize. Only when
+/// every device has failed at once is the bus itself suspected.
+#[derive(Debug, Clone, Default)]
+pub struct BusRecovery {
+    failing_since: Option<Instant>,
+    last_attempt: Option<Instant>,
+    attempts: u32,
+    gave_up: bool,
+}
+
+impl BusRecovery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update with whether any device on the bus is currently responding.
+    pub fn update(&mut self, any_healthy: bool, now: Instant) -> Option<BusEvent> {
+        if any_healthy {
+            let recovered = self.attempts > 0;
+            *self = Self::default();
+            return recovered.then_some(BusEvent::Recovered);
+        }
+
+        let failing_since = *self.failing_since.get_or_insert(now);
+        if self.gave_up || now.saturating_duration_since(failing_since) < LOCKUP_TIME {
+            return None;
+        }
+
+        if self
+            .last_attempt
+            .is_some_and(|last| now.saturating_duration_since(last) < RETRY_INTERVAL)
+        {
+            return None;
+        }
+
+        if self.attempts >= MAX_ATTEMPTS {
+            self.gave_up = true;
+            return Some(BusEvent::GaveUp);
+        }
+
+        self.attempts += 1;
+        self.last_attempt = Some(now);
+        Some(BusEvent::Locked(self.attempts))
+    }
+
+    /// The number of recovery attempts made since a device last responded.
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simultaneous_failure_recovers_bus() {
+        let start = Instant::now();
+        let mut bus = BusRecovery::new();
+        assert_eq!(bus.update(true, start), None);
+
+        // Every device stops responding at once
+        assert_eq!(bus.update(false, start), None);
+        assert_eq!(bus.update(false, start + LOCKUP_TIME - Duration::from_millis(1)), None);
+        assert_eq!(bus.update(false, start + LOCKUP_TIME), Some(BusEvent::Locked(1)));
+
+        // Attempts are spaced out, and limited
+        let mut now = start + LOCKUP_TIME;
+        assert_eq!(bus.update(false, now + Duration::from_secs(1)), None);
+        for attempt in 2..=MAX_ATTEMPTS {
+            now += RETRY_INTERVAL;
+            assert_eq!(bus.update(false, now), Some(BusEvent::Locked(attempt)));
+        }
+        now += RETRY_INTERVAL;
+        assert_eq!(bus.update(false, now), Some(BusEvent::GaveUp));
+        assert_eq!(bus.update(false, now + RETRY_INTERVAL), None);
+
+        // A device responding again ends the lockup
+        assert_eq!(bus.update(true, now + RETRY_INTERVAL), Some(BusEvent::Recovered));
+        assert_eq!(bus.attempts(), 0);
+    }
+}
@@ -0,0 +1,112 @@
+//! A single GPS fix captured at full precision on request, for surveying the
+//! pad or marking a recovery waypoint.
+//!
+//! The fix is too long for one info message, so it is sent as two: one
+//! holding the position, created by [`CapturedFix::position_message`], and
+//! one holding the quality of the fix, created by
+//! [`CapturedFix::quality_message`].
+
+/// The prefix of the info message holding the position of a captured fix.
+const POSITION_PREFIX: &str = "FIX ";
+/// The prefix of the info message holding the quality of a captured fix.
+const QUALITY_PREFIX: &str = "FIXQ ";
+/// Written in place of a value the receiver didn't give.
+const MISSING: &str = "-";
+
+/// A GPS fix with all of the metadata the receiver gave for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedFix {
+    /// Latitude in decimal degrees
+    pub latitude: f64,
+    /// Longitude in decimal degrees
+    pub longitude: f64,
+    /// Altitude above mean sea level in meters
+    pub altitude: f32,
+    pub satellites: u8,
+    pub hdop: Option<f32>,
+    /// The kind of fix, as named by the receiver, e.g. `DGps`
+    pub fix_type: String,
+    /// The UTC time of the fix, as `HH:MM:SS.sss`
+    pub utc: Option<String>,
+}
+
+impl CapturedFix {
+    /// Create the info message holding the position, to a precision of
+    /// roughly 0.1 mm.
+    pub fn position_message(&self) -> String {
+        format!(
+            "{POSITION_PREFIX}{:.9} {:.9} {:.2}",
+            self.latitude, self.longitude, self.altitude
+        )
+    }
+
+    /// Create the info message holding the quality of the fix.
+    pub fn quality_message(&self) -> String {
+        let hdop = self.hdop.map_or(MISSING.to_string(), |hdop| format!("{hdop:.2}"));
+        let utc = self.utc.as_deref().unwrap_or(MISSING);
+
+        format!("{QUALITY_PREFIX}{} {hdop} {} {utc}", self.satellites, self.fix_type)
+    }
+
+    /// Read a captured fix back from the two info messages created by
+    /// [`Self::position_message`] and [`Self::quality_message`].
+    pub fn parse(position: &str, quality: &str) -> Option<Self> {
+        let mut position = position.strip_prefix(POSITION_PREFIX)?.split(' ');
+        let latitude = position.next()?.parse().ok()?;
+        let longitude = position.next()?.parse().ok()?;
+        let altitude = position.next()?.parse().ok()?;
+
+        let mut quality = quality.strip_prefix(QUALITY_PREFIX)?.split(' ');
+        let satellites = quality.next()?.parse().ok()?;
+        let hdop = match quality.next()? {
+            MISSING => None,
+            hdop => Some(hdop.parse().ok()?),
+        };
+        let fix_type = quality.next()?.to_string();
+        let utc = match quality.next()? {
+            MISSING => None,
+            utc => Some(utc.to_string()),
+        };
+
+        Some(Self {
+            latitude,
+            longitude,
+            altitude,
+            satellites,
+            hdop,
+            fix_type,
+            utc,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captured_fix_keeps_full_precision_and_metadata() {
+        let fix = CapturedFix {
+            latitude: 40.820_123_456,
+            longitude: -96.705_654_321,
+            altitude: 362.47,
+            satellites: 11,
+            hdop: Some(0.82),
+            fix_type: "DGps".to_string(),
+            utc: Some("17:04:31.250".to_string()),
+        };
+
+        let position = fix.position_message();
+        let quality = fix.quality_message();
+        assert_eq!(position, "FIX 40.820123456 -96.705654321 362.47");
+        assert_eq!(quality, "FIXQ 11 0.82 DGps 17:04:31.250");
+
+        // Every digit and all of the metadata survive the messages
+        let parsed = CapturedFix::parse(&position, &quality).unwrap();
+        assert_eq!(parsed, fix);
+
+        let sparse = CapturedFix { hdop: None, utc: None, ..fix };
+        assert_eq!(sparse.quality_message(), "FIXQ 11 - DGps -");
+        assert_eq!(CapturedFix::parse(&sparse.position_message(), &sparse.quality_message()), Some(sparse));
+    }
+}
@@ -0,0 +1,81 @@
+//! Sources of the current time.
+//!
+//! Time dependent logic asks a [`Clock`] for the time rather than reading the
+//! system clock directly, so it can be driven deterministically by a
+//! [`FakeClock`] instead of waiting on the real one.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+
+/// A source of the current time.
+pub trait Clock {
+    /// The current monotonic time, for measuring intervals.
+    fn now(&self) -> Instant;
+
+    /// The current wall clock time, in milliseconds since the Unix epoch.
+    fn unix_millis(&self) -> i64;
+}
+
+/// The real system clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn unix_millis(&self) -> i64 {
+        Utc::now().timestamp_millis()
+    }
+}
+
+/// A clock which only moves when told to.
+///
+/// Clones share the same time, so a clone can be handed to the code under
+/// test and the original advanced from outside.
+#[derive(Debug, Clone)]
+pub struct FakeClock {
+    state: Arc<Mutex<FakeTime>>,
+}
+
+#[derive(Debug)]
+struct FakeTime {
+    now: Instant,
+    unix_millis: i64,
+}
+
+impl FakeClock {
+    /// Create a clock stopped at the given wall clock time, in milliseconds
+    /// since the Unix epoch. The monotonic time starts at the real current
+    /// time, as an [`Instant`] can't be created from nothing.
+    pub fn new(unix_millis: i64) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(FakeTime {
+                now: Instant::now(),
+                unix_millis,
+            })),
+        }
+    }
+
+    /// Move the clock forwards.
+    pub fn advance(&self, by: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.now += by;
+        state.unix_millis += by.as_millis() as i64;
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Instant {
+        self.state.lock().unwrap().now
+    }
+
+    fn unix_millis(&self) -> i64 {
+        self.state.lock().unwrap().unix_millis
+    }
+}
@@ -1,53 +1,559 @@
-use std::{fs, io::Write, sync::mpsc::Sender};
+use std::{collections::HashMap, fs, io::Write, sync::mpsc::Sender, time::Duration};
 
+use arowss::{clock::Clock, diagnostics::Diagnostics, events::{Event, EventCode, Message}, rfd::{self, RadioSetting}, timesync::format_pong, uplink::{COMMAND_FRAME_BYTES, COMMAND_MAGIC, REQUEST_RETRANSMIT}, utils::crc8};
 use num_derive::{FromPrimitive, ToPrimitive};
 use num_traits::FromPrimitive;
 use rppal::gpio::OutputPin;
+use tokio::{sync::watch, time::Instant};
+use tracing::{info, warn};
+
+use crate::health::SystemHealth;
+
+/// How long the RFD-900x downlink stays silent after a
+/// [`Commands::BlankTransmit`] command.
+pub const TRANSMIT_BLANK_DURATION: Duration = Duration::from_secs(60);
+
+/// If nothing at all is received from the ground for this long, the link is
+/// considered lost and the failsafe actions are taken.
+pub const LINK_LOSS_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// An action which can be taken automatically when the uplink is lost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailsafeAction {
+    /// Disable the Taisync radio, as nobody can command it any more
+    DisableHighPower,
+    /// Start the recovery buzzer
+    StartBuzzer,
+}
+
+impl FailsafeAction {
+    /// The action with the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "high-power" => Some(Self::DisableHighPower),
+            "buzzer" => Some(Self::StartBuzzer),
+            _ => None,
+        }
+    }
+}
+
+/// Watches for the uplink going silent.
+///
+/// Any byte received counts as uplink activity, not just valid commands, so
+/// a ground station which simply isn't sending commands doesn't look like a
+/// lost link. The watchdog is armed from boot, so a link which never comes
+/// up is treated as lost too.
+#[derive(Debug, Clone)]
+pub struct LinkWatchdog {
+    last_uplink: Instant,
+    lost: bool,
+}
+
+impl LinkWatchdog {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            last_uplink: now,
+            lost: false,
+        }
+    }
+
+    /// Record that something was received from the ground, returning
+    /// whether the link had been lost until now.
+    pub fn received(&mut self, now: Instant) -> bool {
+        self.last_uplink = now;
+        std::mem::replace(&mut self.lost, false)
+    }
+
+    /// Check whether the link has been silent for [`LINK_LOSS_TIMEOUT`],
+    /// returning `true` only the first time it is found to be lost.
+    pub fn check(&mut self, now: Instant) -> bool {
+        if self.lost || now.duration_since(self.last_uplink) <= LINK_LOSS_TIMEOUT {
+            return false;
+        }
+
+        self.lost = true;
+        true
+    }
+}
+
+/// A repeat of the same command within this long is ignored, so a noisy link
+/// or stuck key can't cause a storm of actuations.
+const COMMAND_REPEAT_WINDOW: Duration = Duration::from_secs(1);
+/// Destructive commands can only be run once within this long.
+const DESTRUCTIVE_COMMAND_COOLDOWN: Duration = Duration::from_secs(30);
 
 /// Commands which the air side code must respond to from the ground.
-#[derive(FromPrimitive, ToPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromPrimitive, ToPrimitive)]
 #[repr(u8)]
 #[non_exhaustive]
 pub enum Commands {
+    /// Start pulsing the recovery buzzer
+    StartBuzzer = 60,
+    /// Stop the recovery buzzer
+    StopBuzzer = 61,
+
     /// Enable the Taisync radio
     EnableHighPower = 70,
     /// Disable the Taisync radio
     DisableHighPower = 80,
 
+    /// Stop transmitting on the RFD-900x for [`TRANSMIT_BLANK_DURATION`]
+    BlankTransmit = 90,
+    /// Resume transmitting on the RFD-900x before the blanking window ends
+    ResumeTransmit = 91,
+
     /// Forcibly reboot without waiting for any processes to finish
     Reboot = 100,
     /// Restart the stream process
     RestartStream = 101,
     /// Get the IP address
     GetIpAddress = 102,
+    /// Reply with the time the command was received, to synchronize clocks
+    Ping = 103,
+    /// Get free disk space, memory, CPU temperature, uptime and load
+    SystemHealth = 104,
+    /// Get a summary of the active configuration, over several info
+    /// messages
+    GetConfig = 105,
+    /// Check every subsystem, replying with a bitmask of those working
+    RunDiagnostics = 106,
+    /// Use the current pressure and orientation as the baselines for the
+    /// height and rotation sent, only while stationary
+    TareSensors = 107,
+
+    /// Re-send the configuration commands to the primary GPS
+    ReconfigureGps = 110,
+    /// Downlink the next good primary GPS fix at full precision, with all
+    /// of its metadata
+    CaptureFix = 111,
+
+    /// Reply without doing anything, to test the uplink and downlink
+    Echo = 120,
+    /// Send the frame with the sequence number given by the argument again,
+    /// if it held data which isn't realtime and is still kept
+    RequestRetransmit = REQUEST_RETRANSMIT,
+
+    /// Sample the BMP581 once per second
+    BaroRate1Hz = 130,
+    /// Sample the BMP581 5 times per second
+    BaroRate5Hz = 131,
+    /// Sample the BMP581 10 times per second
+    BaroRate10Hz = 132,
+    /// Sample the BMP581 20 times per second
+    BaroRate20Hz = 133,
+
+    /// Fix the primary GPS once per second
+    GpsRate1Hz = 140,
+    /// Fix the primary GPS twice per second. Any faster would overrun its
+    /// serial link.
+    GpsRate2Hz = 141,
+
+    /// Start recording telemetry to the SD card, in a new file
+    RecorderEnable = 150,
+    /// Stop recording telemetry to the SD card
+    RecorderDisable = 151,
+    /// Close the current recording file and start a new one, replying with
+    /// the new file's name
+    RecorderRotate = 152,
+
+    /// Set the general purpose output given by the argument. Interlocked
+    /// outputs can only be set while armed.
+    SetOutput = 160,
+    /// Allow the interlocked general purpose outputs to be set
+    ArmOutputs = 161,
+    /// Clear the general purpose output given by the argument
+    ClearOutput = 170,
+    /// Clear every interlocked general purpose output, and stop them being
+    /// set until armed again
+    DisarmOutputs = 171,
+
+    /// Change the air rate of both RFD-900x radios to the one in
+    /// [`rfd::AIR_RATES`] indexed by the argument, pending confirmation
+    SetAirRate = 180,
+    /// Change the transmit power of the RFD-900x to the argument in dBm,
+    /// pending confirmation
+    SetTxPower = 181,
+    /// Keep the last radio change, which is otherwise reverted after
+    /// [`rfd::CONFIRM_TIMEOUT`]
+    ConfirmRadio = 182,
+}
+
+impl Commands {
+    /// The minimum time between two runs of this command. Different
+    /// commands, or the same command with different arguments, don't limit
+    /// each other.
+    fn min_interval(&self) -> Duration {
+        match self {
+            Commands::Reboot | Commands::RestartStream => DESTRUCTIVE_COMMAND_COOLDOWN,
+            _ => COMMAND_REPEAT_WINDOW,
+        }
+    }
+}
+
+/// A command received from the ground, along with its argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Command {
+    pub kind: Commands,
+    /// The argument of the command, such as which output to set. Commands
+    /// which take no argument ignore it.
+    pub arg: u8,
+}
+
+/// A pin driven by commands. Commands only drive pins through this, so they
+/// can be run without the GPIO hardware.
+pub trait Output {
+    /// Drive the pin high or low.
+    fn write(&mut self, high: bool);
+}
+
+impl Output for OutputPin {
+    fn write(&mut self, high: bool) {
+        OutputPin::write(self, high.into());
+    }
+}
+
+/// A general purpose output pin, such as an LED or secondary relay, which
+/// can be set and cleared from the ground.
+pub struct NamedOutput<P: Output> {
+    pub name: &'static str,
+    /// The pin, if it could be set up. A missing pin keeps its place, so
+    /// the commands for the outputs after it don't change.
+    pub pin: Option<P>,
+    /// Whether the output can only be set while the outputs are armed, such
+    /// as one enabling a deployment charge
+    pub interlocked: bool,
+}
+
+/// The prefix of the info messages answering [`Commands::GetConfig`].
+const CONFIG_PREFIX: &str = "CFG";
+/// Bytes of each configuration message kept for the prefix and page
+/// numbers.
+const CONFIG_PAGE_HEADER_BYTES: usize = 12;
+
+/// Pack the entries of the configuration summary into numbered info
+/// messages of at most `max_bytes`, so none of them are truncated.
+fn config_pages(entries: &[String], max_bytes: usize) -> Vec<String> {
+    let max_body = max_bytes.saturating_sub(CONFIG_PAGE_HEADER_BYTES);
+
+    let mut pages = Vec::new();
+    let mut page = String::new();
+    for entry in entries {
+        if !page.is_empty() && page.len() + 2 + entry.len() > max_body {
+            pages.push(std::mem::take(&mut page));
+        }
+        if !page.is_empty() {
+            page.push_str("; ");
+        }
+        page.push_str(entry);
+    }
+    if !page.is_empty() {
+        pages.push(page);
+    }
+
+    let count = pages.len();
+    pages
+        .into_iter()
+        .enumerate()
+        .map(|(i, page)| format!("{CONFIG_PREFIX} {}/{count} {page}", i + 1))
+        .collect()
+}
+
+/// A request to the primary GPS task.
+#[derive(Debug, Clone, Copy)]
+pub enum GpsRequest {
+    /// Re-send the configuration commands
+    Reconfigure,
+    /// Change the fix rate, in hertz
+    SetRate(u32),
+    /// Send the next good fix at full precision
+    CaptureFix,
+}
+
+/// A request to the telemetry recorder, which is owned by the sending task.
+#[derive(Debug, Clone, Copy)]
+pub enum RecorderRequest {
+    /// Start recording, in a new file
+    Enable,
+    /// Stop recording
+    Disable,
+    /// Close the current file and start a new one
+    Rotate,
+}
+
+/// A request to change the RFD-900x radios, which the sending task owns the
+/// serial port of.
+#[derive(Debug, Clone, Copy)]
+pub enum RadioRequest {
+    /// Change a setting, for the command given, pending confirmation
+    Change(RadioSetting, Commands),
+    /// Keep the last change
+    Confirm,
+}
+
+/// Whether the primary GPS can output its sentences `rate` times per second
+/// without overrunning its serial link, with 10 bits per byte for 8N1
+/// framing.
+fn gps_rate_supported(rate: u32) -> bool {
+    rate * crate::GPS_BYTES_PER_FIX <= crate::GPS_BAUD / 10
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum ParseErr {
     #[error("Command is not valid")]
     Invalid,
+    #[error("Command was repeated too soon, ignoring")]
+    RateLimited,
+}
+
+/// Add a byte received from the ground to the command buffer, returning the
+/// command once a complete and valid frame has been received.
+///
+/// Each buffer must hold a frame as laid out in [`arowss::uplink`]. If the
+/// buffer violates this at any time, or its checksum doesn't match, it is
+/// discarded up to the next magic byte in it, so a frame which starts inside
+/// noise or a partial frame isn't lost. A valid frame holding an unknown
+/// command is discarded.
+pub fn parse_command_buffer(buf: &mut Vec<u8>, byte: u8) -> Option<Command> {
+    // Anything before the start of a frame is noise
+    if buf.is_empty() && byte != COMMAND_MAGIC {
+        return None;
+    }
+
+    buf.push(byte);
+
+    // The command may not be a space, and the last byte must be one. The
+    // argument and checksum may be anything, so every argument can be sent.
+    let valid = match buf.len() {
+        1 | 3 | 4 => true,
+        2 => byte != b' ',
+        COMMAND_FRAME_BYTES => byte == b' ',
+        _ => false,
+    };
+
+    if !valid {
+        warn!("Buffer invalid: {:?}", buf);
+        resync(buf);
+        return None;
+    }
+
+    if buf.len() < COMMAND_FRAME_BYTES {
+        return None;
+    }
+
+    info!("Got command {:?}", buf);
+
+    let (data, arg, check) = (buf[1], buf[2], buf[3]);
+    let new_cksum = crc8(&[COMMAND_MAGIC, data, arg]);
+    if check != new_cksum {
+        warn!(
+            "Checksums do not match ({} != {}), discarding packet",
+            check,
+            new_cksum
+        );
+        resync(buf);
+        return None;
+    }
+    buf.clear();
+
+    let Some(kind) = Commands::from_u8(data) else {
+        warn!("Unknown command {data}, discarding packet");
+        return None;
+    };
+
+    Some(Command { kind, arg })
+}
+
+/// Discard an invalid buffer up to the next magic byte in it, parsing the
+/// rest again as the start of a new frame.
+fn resync(buf: &mut Vec<u8>) {
+    let rest = match buf.iter().skip(1).position(|&byte| byte == COMMAND_MAGIC) {
+        Some(start) => buf.split_off(start + 1),
+        None => Vec::new(),
+    };
+    buf.clear();
+
+    // The rest is shorter than a frame, so it can't complete a command
+    for byte in rest {
+        parse_command_buffer(buf, byte);
+    }
 }
 
 // Struct containing items which need to be modified by ground commands.
-pub struct CommandParser {
-    pub relay_pin: OutputPin,
-    pub info_sender: Sender<String>,
+pub struct CommandParser<C: Clock, P: Output> {
+    /// The high power relay, if its pin could be set up.
+    pub relay_pin: Option<P>,
+    pub info_sender: Sender<Message>,
+    /// The time until which the RFD-900x downlink should be silent.
+    pub blank_until: watch::Sender<Option<Instant>>,
+    /// Whether the recovery buzzer should be sounding.
+    pub buzzer: watch::Sender<bool>,
+    /// Requests to the primary GPS task.
+    pub gps: Sender<GpsRequest>,
+    /// The rate the BMP581 is sampled at, in hertz.
+    pub baro_rate: watch::Sender<u32>,
+    /// General purpose outputs, in the order of their commands.
+    pub outputs: Vec<NamedOutput<P>>,
+    /// Whether the interlocked outputs can be set.
+    pub armed: bool,
+    /// Requests to the telemetry recorder.
+    pub recorder: Sender<RecorderRequest>,
+    /// Diagnostics for the sending task to complete and downlink.
+    pub diagnostics: Sender<Diagnostics>,
+    /// Requests to tare the sensors, which the sending task has the
+    /// readings for.
+    pub tare: Sender<()>,
+    /// Sequence numbers of frames the ground has asked for again.
+    pub retransmit: Sender<u8>,
+    /// Requests to change the radios.
+    pub radio: Sender<RadioRequest>,
+    /// What to do when the uplink is lost.
+    pub failsafe: Vec<FailsafeAction>,
+    /// Whether the sensors are read once per packet, in which case their
+    /// rates can't be changed.
+    pub low_power: bool,
+    /// The entries of the configuration summary sent for
+    /// [`Commands::GetConfig`].
+    pub config: Vec<String>,
+    /// When each command was last run, for rate limiting.
+    pub last_run: HashMap<Command, Instant>,
+    /// Where the time used for rate limiting and replies comes from.
+    pub clock: C,
 }
 
-impl CommandParser {
-    pub async fn parse_command(&mut self, data: u8) -> Result<(), ParseErr> {
-        let Some(command) = Commands::from_u8(data) else {
-            return Err(ParseErr::Invalid)
+impl<C: Clock, P: Output> CommandParser<C, P> {
+    /// The current time, as used by tokio timers.
+    pub fn now(&self) -> Instant {
+        Instant::from_std(self.clock.now())
+    }
+
+    /// Take the failsafe actions after the uplink has been lost.
+    pub fn run_failsafe(&mut self) {
+        let _ = self.info_sender.send(EventCode::LinkLost.into());
+
+        for action in &self.failsafe {
+            warn!("Uplink lost, failsafe: {action:?}");
+            match action {
+                FailsafeAction::DisableHighPower => match self.relay_pin.as_mut() {
+                    Some(relay) => {
+                        relay.write(false);
+                        let _ = self.info_sender.send(EventCode::RelayDisabled.into());
+                    }
+                    None => warn!("Relay is not available to disable"),
+                },
+                FailsafeAction::StartBuzzer => {
+                    let _ = self.buzzer.send(true);
+                    let _ = self.info_sender.send(EventCode::BuzzerStarted.into());
+                }
+            }
+        }
+    }
+
+    /// Reject a command, telling the ground it was not run.
+    fn reject(&mut self, command: Commands, reason: &str) {
+        let data = command as u8;
+        warn!("Rejected command {data}: {reason}");
+        let _ = self.info_sender.send(Event::with_arg(EventCode::CommandRejected, data.into()).into());
+    }
+
+    fn set_baro_rate(&mut self, command: Commands, rate: u32) {
+        if self.low_power {
+            self.reject(command, "sensors are in low power mode");
+            return;
+        }
+
+        // The BMP581 task acknowledges once it has changed the rate
+        let _ = self.baro_rate.send(rate);
+    }
+
+    fn set_gps_rate(&mut self, command: Commands, rate: u32) {
+        if !gps_rate_supported(rate) {
+            self.reject(command, "GPS serial link is too slow for this rate");
+            return;
+        }
+
+        // The GPS task acknowledges once it has changed the rate
+        let _ = self.gps.send(GpsRequest::SetRate(rate));
+    }
+
+    /// Check that the same command, with the same argument, hasn't been run
+    /// within its minimum interval, recording that it is being run now if
+    /// so.
+    fn rate_limit(&mut self, command: Command) -> Result<(), ParseErr> {
+        let now = self.now();
+        if let Some(last) = self.last_run.get(&command)
+            && now.duration_since(*last) < command.kind.min_interval()
+        {
+            return Err(ParseErr::RateLimited);
+        }
+        self.last_run.insert(command, now);
+
+        Ok(())
+    }
+
+    /// Drive the high power relay, rejecting the command if its pin couldn't
+    /// be set up.
+    fn set_relay(&mut self, command: Commands, high: bool) {
+        let Some(relay) = self.relay_pin.as_mut() else {
+            self.reject(command, "relay is not available");
+            return;
         };
 
-        match command {
-            Commands::EnableHighPower => {
-                self.relay_pin.set_high();
-                let _ = self.info_sender.send("Relay enabled".to_string());
+        relay.write(high);
+        let code = if high { EventCode::RelayEnabled } else { EventCode::RelayDisabled };
+        let _ = self.info_sender.send(code.into());
+    }
+
+    /// Set or clear one of the general purpose outputs.
+    fn set_output(&mut self, command: Commands, index: usize, high: bool) -> Result<(), ParseErr> {
+        if index >= self.outputs.len() {
+            return Err(ParseErr::Invalid);
+        }
+
+        let output = &mut self.outputs[index];
+        let name = output.name;
+        if high && output.interlocked && !self.armed {
+            self.reject(command, &format!("output {name} is not armed"));
+            return Ok(());
+        }
+        let Some(pin) = output.pin.as_mut() else {
+            self.reject(command, &format!("output {name} is not available"));
+            return Ok(());
+        };
+
+        pin.write(high);
+        let code = if high { EventCode::OutputSet } else { EventCode::OutputCleared };
+
+        info!("Output {name} {}", if high { "set" } else { "cleared" });
+        let _ = self.info_sender.send(Event::with_arg(code, index as u32).into());
+
+        Ok(())
+    }
+
+    pub async fn parse_command(&mut self, command: Command) -> Result<(), ParseErr> {
+        self.rate_limit(command)?;
+        let now = self.now();
+
+        match command.kind {
+            Commands::StartBuzzer => {
+                let _ = self.buzzer.send(true);
+                let _ = self.info_sender.send(EventCode::BuzzerStarted.into());
+            }
+            Commands::StopBuzzer => {
+                let _ = self.buzzer.send(false);
+                let _ = self.info_sender.send(EventCode::BuzzerStopped.into());
             }
-            Commands::DisableHighPower => {
-                self.relay_pin.set_low();
-                let _ = self.info_sender.send("Relay disabled".to_string());
+            Commands::EnableHighPower => self.set_relay(command.kind, true),
+            Commands::DisableHighPower => self.set_relay(command.kind, false),
+            Commands::BlankTransmit => {
+                let _ = self.blank_until.send(Some(now + TRANSMIT_BLANK_DURATION));
+                let _ = self.info_sender.send(Event::with_arg(
+                    EventCode::TransmitBlanked,
+                    TRANSMIT_BLANK_DURATION.as_secs() as u32,
+                ).into());
+            }
+            Commands::ResumeTransmit => {
+                let _ = self.blank_until.send(None);
+                let _ = self.info_sender.send(EventCode::TransmitResumed.into());
             }
             Commands::Reboot => {
                 if let Ok(mut reboot_file) = fs::File::create("/proc/sysrq-trigger") {
@@ -59,19 +565,478 @@ impl CommandParser {
                     .arg("restart")
                     .arg("streaming.service")
                     .spawn();
-                let _ = self.info_sender.send("Restarted streaming service".to_string());
+                let _ = self.info_sender.send(EventCode::StreamRestarted.into());
             }
             Commands::GetIpAddress => {
                 if let Ok(ip) = std::process::Command::new("hostname")
                     .arg("-I")
                     .output()
                 {
-                    let _ = self.info_sender.send(String::from_utf8_lossy(&ip.stdout).to_string());
+                    let _ = self.info_sender.send(String::from_utf8_lossy(&ip.stdout).to_string().into());
                 }
             }
+            Commands::Ping => {
+                let received = self.clock.unix_millis();
+                let _ = self.info_sender.send(format_pong(received).into());
+            }
+            Commands::SystemHealth => {
+                let _ = self.info_sender.send(SystemHealth::gather().to_string().into());
+            }
+            Commands::GetConfig => {
+                for page in config_pages(&self.config, crate::MAX_INFO_BYTES) {
+                    let _ = self.info_sender.send(page.into());
+                }
+            }
+            Commands::RunDiagnostics => {
+                // The sending task fills in the other subsystems from the
+                // data it already has, so nothing is disturbed by checking
+                let mut diagnostics = Diagnostics::new();
+                diagnostics.set(Diagnostics::RELAY, self.relay_pin.is_some());
+                let _ = self.diagnostics.send(diagnostics);
+            }
+            Commands::TareSensors => {
+                // The sending task acknowledges once it has checked the
+                // readings are good enough to tare to
+                let _ = self.tare.send(());
+            }
+            Commands::ReconfigureGps => {
+                // The GPS task acknowledges once it has reconfigured
+                let _ = self.gps.send(GpsRequest::Reconfigure);
+            }
+            Commands::CaptureFix => {
+                // The GPS task replies with the fix once it has a good one
+                let _ = self.gps.send(GpsRequest::CaptureFix);
+            }
+            Commands::RequestRetransmit => {
+                // The frame itself is the reply, and the sending task
+                // rejects the request if it no longer has the frame
+                let _ = self.retransmit.send(command.arg);
+            }
+            Commands::Echo => {
+                let _ = self.info_sender.send(Event::with_arg(EventCode::Echo, command.arg.into()).into());
+            }
+            Commands::RecorderEnable => {
+                let _ = self.recorder.send(RecorderRequest::Enable);
+                let _ = self.info_sender.send(EventCode::RecorderEnabled.into());
+            }
+            Commands::RecorderDisable => {
+                let _ = self.recorder.send(RecorderRequest::Disable);
+                let _ = self.info_sender.send(EventCode::RecorderDisabled.into());
+            }
+            Commands::RecorderRotate => {
+                // The sending task replies with the new file once it has
+                // switched to it
+                let _ = self.recorder.send(RecorderRequest::Rotate);
+            }
+            Commands::BaroRate1Hz => self.set_baro_rate(command.kind, 1),
+            Commands::BaroRate5Hz => self.set_baro_rate(command.kind, 5),
+            Commands::BaroRate10Hz => self.set_baro_rate(command.kind, 10),
+            Commands::BaroRate20Hz => self.set_baro_rate(command.kind, 20),
+            Commands::GpsRate1Hz => self.set_gps_rate(command.kind, 1),
+            Commands::GpsRate2Hz => self.set_gps_rate(command.kind, 2),
+            Commands::SetOutput => self.set_output(command.kind, command.arg.into(), true)?,
+            Commands::ClearOutput => self.set_output(command.kind, command.arg.into(), false)?,
+            Commands::ArmOutputs => {
+                info!("Outputs armed");
+                self.armed = true;
+                let _ = self.info_sender.send(EventCode::OutputsArmed.into());
+            }
+            Commands::DisarmOutputs => {
+                info!("Outputs disarmed");
+                self.armed = false;
+                for output in self.outputs.iter_mut().filter(|output| output.interlocked) {
+                    if let Some(pin) = output.pin.as_mut() {
+                        pin.write(false);
+                    }
+                }
+                let _ = self.info_sender.send(EventCode::OutputsDisarmed.into());
+            }
+            Commands::SetAirRate => match rfd::AIR_RATES.get(usize::from(command.arg)) {
+                // The sending task acknowledges once it has changed the
+                // radios, and rejects the change if it isn't supported
+                Some(&rate) => {
+                    let _ = self.radio.send(RadioRequest::Change(RadioSetting::AirRate(rate), command.kind));
+                }
+                None => return Err(ParseErr::Invalid),
+            },
+            Commands::SetTxPower => {
+                let setting = RadioSetting::TxPower(command.arg.into());
+                let _ = self.radio.send(RadioRequest::Change(setting, command.kind));
+            }
+            Commands::ConfirmRadio => {
+                let _ = self.radio.send(RadioRequest::Confirm);
+            }
             //_ => warn!("Invalid command"),
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{self, Receiver};
+
+    use arowss::{clock::FakeClock, events::EventQueue, uplink::encode_command};
+
+    use super::*;
+
+    /// A pin which only remembers how it was driven.
+    #[derive(Debug, Default)]
+    struct FakePin {
+        high: bool,
+    }
+
+    impl Output for FakePin {
+        fn write(&mut self, high: bool) {
+            self.high = high;
+        }
+    }
+
+    /// A command parser with fake pins and clock, along with the info
+    /// messages it sends.
+    fn command_parser() -> (CommandParser<FakeClock, FakePin>, Receiver<Message>) {
+        let (info_sender, info) = mpsc::channel();
+        let parser = CommandParser {
+            relay_pin: Some(FakePin::default()),
+            info_sender,
+            blank_until: watch::channel(None).0,
+            buzzer: watch::channel(false).0,
+            gps: mpsc::channel().0,
+            baro_rate: watch::channel(0).0,
+            outputs: Vec::new(),
+            armed: false,
+            recorder: mpsc::channel().0,
+            diagnostics: mpsc::channel().0,
+            tare: mpsc::channel().0,
+            retransmit: mpsc::channel().0,
+            radio: mpsc::channel().0,
+            failsafe: vec![FailsafeAction::DisableHighPower],
+            low_power: false,
+            config: Vec::new(),
+            last_run: HashMap::new(),
+            clock: FakeClock::new(0),
+        };
+
+        (parser, info)
+    }
+
+    /// A command frame as the ground sends it.
+    fn frame(data: u8, arg: u8) -> Vec<u8> {
+        encode_command(data, arg).to_vec()
+    }
+
+    /// Feed bytes to a fresh parser, returning every command parsed.
+    fn parse(bytes: &[u8]) -> Vec<Command> {
+        let mut buf = Vec::new();
+        bytes.iter().filter_map(|byte| parse_command_buffer(&mut buf, *byte)).collect()
+    }
+
+    const ECHO: Command = Command { kind: Commands::Echo, arg: 7 };
+
+    #[test]
+    fn valid_command() {
+        assert_eq!(parse(&frame(Commands::Echo as u8, 7)), [ECHO]);
+        assert_eq!(parse(&[frame(120, 7), frame(120, 7)].concat()), [ECHO, ECHO]);
+    }
+
+    #[test]
+    fn missing_magic_byte() {
+        let mut bytes = frame(120, 7);
+        bytes[0] = 0x5A;
+        assert!(parse(&bytes).is_empty());
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let mut bytes = frame(120, 7);
+        bytes[3] ^= 0x01;
+        assert!(parse(&bytes).is_empty());
+
+        // The checksum covers the argument
+        let mut bytes = frame(120, 7);
+        bytes[2] = 8;
+        assert!(parse(&bytes).is_empty());
+    }
+
+    #[test]
+    fn truncated_frame() {
+        let bytes = frame(120, 7);
+        for len in 0..bytes.len() {
+            assert!(parse(&bytes[..len]).is_empty());
+        }
+    }
+
+    #[test]
+    fn early_space() {
+        let mut bytes = frame(120, 7);
+        bytes[1] = b' ';
+        assert!(parse(&bytes).is_empty());
+
+        assert!(parse(&[COMMAND_MAGIC, 120, 7, b' ']).is_empty());
+    }
+
+    #[test]
+    fn trailing_non_space() {
+        let mut bytes = frame(120, 7);
+        bytes[4] = b'x';
+        assert!(parse(&bytes).is_empty());
+
+        // The parser recovers for the next frame
+        assert_eq!(parse(&[bytes, frame(120, 7)].concat()), [ECHO]);
+    }
+
+    #[test]
+    fn frame_inside_partial_frame() {
+        let bytes = [&[COMMAND_MAGIC, 120, 7][..], &frame(120, 7)].concat();
+        assert_eq!(parse(&bytes), [ECHO]);
+
+        // Even when the two together look like a frame with a bad checksum
+        let arg = (0..=u8::MAX).find(|&arg| crc8(&[COMMAND_MAGIC, 120, arg]) == b' ').unwrap();
+        let bytes = [&[COMMAND_MAGIC][..], &frame(120, arg)].concat();
+        assert_eq!(parse(&bytes), [Command { kind: Commands::Echo, arg }]);
+
+        let mut bytes = frame(120, 7);
+        bytes[4] = COMMAND_MAGIC;
+        assert_eq!(parse(&[bytes, frame(120, 7)[1..].to_vec()].concat()), [ECHO]);
+    }
+
+    #[test]
+    fn random_bytes_rarely_validate() {
+        // A fixed xorshift sequence, so the test always sees the same bytes
+        let mut state = 0x2545_F491_4F6C_DD1D_u64;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        };
+
+        // A burst of noise shorter than a frame can never be a command
+        for _ in 0..10_000 {
+            assert!(parse(&[random(), random(), random()]).is_empty());
+        }
+
+        // Three random bytes in place of the command, argument and
+        // checksum only pass for the 1 in 256 with the right checksum
+        let trials = 100_000;
+        let valid = (0..trials)
+            .filter(|_| !parse(&[COMMAND_MAGIC, random(), random(), random(), b' ']).is_empty())
+            .count();
+        assert!(valid < trials / 200, "{valid} of {trials} random frames validated");
+
+        // And a long stream of noise almost never holds a whole frame
+        let noise: Vec<u8> = (0..1_000_000).map(|_| random()).collect();
+        assert!(parse(&noise).len() <= 1);
+    }
+
+    #[test]
+    fn oversized_buffer() {
+        let mut buf = vec![COMMAND_MAGIC, 120, 7, 0, b' ', 0];
+        assert_eq!(parse_command_buffer(&mut buf, b' '), None);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn noise_before_frame() {
+        let noise = [0x00, b' ', 0xFF, 120, b'\n', 0x13];
+        assert_eq!(parse(&[&noise[..], &frame(120, 7)].concat()), [ECHO]);
+    }
+
+    #[test]
+    fn interleaved_garbage() {
+        let mut bytes = Vec::new();
+        for garbage in [&b"abc"[..], &[0x00, 0xFF], b"  "] {
+            bytes.extend_from_slice(garbage);
+            bytes.extend_from_slice(&frame(120, 7));
+        }
+        assert_eq!(parse(&bytes), [ECHO; 3]);
+    }
+
+    #[test]
+    fn every_command_byte() {
+        for data in u8::MIN..=u8::MAX {
+            let parsed = parse(&frame(data, 0));
+
+            match Commands::from_u8(data) {
+                Some(kind) => assert_eq!(parsed, [Command { kind, arg: 0 }]),
+                None => assert!(parsed.is_empty(), "unknown command {data} parsed"),
+            }
+        }
+    }
+
+    #[test]
+    fn every_argument() {
+        for arg in u8::MIN..=u8::MAX {
+            let kind = Commands::RequestRetransmit;
+            assert_eq!(parse(&frame(kind as u8, arg)), [Command { kind, arg }]);
+        }
+    }
+
+    #[tokio::test]
+    async fn echo_returns_argument() {
+        let (mut parser, info) = command_parser();
+        let mut events = EventQueue::new(1, 8);
+
+        parser.parse_command(Command { kind: Commands::Echo, arg: 0xA5 }).await.unwrap();
+        for message in info.try_iter() {
+            if let Message::Event(event) = message {
+                events.push(event);
+            }
+        }
+        assert_eq!(events.next_frame(), [Event::with_arg(EventCode::Echo, 0xA5)]);
+
+        // Echoes are rate limited like any other command
+        let echo = Command { kind: Commands::Echo, arg: 1 };
+        parser.parse_command(echo).await.unwrap();
+        assert!(matches!(parser.parse_command(echo).await, Err(ParseErr::RateLimited)));
+        assert_eq!(info.try_iter().count(), 1);
+    }
+
+    #[test]
+    fn destructive_commands_cool_down() {
+        let (mut parser, _info) = command_parser();
+        let reboot = Command { kind: Commands::Reboot, arg: 0 };
+
+        parser.rate_limit(reboot).unwrap();
+        parser.clock.advance(COMMAND_REPEAT_WINDOW);
+        assert!(matches!(parser.rate_limit(reboot), Err(ParseErr::RateLimited)));
+        parser.clock.advance(DESTRUCTIVE_COMMAND_COOLDOWN - COMMAND_REPEAT_WINDOW * 2);
+        assert!(matches!(parser.rate_limit(reboot), Err(ParseErr::RateLimited)));
+        parser.clock.advance(COMMAND_REPEAT_WINDOW);
+        parser.rate_limit(reboot).unwrap();
+    }
+
+    #[test]
+    fn different_commands_do_not_limit_each_other() {
+        let (mut parser, _info) = command_parser();
+        let reboot = Command { kind: Commands::Reboot, arg: 0 };
+        let echo = Command { kind: Commands::Echo, arg: 1 };
+
+        parser.rate_limit(reboot).unwrap();
+        parser.rate_limit(Command { kind: Commands::RestartStream, arg: 0 }).unwrap();
+        parser.rate_limit(echo).unwrap();
+        // The same command with another argument is a different command
+        parser.rate_limit(Command { arg: 2, ..echo }).unwrap();
+        parser.rate_limit(Command { arg: 1, ..reboot }).unwrap();
+
+        assert!(matches!(parser.rate_limit(echo), Err(ParseErr::RateLimited)));
+        assert!(matches!(parser.rate_limit(reboot), Err(ParseErr::RateLimited)));
+    }
+
+    #[test]
+    fn failsafe_fires_after_timeout() {
+        let start = Instant::now();
+        let mut watchdog = LinkWatchdog::new(start);
+
+        // Armed from boot, before anything has been received
+        assert!(!watchdog.check(start + LINK_LOSS_TIMEOUT));
+        assert!(watchdog.check(start + LINK_LOSS_TIMEOUT + Duration::from_secs(1)));
+        assert!(!watchdog.check(start + LINK_LOSS_TIMEOUT * 2));
+
+        // Any byte restores the link and restarts the timeout
+        let restored = start + LINK_LOSS_TIMEOUT * 3;
+        assert!(watchdog.received(restored));
+        assert!(!watchdog.received(restored));
+        assert!(!watchdog.check(restored + LINK_LOSS_TIMEOUT));
+        assert!(watchdog.check(restored + LINK_LOSS_TIMEOUT + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn failsafe_takes_configured_actions() {
+        let (mut parser, info) = command_parser();
+        parser.relay_pin = Some(FakePin { high: true });
+        parser.failsafe = vec![FailsafeAction::DisableHighPower, FailsafeAction::StartBuzzer];
+        let buzzer = parser.buzzer.subscribe();
+
+        parser.run_failsafe();
+        assert!(!parser.relay_pin.as_ref().unwrap().high);
+        assert!(*buzzer.borrow());
+        let events: Vec<_> = info.try_iter().collect();
+        assert_eq!(
+            events,
+            [EventCode::LinkLost.into(), EventCode::RelayDisabled.into(), EventCode::BuzzerStarted.into()]
+        );
+    }
+
+    #[tokio::test]
+    async fn rate_commands_reach_their_sensor() {
+        let (mut parser, info) = command_parser();
+        let (gps, gps_requests) = mpsc::channel();
+        parser.gps = gps;
+        let mut baro_rate = parser.baro_rate.subscribe();
+
+        parser.parse_command(Command { kind: Commands::BaroRate10Hz, arg: 0 }).await.unwrap();
+        assert!(baro_rate.has_changed().unwrap());
+        assert_eq!(*baro_rate.borrow_and_update(), 10);
+        assert!(gps_requests.try_recv().is_err());
+
+        parser.parse_command(Command { kind: Commands::GpsRate2Hz, arg: 0 }).await.unwrap();
+        assert!(matches!(gps_requests.try_recv(), Ok(GpsRequest::SetRate(2))));
+        assert!(!baro_rate.has_changed().unwrap());
+
+        // Each sensor acknowledges once it has actually changed its rate
+        assert_eq!(info.try_iter().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn missing_relay_clears_its_bit() {
+        let (mut parser, info) = command_parser();
+        let (diagnostics, results) = mpsc::channel();
+        parser.diagnostics = diagnostics;
+
+        parser.parse_command(Command { kind: Commands::RunDiagnostics, arg: 0 }).await.unwrap();
+        assert!(results.try_recv().unwrap().is_ok(Diagnostics::RELAY));
+
+        // A relay pin which couldn't be set up can't be driven
+        parser.relay_pin = None;
+        parser.clock.advance(COMMAND_REPEAT_WINDOW);
+        parser.parse_command(Command { kind: Commands::RunDiagnostics, arg: 0 }).await.unwrap();
+        assert!(!results.try_recv().unwrap().is_ok(Diagnostics::RELAY));
+
+        parser.parse_command(Command { kind: Commands::EnableHighPower, arg: 0 }).await.unwrap();
+        let rejected = Event::with_arg(EventCode::CommandRejected, Commands::EnableHighPower as u32);
+        assert_eq!(info.try_iter().collect::<Vec<_>>(), [rejected.into()]);
+    }
+
+    #[test]
+    fn every_gps_rate_fits_the_serial_link() {
+        for rate in [1, 2] {
+            assert!(gps_rate_supported(rate), "{rate}Hz");
+        }
+        assert!(!gps_rate_supported(5));
+    }
+
+    #[tokio::test]
+    async fn interlocked_output_needs_arming() {
+        let (mut parser, info) = command_parser();
+        parser.outputs = vec![
+            NamedOutput { name: "status_led", pin: Some(FakePin::default()), interlocked: false },
+            NamedOutput { name: "charge", pin: Some(FakePin::default()), interlocked: true },
+        ];
+        let high = |parser: &CommandParser<FakeClock, FakePin>| {
+            parser.outputs.iter().map(|output| output.pin.as_ref().unwrap().high).collect::<Vec<_>>()
+        };
+        let set = |arg| Command { kind: Commands::SetOutput, arg };
+
+        parser.parse_command(set(0)).await.unwrap();
+        assert_eq!(high(&parser), [true, false]);
+        assert_eq!(info.try_recv().unwrap(), Event::with_arg(EventCode::OutputSet, 0).into());
+
+        parser.parse_command(set(1)).await.unwrap();
+        assert_eq!(high(&parser), [true, false]);
+        let rejected = Event::with_arg(EventCode::CommandRejected, Commands::SetOutput as u32);
+        assert_eq!(info.try_recv().unwrap(), rejected.into());
+
+        parser.parse_command(Command { kind: Commands::ArmOutputs, arg: 0 }).await.unwrap();
+        parser.clock.advance(COMMAND_REPEAT_WINDOW);
+        parser.parse_command(set(1)).await.unwrap();
+        assert_eq!(high(&parser), [true, true]);
+
+        // Disarming only clears the interlocked outputs
+        parser.parse_command(Command { kind: Commands::DisarmOutputs, arg: 0 }).await.unwrap();
+        assert_eq!(high(&parser), [true, false]);
+
+        assert!(matches!(parser.parse_command(set(2)).await, Err(ParseErr::Invalid)));
+    }
+}
@@ -0,0 +1,124 @@
+//! Cross-checking of the GPS and barometric altitudes, so a faulty sensor can
+//! be spotted in flight.
+
+use std::time::Instant;
+
+/// The altitudes may disagree by this many meters while the payload is still.
+/// This covers the error of both sensors and of the sea level calibration.
+const MAX_DIVERGENCE: f64 = 50.0;
+/// How far in seconds the GPS altitude may lag behind the barometer. While
+/// climbing or descending, the allowed divergence grows by the distance
+/// covered in this time.
+const GPS_LAG: f64 = 2.0;
+/// The number of consecutive updates the altitudes must disagree for before
+/// it is flagged, or agree for before the flag is cleared. At the normal
+/// send interval this is 5 seconds.
+const SUSTAINED_UPDATES: u32 = 20;
+
+/// Flags a sustained disagreement between the GPS and barometric altitudes.
+///
+/// Both altitudes must be above mean sea level. The GGA altitude already is,
+/// so no geoid correction is applied to it, but the barometric altitude is
+/// only meaningful once the sea level pressure has been calibrated.
+#[derive(Debug, Clone, Default)]
+pub struct AltitudeConsistency {
+    last_baro: Option<(f64, Instant)>,
+    vertical_speed: f64,
+    streak: u32,
+    diverged: bool,
+}
+
+impl AltitudeConsistency {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compare the latest GPS and barometric altitudes, in meters, measured
+    /// at `now`.
+    ///
+    /// Returns the new state if the altitudes have started or stopped
+    /// disagreeing. Updates missing either altitude are ignored.
+    pub fn update(&mut self, gps: Option<f64>, baro: Option<f64>, now: Instant) -> Option<bool> {
+        let (Some(gps), Some(baro)) = (gps, baro) else {
+            return None;
+        };
+
+        if let Some((last, at)) = self.last_baro {
+            let elapsed = now.saturating_duration_since(at).as_secs_f64();
+            if elapsed > 0.0 {
+                self.vertical_speed = (baro - last) / elapsed;
+            }
+        }
+        self.last_baro = Some((baro, now));
+
+        let allowed = MAX_DIVERGENCE + self.vertical_speed.abs() * GPS_LAG;
+        let diverged = (gps - baro).abs() > allowed;
+
+        if diverged == self.diverged {
+            self.streak = 0;
+            return None;
+        }
+
+        self.streak += 1;
+        if self.streak < SUSTAINED_UPDATES {
+            return None;
+        }
+
+        self.streak = 0;
+        self.diverged = diverged;
+        Some(diverged)
+    }
+
+    /// Whether the altitudes currently disagree.
+    pub fn is_diverged(&self) -> bool {
+        self.diverged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_millis(250);
+
+    #[test]
+    fn sustained_divergence_is_flagged() {
+        let start = Instant::now();
+        let mut consistency = AltitudeConsistency::new();
+        let at = |update: u32| start + INTERVAL * update;
+
+        // Still on the pad, with the GPS drifting far from the barometer
+        for update in 0..SUSTAINED_UPDATES - 1 {
+            assert_eq!(consistency.update(Some(500.0), Some(350.0), at(update)), None);
+        }
+        assert_eq!(consistency.update(Some(500.0), Some(350.0), at(SUSTAINED_UPDATES)), Some(true));
+        assert!(consistency.is_diverged());
+
+        // A brief agreement doesn't clear the flag
+        let mut update = SUSTAINED_UPDATES + 1;
+        assert_eq!(consistency.update(Some(350.0), Some(350.0), at(update)), None);
+        update += 1;
+        assert_eq!(consistency.update(Some(500.0), Some(350.0), at(update)), None);
+        for _ in 0..SUSTAINED_UPDATES - 1 {
+            update += 1;
+            assert_eq!(consistency.update(Some(350.0), Some(350.0), at(update)), None);
+        }
+        assert_eq!(consistency.update(Some(350.0), Some(350.0), at(update + 1)), Some(false));
+    }
+
+    #[test]
+    fn fast_climb_allows_gps_lag() {
+        let start = Instant::now();
+        let mut consistency = AltitudeConsistency::new();
+
+        // Climbing at 100 m/s with the GPS 1.5 seconds behind
+        for update in 0..SUSTAINED_UPDATES * 2 {
+            let baro = 350.0 + 25.0 * f64::from(update);
+            let gps = baro - 150.0;
+            assert_eq!(consistency.update(Some(gps), Some(baro), start + INTERVAL * update), None);
+        }
+        assert!(!consistency.is_diverged());
+    }
+}
@@ -0,0 +1,442 @@
+//! Conversion of GPS coordinates into the formats used by recovery teams.
+//!
+//! The downlink always carries decimal degrees; these conversions are only
+//! meant for display and export on the ground.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{heading::wrap_degrees, GpsInfo};
+
+/// WGS84 semi-major axis in meters.
+const WGS84_A: f64 = 6_378_137.0;
+/// WGS84 flattening.
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+/// Mean radius of the Earth in meters, used for distances between fixes.
+const EARTH_MEAN_RADIUS: f64 = 6_371_008.8;
+/// UTM central meridian scale factor.
+const UTM_K0: f64 = 0.9996;
+
+/// UTM latitude band letters, each covering 8° starting at 80°S.
+const UTM_BANDS: &[u8] = b"CDEFGHJKLMNPQRSTUVWX";
+
+/// The format in which to display a coordinate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoordinateFormat {
+    /// Decimal degrees, e.g. `40.446200, -79.982200`
+    #[default]
+    Decimal,
+    /// Degrees, minutes and seconds, e.g. `40°26'46.3"N 79°58'55.9"W`
+    Dms,
+    /// Universal Transverse Mercator, e.g. `17T 586311E 4477780N`
+    Utm,
+    /// Military Grid Reference System, e.g. `17T NE 86311 77780`
+    Mgrs,
+}
+
+impl CoordinateFormat {
+    /// Format a latitude and longitude, given in decimal degrees.
+    ///
+    /// UTM and MGRS are undefined near the poles (beyond 80°S and 84°N), so
+    /// positions there fall back to decimal degrees.
+    pub fn format(self, latitude: f64, longitude: f64) -> String {
+        match self {
+            CoordinateFormat::Decimal => format!("{latitude:.6}, {longitude:.6}"),
+            CoordinateFormat::Dms => format!(
+                "{} {}",
+                format_dms(latitude, 'N', 'S'),
+                format_dms(longitude, 'E', 'W')
+            ),
+            CoordinateFormat::Utm => match Utm::from_lat_lon(latitude, longitude) {
+                Some(utm) => format!(
+                    "{}{} {:.0}E {:.0}N",
+                    utm.zone,
+                    utm.band,
+                    utm.easting.floor(),
+                    utm.northing.floor()
+                ),
+                None => CoordinateFormat::Decimal.format(latitude, longitude),
+            },
+            CoordinateFormat::Mgrs => match Utm::from_lat_lon(latitude, longitude) {
+                Some(utm) => utm.to_mgrs(),
+                None => CoordinateFormat::Decimal.format(latitude, longitude),
+            },
+        }
+    }
+}
+
+/// Format a single angle as degrees, minutes and seconds.
+fn format_dms(angle: f64, positive: char, negative: char) -> String {
+    let hemisphere = if angle < 0.0 { negative } else { positive };
+
+    // Round to tenths of a second first so 59.96" doesn't display as 60.0"
+    let tenths = (angle.abs() * 36_000.0).round() as u64;
+    let degrees = tenths / 36_000;
+    let minutes = (tenths % 36_000) / 600;
+    let seconds = (tenths % 600) as f64 / 10.0;
+
+    format!("{degrees}°{minutes}'{seconds:.1}\"{hemisphere}")
+}
+
+/// A position in Universal Transverse Mercator coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Utm {
+    pub zone: u8,
+    pub band: char,
+    pub easting: f64,
+    pub northing: f64,
+}
+
+impl Utm {
+    /// Convert a WGS84 latitude and longitude into UTM coordinates.
+    ///
+    /// Returns `None` outside of the UTM latitude limits of 80°S to 84°N.
+    pub fn from_lat_lon(latitude: f64, longitude: f64) -> Option<Self> {
+        if !(-80.0..=84.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+            return None;
+        }
+
+        let zone = utm_zone(latitude, longitude);
+        let band_index = (((latitude + 80.0) / 8.0) as usize).min(UTM_BANDS.len() - 1);
+        let band = UTM_BANDS[band_index] as char;
+
+        let central_meridian = (f64::from(zone) - 1.0) * 6.0 - 180.0 + 3.0;
+
+        let e2 = WGS84_F * (2.0 - WGS84_F);
+        let e4 = e2 * e2;
+        let e6 = e4 * e2;
+        let ep2 = e2 / (1.0 - e2);
+
+        let phi = latitude.to_radians();
+        let (sin_phi, cos_phi) = phi.sin_cos();
+
+        let n = WGS84_A / (1.0 - e2 * sin_phi * sin_phi).sqrt();
+        let t = phi.tan().powi(2);
+        let c = ep2 * cos_phi * cos_phi;
+        let a = cos_phi * (longitude - central_meridian).to_radians();
+
+        let m = WGS84_A
+            * ((1.0 - e2 / 4.0 - 3.0 * e4 / 64.0 - 5.0 * e6 / 256.0) * phi
+                - (3.0 * e2 / 8.0 + 3.0 * e4 / 32.0 + 45.0 * e6 / 1024.0) * (2.0 * phi).sin()
+                + (15.0 * e4 / 256.0 + 45.0 * e6 / 1024.0) * (4.0 * phi).sin()
+                - (35.0 * e6 / 3072.0) * (6.0 * phi).sin());
+
+        let easting = UTM_K0
+            * n
+            * (a + (1.0 - t + c) * a.powi(3) / 6.0
+                + (5.0 - 18.0 * t + t * t + 72.0 * c - 58.0 * ep2) * a.powi(5) / 120.0)
+            + 500_000.0;
+
+        let mut northing = UTM_K0
+            * (m + n
+                * phi.tan()
+                * (a * a / 2.0
+                    + (5.0 - t + 9.0 * c + 4.0 * c * c) * a.powi(4) / 24.0
+                    + (61.0 - 58.0 * t + t * t + 600.0 * c - 330.0 * ep2) * a.powi(6) / 720.0));
+
+        // The southern hemisphere uses a false northing
+        if latitude < 0.0 {
+            northing += 10_000_000.0;
+        }
+
+        Some(Self {
+            zone,
+            band,
+            easting,
+            northing,
+        })
+    }
+
+    /// Format this position as a 1 meter precision MGRS grid reference.
+    pub fn to_mgrs(&self) -> String {
+        const COLUMN_SETS: [&[u8]; 3] = [b"STUVWXYZ", b"ABCDEFGH", b"JKLMNPQR"];
+        const ROW_LETTERS: &[u8] = b"ABCDEFGHJKLMNPQRSTUV";
+
+        let easting = self.easting.floor() as u64;
+        let northing = self.northing.floor() as u64;
+
+        // The column letters repeat every 3 zones, the row letters are
+        // offset by 5 in even zones.
+        let column_set = COLUMN_SETS[usize::from(self.zone) % 3];
+        let column = column_set[(easting / 100_000).clamp(1, 8) as usize - 1] as char;
+
+        let row_offset = if self.zone.is_multiple_of(2) { 5 } else { 0 };
+        let row = ROW_LETTERS[((northing / 100_000 + row_offset) % 20) as usize] as char;
+
+        format!(
+            "{}{} {column}{row} {:05} {:05}",
+            self.zone,
+            self.band,
+            easting % 100_000,
+            northing % 100_000,
+        )
+    }
+}
+
+/// Find the UTM zone of a position, including the Norway and Svalbard
+/// exceptions.
+fn utm_zone(latitude: f64, longitude: f64) -> u8 {
+    if (56.0..64.0).contains(&latitude) && (3.0..12.0).contains(&longitude) {
+        return 32;
+    }
+
+    if (72.0..=84.0).contains(&latitude) && longitude >= 0.0 {
+        match longitude {
+            l if l < 9.0 => return 31,
+            l if l < 21.0 => return 33,
+            l if l < 33.0 => return 35,
+            l if l < 42.0 => return 37,
+            _ => (),
+        }
+    }
+
+    (((longitude + 180.0) / 6.0) as u8 % 60) + 1
+}
+
+/// Estimate the speed in meters per second and course in degrees from true
+/// north between two positions, given in decimal degrees, taken `elapsed`
+/// seconds apart.
+///
+/// Returns `None` if no time has elapsed. The speed and course downlinked
+/// in [`GpsInfo`](crate::GpsInfo) come from the receiver itself and should
+/// be preferred; recomputing them on the ground from downlinked positions is
+/// lossy, as any rounding of the positions is amplified by the short time
+/// between fixes.
+pub fn ground_track(from: (f64, f64), to: (f64, f64), elapsed: f64) -> Option<(f64, f64)> {
+    if elapsed <= 0.0 {
+        return None;
+    }
+
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+    let (d_lat, d_lon) = (lat2 - lat1, lon2 - lon1);
+
+    // Haversine distance
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    let distance = 2.0 * EARTH_MEAN_RADIUS * a.sqrt().asin();
+
+    // Initial bearing
+    let y = d_lon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lon.cos();
+    let course = y.atan2(x).to_degrees().rem_euclid(360.0);
+
+    Some((distance / elapsed, course))
+}
+
+/// Positions closer together than this, in meters, have no meaningful
+/// direction between them.
+const MIN_LOOK_RANGE: f64 = 1.0;
+
+/// The azimuth clockwise from true north and elevation above the horizon,
+/// both in degrees, at which to point an antenna at `ground` towards the
+/// payload.
+///
+/// `ground` is the latitude and longitude in decimal degrees and the
+/// altitude above mean sea level in meters, the same datum as the GPS
+/// altitude. A payload below the ground station's horizon, such as one
+/// which has landed behind a hill, has a negative elevation. Returns `None`
+/// if the payload is at the ground station, where there is no direction to
+/// point in.
+pub fn look_angles(ground: (f64, f64, f32), payload: &GpsInfo) -> Option<(f32, f32)> {
+    let (latitude, longitude, altitude) = ground;
+    let origin = Origin {
+        latitude,
+        longitude,
+        altitude: f64::from(altitude),
+    };
+    let enu = origin.to_enu(payload.latitude, payload.longitude, f64::from(payload.altitude));
+
+    let horizontal = enu.east.hypot(enu.north);
+    if horizontal.hypot(enu.up) < MIN_LOOK_RANGE {
+        return None;
+    }
+
+    // Straight up or down has no azimuth, so north is used
+    let azimuth = if horizontal < MIN_LOOK_RANGE {
+        0.0
+    } else {
+        wrap_degrees(enu.east.atan2(enu.north).to_degrees() as f32)
+    };
+    let elevation = enu.up.atan2(horizontal).to_degrees();
+
+    Some((azimuth, elevation as f32))
+}
+
+/// A fixed position, such as the launch pad, from which local coordinates
+/// are measured. Latitude and longitude are in decimal degrees, and altitude
+/// in meters.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Origin {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: f64,
+}
+
+/// A position in meters east, north and up from an [`Origin`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Enu {
+    pub east: f64,
+    pub north: f64,
+    pub up: f64,
+}
+
+impl Origin {
+    /// Convert a position into local east, north, up coordinates relative
+    /// to this origin.
+    ///
+    /// Both altitudes must be relative to the same datum. The GPS altitude
+    /// above mean sea level is fine near the origin, as the geoid separation
+    /// barely changes over the range of a flight.
+    pub fn to_enu(&self, latitude: f64, longitude: f64, altitude: f64) -> Enu {
+        let origin = to_ecef(self.latitude, self.longitude, self.altitude);
+        let point = to_ecef(latitude, longitude, altitude);
+        let [dx, dy, dz] = [point[0] - origin[0], point[1] - origin[1], point[2] - origin[2]];
+
+        let (sin_lat, cos_lat) = self.latitude.to_radians().sin_cos();
+        let (sin_lon, cos_lon) = self.longitude.to_radians().sin_cos();
+
+        Enu {
+            east: -sin_lon * dx + cos_lon * dy,
+            north: -sin_lat * cos_lon * dx - sin_lat * sin_lon * dy + cos_lat * dz,
+            up: cos_lat * cos_lon * dx + cos_lat * sin_lon * dy + sin_lat * dz,
+        }
+    }
+}
+
+impl fmt::Display for Enu {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.0}m E {:.0}m N {:.0}m U", self.east, self.north, self.up)
+    }
+}
+
+/// Convert a WGS84 position into Earth-centered, Earth-fixed coordinates.
+fn to_ecef(latitude: f64, longitude: f64, altitude: f64) -> [f64; 3] {
+    let e2 = WGS84_F * (2.0 - WGS84_F);
+    let (sin_lat, cos_lat) = latitude.to_radians().sin_cos();
+    let (sin_lon, cos_lon) = longitude.to_radians().sin_cos();
+
+    let n = WGS84_A / (1.0 - e2 * sin_lat * sin_lat).sqrt();
+
+    [
+        (n + altitude) * cos_lat * cos_lon,
+        (n + altitude) * cos_lat * sin_lon,
+        (n * (1.0 - e2) + altitude) * sin_lat,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The CN Tower, a common UTM reference point.
+    const CN_TOWER: (f64, f64) = (43.642567, -79.387139);
+    /// The Sydney Opera House, in the southern and eastern hemispheres.
+    const SYDNEY: (f64, f64) = (-33.856784, 151.215297);
+
+    #[test]
+    fn decimal() {
+        assert_eq!(CoordinateFormat::Decimal.format(CN_TOWER.0, CN_TOWER.1), "43.642567, -79.387139");
+        assert_eq!(CoordinateFormat::Decimal.format(SYDNEY.0, SYDNEY.1), "-33.856784, 151.215297");
+    }
+
+    #[test]
+    fn dms() {
+        assert_eq!(CoordinateFormat::Dms.format(CN_TOWER.0, CN_TOWER.1), "43°38'33.2\"N 79°23'13.7\"W");
+        assert_eq!(CoordinateFormat::Dms.format(SYDNEY.0, SYDNEY.1), "33°51'24.4\"S 151°12'55.1\"E");
+    }
+
+    #[test]
+    fn utm() {
+        assert_eq!(CoordinateFormat::Utm.format(CN_TOWER.0, CN_TOWER.1), "17T 630084E 4833438N");
+        assert_eq!(CoordinateFormat::Utm.format(SYDNEY.0, SYDNEY.1), "56H 334900E 6252290N");
+    }
+
+    #[test]
+    fn mgrs() {
+        assert_eq!(CoordinateFormat::Mgrs.format(CN_TOWER.0, CN_TOWER.1), "17T PJ 30084 33438");
+        assert_eq!(CoordinateFormat::Mgrs.format(SYDNEY.0, SYDNEY.1), "56H LH 34900 52290");
+    }
+
+    #[test]
+    fn polar_falls_back_to_decimal() {
+        assert_eq!(CoordinateFormat::Utm.format(-85.0, 10.0), "-85.000000, 10.000000");
+        assert_eq!(CoordinateFormat::Mgrs.format(85.0, 10.0), "85.000000, 10.000000");
+    }
+
+    #[test]
+    fn enu_near_origin() {
+        let origin = Origin { latitude: 40.0, longitude: -96.0, altitude: 400.0 };
+
+        // A thousandth of a degree is about 111m north and 85m east at 40°N
+        let enu = origin.to_enu(40.001, -95.999, 500.0);
+        assert!((enu.east - 85.39).abs() < 0.05, "{enu:?}");
+        assert!((enu.north - 111.03).abs() < 0.05, "{enu:?}");
+        assert!((enu.up - 100.0).abs() < 0.05, "{enu:?}");
+        assert_eq!(enu.to_string(), "85m E 111m N 100m U");
+
+        let enu = origin.to_enu(40.0, -96.0, 400.0);
+        assert!(enu.east.abs() < 1e-6 && enu.north.abs() < 1e-6 && enu.up.abs() < 1e-6);
+    }
+
+    /// A fix at the given position, with nothing else known.
+    fn fix(latitude: f64, longitude: f64, altitude: f32) -> GpsInfo {
+        GpsInfo {
+            latitude,
+            longitude,
+            altitude,
+            satellites: 8,
+            hdop: None,
+            geoid_separation: None,
+            speed: None,
+            course: None,
+        }
+    }
+
+    #[test]
+    fn look_angles_by_hand() {
+        let ground = (40.0, -96.0, 400.0);
+
+        // 85.39m east, 111.03m north and 100m up, so the azimuth is
+        // atan(85.39 / 111.03) = 37.56° and, with a horizontal range of
+        // 140.07m, the elevation is atan(100 / 140.07) = 35.52°
+        let (azimuth, elevation) = look_angles(ground, &fix(40.001, -95.999, 500.0)).unwrap();
+        assert!((azimuth - 37.56).abs() < 0.05, "{azimuth}");
+        assert!((elevation - 35.52).abs() < 0.05, "{elevation}");
+
+        // Due south and level, the azimuth is 180° and the curve of
+        // the earth drops the payload just below the horizon
+        let (azimuth, elevation) = look_angles(ground, &fix(39.99, -96.0, 400.0)).unwrap();
+        assert!((azimuth - 180.0).abs() < 0.01, "{azimuth}");
+        assert!(elevation < 0.0 && elevation > -0.01, "{elevation}");
+
+        // Straight up points north, and on top of the station points nowhere
+        assert_eq!(look_angles(ground, &fix(40.0, -96.0, 1400.0)), Some((0.0, 90.0)));
+        assert_eq!(look_angles(ground, &fix(40.0, -96.0, 400.0)), None);
+    }
+
+    #[test]
+    fn rounding_positions_corrupts_speed() {
+        // 5m/s due north, between two fixes 250ms apart
+        let elapsed = 0.25;
+        let from = (40.820_123, -96.705_654);
+        let to = (from.0 + (5.0 * elapsed / EARTH_MEAN_RADIUS).to_degrees(), from.1);
+        let round = |(lat, lon): (f64, f64), places: i32| {
+            let scale = 10f64.powi(places);
+            ((lat * scale).round() / scale, (lon * scale).round() / scale)
+        };
+
+        let (speed, course) = ground_track(from, to, elapsed).unwrap();
+        assert!((speed - 5.0).abs() < 1e-6, "{speed}");
+        assert!(course.abs() < 1e-6, "{course}");
+
+        // Rounded to about a meter, the speed is off by over 10%
+        let (speed, _) = ground_track(round(from, 5), round(to, 5), elapsed).unwrap();
+        assert!((speed - 5.0).abs() > 0.5, "{speed}");
+
+        // Rounded to about 10m, the payload doesn't seem to move at all
+        let (speed, _) = ground_track(round(from, 4), round(to, 4), elapsed).unwrap();
+        assert_eq!(speed, 0.0);
+    }
+}
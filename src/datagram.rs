@@ -0,0 +1,126 @@
+//! Validation of the UDP datagrams received from the payload, before their
+//! frames are decoded.
+//!
+//! The payload sends exactly one frame in each datagram, so a datagram
+//! which doesn't hold a whole frame has been damaged or cut off.
+
+use crate::{
+    frame::{FrameError, SYNC_WORD},
+    validation::{decode_frame_validated, PlausibilityError, ValidationMode},
+    TelemetryPacket,
+};
+
+/// The largest datagram which fits in a single Ethernet frame, after the IP
+/// and UDP headers. Anything larger is fragmented by the IP layer, and is
+/// lost entirely if any fragment is.
+pub const DEFAULT_MAX_DATAGRAM_BYTES: usize = 1500 - 20 - 8;
+
+/// Reasons a datagram is rejected without being decoded.
+#[derive(Debug, thiserror::Error)]
+pub enum DatagramError {
+    #[error("Datagram is empty")]
+    Empty,
+    #[error("Datagram of at least {len} bytes is larger than the maximum of {max}")]
+    Oversized { len: usize, max: usize },
+    #[error("Datagram does not hold a whole frame")]
+    Truncated,
+    #[error("Datagram frame could not be decoded: {0}")]
+    Frame(#[from] FrameError),
+}
+
+/// Checks and decodes datagrams received from the payload.
+#[derive(Debug, Clone, Copy)]
+pub struct DatagramDecoder {
+    max_bytes: usize,
+    validation: ValidationMode,
+}
+
+impl Default for DatagramDecoder {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_DATAGRAM_BYTES)
+    }
+}
+
+impl DatagramDecoder {
+    /// Create a decoder which rejects datagrams larger than `max_bytes`,
+    /// and packets with implausible values.
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            validation: ValidationMode::Strict,
+        }
+    }
+
+    /// Set how packets with implausible values are treated.
+    pub fn with_validation(mut self, validation: ValidationMode) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// The size of the buffer to receive datagrams into.
+    ///
+    /// This is one byte more than the largest datagram accepted, as a
+    /// datagram too large for the buffer is silently cut off to fit it, and
+    /// filling the buffer is the only sign of that.
+    pub fn buffer_size(&self) -> usize {
+        self.max_bytes + 1
+    }
+
+    /// Check that a received datagram holds a single whole frame.
+    pub fn validate(&self, datagram: &[u8]) -> Result<(), DatagramError> {
+        if datagram.is_empty() {
+            return Err(DatagramError::Empty);
+        }
+
+        if datagram.len() > self.max_bytes {
+            return Err(DatagramError::Oversized {
+                len: datagram.len(),
+                max: self.max_bytes,
+            });
+        }
+
+        if !datagram.starts_with(&SYNC_WORD) || !datagram.ends_with(b"\n") {
+            return Err(DatagramError::Truncated);
+        }
+
+        Ok(())
+    }
+
+    /// Validate a received datagram, then decode the frame it holds into
+    /// its sequence number and packet, checking the packet is plausible.
+    ///
+    /// The implausible values are only returned in
+    /// [`ValidationMode::Lenient`], as otherwise the packet is rejected.
+    pub fn decode(&self, datagram: &[u8]) -> Result<(u8, TelemetryPacket, Vec<PlausibilityError>), DatagramError> {
+        self.validate(datagram)?;
+
+        Ok(decode_frame_validated(datagram, self.validation)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode_frame;
+
+    #[test]
+    fn bad_datagrams_are_rejected() {
+        let frame = encode_frame(9, &TelemetryPacket::default());
+        let decoder = DatagramDecoder::new(frame.len());
+        assert_eq!(decoder.decode(&frame).unwrap().0, 9);
+
+        assert!(matches!(decoder.decode(&[]), Err(DatagramError::Empty)));
+
+        // One byte over the limit
+        let mut oversized = frame.clone();
+        oversized.insert(SYNC_WORD.len(), b' ');
+        assert!(matches!(
+            decoder.decode(&oversized),
+            Err(DatagramError::Oversized { len, max }) if len == frame.len() + 1 && max == frame.len()
+        ));
+        assert!(matches!(
+            decoder.decode(&frame[..frame.len() - 1]),
+            Err(DatagramError::Truncated)
+        ));
+    }
+}
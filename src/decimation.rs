@@ -0,0 +1,180 @@
+//! Reduction of the samples taken by a sensor during one send interval into
+//! the values which are downlinked.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{parse_float, truncate_float};
+
+/// How the samples of a sensor within a send interval are reduced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DecimationPolicy {
+    /// Send the most recent sample
+    #[default]
+    Latest,
+    /// Send the mean of the samples
+    Mean,
+    /// Send the most recent sample, along with the minimum and maximum
+    MinMax,
+}
+
+/// The extremes of the samples of a sensor within a send interval.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SampleRange {
+    #[serde(serialize_with = "truncate_float", deserialize_with = "parse_float")]
+    pub min: f64,
+    #[serde(serialize_with = "truncate_float", deserialize_with = "parse_float")]
+    pub max: f64,
+}
+
+/// The result of decimating the samples within a send interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decimated {
+    /// The single value representing the interval
+    pub value: f64,
+    /// The extremes of the interval, only given by [`DecimationPolicy::MinMax`]
+    pub range: Option<SampleRange>,
+}
+
+/// Collects the samples of a sensor over a send interval.
+#[derive(Debug, Clone, Default)]
+pub struct Decimator {
+    policy: DecimationPolicy,
+    median_window: Option<usize>,
+    samples: Vec<f64>,
+}
+
+impl Decimator {
+    pub fn new(policy: DecimationPolicy) -> Self {
+        Self {
+            policy,
+            median_window: None,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Pass the samples of each interval through a median filter of `window`
+    /// samples before reducing them, so a single spike can't skew the
+    /// reported extremes.
+    ///
+    /// Intervals with fewer samples than the window are not filtered, as
+    /// there aren't enough samples to tell a spike from a real change.
+    pub fn with_median_filter(mut self, window: usize) -> Self {
+        self.median_window = Some(window);
+        self
+    }
+
+    /// Add a sample to the current interval.
+    pub fn push(&mut self, sample: f64) {
+        self.samples.push(sample);
+    }
+
+    /// Reduce the samples of the current interval according to the policy,
+    /// and start a new interval.
+    ///
+    /// Returns `None` if there were no samples in the interval.
+    pub fn finish(&mut self) -> Option<Decimated> {
+        if let Some(window) = self.median_window
+            && window > 1
+            && self.samples.len() >= window
+        {
+            self.samples = median_filter(&self.samples, window);
+        }
+
+        let latest = *self.samples.last()?;
+
+        let decimated = match self.policy {
+            DecimationPolicy::Latest => Decimated {
+                value: latest,
+                range: None,
+            },
+            DecimationPolicy::Mean => Decimated {
+                value: self.samples.iter().sum::<f64>() / self.samples.len() as f64,
+                range: None,
+            },
+            DecimationPolicy::MinMax => Decimated {
+                value: latest,
+                range: Some(SampleRange {
+                    min: self.samples.iter().copied().fold(f64::INFINITY, f64::min),
+                    max: self.samples.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                }),
+            },
+        };
+
+        self.samples.clear();
+
+        Some(decimated)
+    }
+}
+
+/// Replace each sample with the median of the `window` samples around it.
+/// Near the ends, the window is shifted inwards rather than shrunk.
+fn median_filter(samples: &[f64], window: usize) -> Vec<f64> {
+    let mut sorted = Vec::with_capacity(window);
+
+    (0..samples.len())
+        .map(|i| {
+            let start = i.saturating_sub(window / 2).min(samples.len() - window);
+
+            sorted.clear();
+            sorted.extend_from_slice(&samples[start..start + window]);
+            sorted.sort_unstable_by(f64::total_cmp);
+
+            sorted[window / 2]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_max_reports_extremes() {
+        let mut decimator = Decimator::new(DecimationPolicy::MinMax);
+        for sample in [101_300.0, 101_250.0, 101_410.0, 101_320.0, 101_280.0] {
+            decimator.push(sample);
+        }
+
+        assert_eq!(
+            decimator.finish(),
+            Some(Decimated {
+                value: 101_280.0,
+                range: Some(SampleRange { min: 101_250.0, max: 101_410.0 }),
+            })
+        );
+
+        // Each interval starts afresh
+        assert_eq!(decimator.finish(), None);
+    }
+
+    #[test]
+    fn median_filter_removes_spike() {
+        let mut decimator = Decimator::new(DecimationPolicy::MinMax).with_median_filter(3);
+        for sample in [101_300.0, 101_310.0, 150_000.0, 101_320.0, 101_330.0] {
+            decimator.push(sample);
+        }
+
+        assert_eq!(
+            decimator.finish(),
+            Some(Decimated {
+                value: 101_330.0,
+                range: Some(SampleRange { min: 101_310.0, max: 101_330.0 }),
+            })
+        );
+    }
+
+    #[test]
+    fn median_filter_needs_a_full_window() {
+        let mut decimator = Decimator::new(DecimationPolicy::MinMax).with_median_filter(3);
+        decimator.push(101_300.0);
+        decimator.push(150_000.0);
+
+        assert_eq!(
+            decimator.finish(),
+            Some(Decimated {
+                value: 150_000.0,
+                range: Some(SampleRange { min: 101_300.0, max: 150_000.0 }),
+            })
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! A compact summary of which subsystems are working, for a quick go/no-go
+//! from the ground.
+
+use std::fmt;
+
+/// One bit for each subsystem, set if it is working.
+///
+/// The bits must never be reassigned, so that old recordings can still be
+/// read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Diagnostics(u8);
+
+impl Diagnostics {
+    /// The primary GPS is producing fixes
+    pub const GPS: u8 = 1 << 0;
+    /// The BMP581 is producing readings
+    pub const BMP: u8 = 1 << 1;
+    /// The BNO055 is producing readings
+    pub const BNO: u8 = 1 << 2;
+    /// The HTS221 is producing readings
+    pub const HTS: u8 = 1 << 3;
+    /// Telemetry is being recorded to the SD card
+    pub const RECORDER: u8 = 1 << 4;
+    /// The high power relay's pin could be set up, so it can be driven
+    pub const RELAY: u8 = 1 << 5;
+
+    /// Every bit, along with its name.
+    const NAMES: [(u8, &str); 6] = [
+        (Self::GPS, "GPS"),
+        (Self::BMP, "BMP"),
+        (Self::BNO, "BNO"),
+        (Self::HTS, "HTS"),
+        (Self::RECORDER, "recorder"),
+        (Self::RELAY, "relay"),
+    ];
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read back a bitmask downlinked by the payload.
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Set or clear the bit of a subsystem.
+    pub fn set(&mut self, bit: u8, ok: bool) {
+        if ok {
+            self.0 |= bit;
+        } else {
+            self.0 &= !bit;
+        }
+    }
+
+    /// Whether the subsystem of `bit` is working.
+    pub fn is_ok(self, bit: u8) -> bool {
+        self.0 & bit == bit
+    }
+}
+
+/// Lists the subsystems which are not working, e.g. `BMP, relay failed`.
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed: Vec<&str> = Self::NAMES
+            .iter()
+            .filter(|(bit, _)| !self.is_ok(*bit))
+            .map(|(_, name)| *name)
+            .collect();
+
+        if failed.is_empty() {
+            write!(f, "All subsystems ok")
+        } else {
+            write!(f, "{} failed", failed.join(", "))
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Duty cycling of the transmitter, to keep the RFD-900x within its thermal
+//! limits in a sealed payload.
+
+/// How often the transmitter sends a packet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DutyPolicy {
+    /// Send every packet
+    Always,
+    /// Send `on` out of every `period` packets
+    Fixed { on: u32, period: u32 },
+    /// Send every packet until the temperature in degrees Celsius reaches
+    /// `limit`, then `on` out of every `period` until it has cooled to
+    /// `hysteresis` below the limit
+    Thermal { limit: f32, hysteresis: f32, on: u32, period: u32 },
+}
+
+impl DutyPolicy {
+    /// Whether the policy depends on the temperature.
+    pub fn uses_temperature(&self) -> bool {
+        matches!(self, DutyPolicy::Thermal { .. })
+    }
+}
+
+/// Decides which packets are transmitted under a [`DutyPolicy`].
+#[derive(Debug, Clone)]
+pub struct DutyCycle {
+    policy: DutyPolicy,
+    count: u32,
+    hot: bool,
+}
+
+impl DutyCycle {
+    pub fn new(policy: DutyPolicy) -> Self {
+        Self {
+            policy,
+            count: 0,
+            hot: false,
+        }
+    }
+
+    /// Decide whether to transmit the next packet.
+    ///
+    /// A `critical` packet, such as one sent during flight, is always
+    /// transmitted. An unknown temperature leaves a thermal policy as it
+    /// was.
+    pub fn should_send(&mut self, temperature: Option<f32>, critical: bool) -> bool {
+        let slot = self.count;
+        self.count = self.count.wrapping_add(1);
+
+        if let DutyPolicy::Thermal { limit, hysteresis, .. } = self.policy
+            && let Some(temperature) = temperature
+        {
+            if temperature >= limit {
+                self.hot = true;
+            } else if temperature <= limit - hysteresis {
+                self.hot = false;
+            }
+        }
+
+        let (on, period) = match self.policy {
+            DutyPolicy::Always => return true,
+            DutyPolicy::Fixed { on, period } => (on, period),
+            DutyPolicy::Thermal { on, period, .. } if self.hot => (on, period),
+            DutyPolicy::Thermal { .. } => return true,
+        };
+
+        critical || period == 0 || slot % period < on
+    }
+
+    /// Whether a thermal policy is currently cutting back transmissions.
+    pub fn is_hot(&self) -> bool {
+        self.hot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duty_cycle_respects_critical_packets() {
+        let mut duty = DutyCycle::new(DutyPolicy::Fixed { on: 1, period: 4 });
+        let sent: Vec<_> = (0..8).map(|_| duty.should_send(None, false)).collect();
+        assert_eq!(sent, [true, false, false, false, true, false, false, false]);
+        assert!((0..8).all(|_| duty.should_send(None, true)));
+
+        let mut duty = DutyCycle::new(DutyPolicy::Thermal { limit: 70.0, hysteresis: 5.0, on: 1, period: 2 });
+        assert!(duty.should_send(Some(60.0), false));
+        assert!(duty.should_send(Some(60.0), false));
+
+        // Once hot, packets are cut back, other than critical ones
+        let sent: Vec<_> = (0..4).map(|_| duty.should_send(Some(70.0), false)).collect();
+        assert_eq!(sent, [true, false, true, false]);
+        assert!(duty.is_hot());
+        assert!(duty.should_send(Some(68.0), true));
+        assert!(!duty.should_send(Some(68.0), false));
+
+        // An unknown temperature changes nothing, so it stays hot until
+        // cooled past the hysteresis
+        let sent: Vec<_> = (0..2).map(|_| duty.should_send(None, false)).collect();
+        assert_eq!(sent, [true, false]);
+        assert!(duty.should_send(Some(65.0), false));
+        assert!(duty.should_send(Some(65.0), false));
+        assert!(!duty.is_hot());
+    }
+}
@@ -0,0 +1,302 @@
+//! Compact numeric codes for the common messages sent in the info channel.
+//!
+//! Both sides share the table in [`EventCode`], so only the code, and an
+//! optional argument, need to be downlinked. Free text info is reserved for
+//! messages which are genuinely arbitrary.
+
+use std::{collections::VecDeque, fmt};
+
+use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::FromPrimitive as _;
+use serde::{Deserialize, Serialize};
+
+/// Every event which can be downlinked. The values must never be reused, so
+/// that old recordings can still be rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+#[repr(u16)]
+#[non_exhaustive]
+pub enum EventCode {
+    BuzzerStarted = 1,
+    BuzzerStopped = 2,
+    RelayEnabled = 3,
+    RelayDisabled = 4,
+    /// The argument is how long the transmitter is blanked for in seconds
+    TransmitBlanked = 5,
+    TransmitResumed = 6,
+    StreamRestarted = 7,
+    GpsReconfigured = 8,
+    /// The argument is the locked sea level pressure in pascals
+    SeaLevelLocked = 9,
+    /// The argument is the byte sent with the echo command
+    Echo = 10,
+    LinkLost = 11,
+    LinkRestored = 12,
+    /// The argument is the command which was rejected
+    CommandRejected = 13,
+    /// The argument is the new BMP581 sample rate in hertz
+    BaroRateSet = 14,
+    /// The argument is the new primary GPS fix rate in hertz
+    GpsRateSet = 15,
+    /// No good enough fix was found for a fix capture in time
+    FixCaptureTimedOut = 16,
+    /// The argument is how far apart the GPS and barometric altitudes are
+    /// in meters
+    AltitudeDiverged = 17,
+    AltitudeAgreed = 18,
+    RecorderEnabled = 19,
+    RecorderDisabled = 20,
+    /// The argument is the index of the general purpose output
+    OutputSet = 21,
+    /// The argument is the index of the general purpose output
+    OutputCleared = 22,
+    /// Every I²C sensor stopped responding at once. The argument is the
+    /// number of the attempt to recover the bus.
+    I2cBusLocked = 23,
+    I2cBusRecovered = 24,
+    /// No more attempts will be made to recover the I²C bus
+    I2cBusRecoveryFailed = 25,
+    /// The argument is the [`Diagnostics`](crate::diagnostics::Diagnostics)
+    /// bitmask of working subsystems
+    DiagnosticsResult = 26,
+    /// The argument is the seconds since the payload started
+    GpsFixAcquired = 27,
+    /// Both the barometer and GPS found apogee. The argument is the peak
+    /// altitude in meters.
+    Apogee = 28,
+    /// Only one sensor found apogee, or the two disagreed. The argument is
+    /// the peak altitude in meters.
+    ApogeeLowConfidence = 29,
+    /// The argument is the pressure tared to in pascals
+    SensorsTared = 30,
+    /// The buzzer turned itself off to conserve battery
+    BuzzerTimedOut = 31,
+    /// A change to the radios was applied, and is reverted unless confirmed
+    RadioChangePending = 32,
+    RadioChangeConfirmed = 33,
+    /// A change to the radios wasn't confirmed in time
+    RadioChangeReverted = 34,
+    OutputsArmed = 35,
+    /// Every interlocked output was cleared
+    OutputsDisarmed = 36,
+}
+
+impl EventCode {
+    /// A human readable description of the event.
+    pub fn description(self) -> &'static str {
+        match self {
+            EventCode::BuzzerStarted => "Buzzer started",
+            EventCode::BuzzerStopped => "Buzzer stopped",
+            EventCode::RelayEnabled => "Relay enabled",
+            EventCode::RelayDisabled => "Relay disabled",
+            EventCode::TransmitBlanked => "Transmit blanked",
+            EventCode::TransmitResumed => "Transmit resumed",
+            EventCode::StreamRestarted => "Restarted streaming service",
+            EventCode::GpsReconfigured => "GPS reconfigured",
+            EventCode::SeaLevelLocked => "Sea level pressure locked",
+            EventCode::Echo => "Echo",
+            EventCode::LinkLost => "Uplink lost",
+            EventCode::LinkRestored => "Uplink restored",
+            EventCode::CommandRejected => "Command rejected",
+            EventCode::BaroRateSet => "Barometer rate set",
+            EventCode::GpsRateSet => "GPS rate set",
+            EventCode::FixCaptureTimedOut => "Fix capture timed out",
+            EventCode::AltitudeDiverged => "GPS and barometric altitudes disagree",
+            EventCode::AltitudeAgreed => "GPS and barometric altitudes agree",
+            EventCode::RecorderEnabled => "Recorder enabled",
+            EventCode::RecorderDisabled => "Recorder disabled",
+            EventCode::OutputSet => "Output set",
+            EventCode::OutputCleared => "Output cleared",
+            EventCode::I2cBusLocked => "I2C bus locked up, recovering",
+            EventCode::I2cBusRecovered => "I2C bus recovered",
+            EventCode::I2cBusRecoveryFailed => "I2C bus recovery failed",
+            EventCode::DiagnosticsResult => "Diagnostics",
+            EventCode::GpsFixAcquired => "GPS fix acquired",
+            EventCode::Apogee => "Apogee",
+            EventCode::ApogeeLowConfidence => "Apogee (low confidence)",
+            EventCode::SensorsTared => "Sensors tared",
+            EventCode::BuzzerTimedOut => "Buzzer timed out",
+            EventCode::RadioChangePending => "Radio change pending confirmation",
+            EventCode::RadioChangeConfirmed => "Radio change confirmed",
+            EventCode::RadioChangeReverted => "Radio change reverted",
+            EventCode::OutputsArmed => "Outputs armed",
+            EventCode::OutputsDisarmed => "Outputs disarmed",
+        }
+    }
+}
+
+/// An event which occurred on the air side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    /// The [`EventCode`] of this event. This is kept as a number, so events
+    /// from newer code can still be decoded.
+    #[serde(rename = "c")]
+    pub code: u16,
+    /// Extra information, whose meaning depends on the code
+    #[serde(rename = "a", default, skip_serializing_if = "Option::is_none")]
+    pub arg: Option<u32>,
+}
+
+impl Event {
+    pub fn new(code: EventCode) -> Self {
+        Self {
+            code: code as u16,
+            arg: None,
+        }
+    }
+
+    pub fn with_arg(code: EventCode, arg: u32) -> Self {
+        Self {
+            code: code as u16,
+            arg: Some(arg),
+        }
+    }
+
+    /// Look up the code of this event, if it is known.
+    pub fn event_code(&self) -> Option<EventCode> {
+        EventCode::from_u16(self.code)
+    }
+}
+
+/// Renders the event as human readable text, e.g. `Transmit blanked (60)`.
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.event_code() {
+            Some(code) => write!(f, "{}", code.description())?,
+            None => write!(f, "Unknown event {}", self.code)?,
+        }
+
+        match self.arg {
+            Some(arg) => write!(f, " ({arg})"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A message to send to the ground, either as an event code or free text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Event(Event),
+    Text(String),
+}
+
+impl From<Event> for Message {
+    fn from(event: Event) -> Self {
+        Message::Event(event)
+    }
+}
+
+impl From<EventCode> for Message {
+    fn from(code: EventCode) -> Self {
+        Message::Event(Event::new(code))
+    }
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Self {
+        Message::Text(text)
+    }
+}
+
+/// Holds events waiting to be sent. Each event is sent in a few packets in
+/// a row, so one isn't lost with a single dropped packet, and then dropped,
+/// so packets only carry events when something has happened.
+#[derive(Debug, Clone)]
+pub struct EventQueue {
+    /// Each event along with the number of packets it is still to be sent in
+    events: VecDeque<(Event, u32)>,
+    repeats: u32,
+    max_events: usize,
+}
+
+impl EventQueue {
+    /// Create a queue which sends each event in `repeats` packets, and
+    /// holds at most `max_events` events.
+    pub fn new(repeats: u32, max_events: usize) -> Self {
+        Self {
+            events: VecDeque::new(),
+            repeats,
+            max_events,
+        }
+    }
+
+    /// Queue an event to be sent in the following packets.
+    ///
+    /// Returns `false` if the queue was full, in which case the oldest
+    /// event was dropped to make room.
+    pub fn push(&mut self, event: Event) -> bool {
+        self.events.push_back((event, self.repeats));
+
+        if self.events.len() > self.max_events {
+            self.events.pop_front();
+            return false;
+        }
+
+        true
+    }
+
+    /// The events to put in the next packet, counting them as sent.
+    pub fn next_frame(&mut self) -> VecDeque<Event> {
+        let events = self.events.iter().map(|(event, _)| *event).collect();
+
+        for (_, remaining) in &mut self.events {
+            *remaining = remaining.saturating_sub(1);
+        }
+        self.events.retain(|(_, remaining)| *remaining > 0);
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_round_trip_and_rendering() {
+        let event = Event::with_arg(EventCode::TransmitBlanked, 60);
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(json, r#"{"c":5,"a":60}"#);
+
+        let decoded: Event = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, event);
+        assert_eq!(decoded.event_code(), Some(EventCode::TransmitBlanked));
+        assert_eq!(decoded.to_string(), "Transmit blanked (60)");
+
+        let decoded: Event = serde_json::from_str(r#"{"c":12}"#).unwrap();
+        assert_eq!(decoded, Event::new(EventCode::LinkRestored));
+        assert_eq!(decoded.to_string(), "Uplink restored");
+
+        // Codes from newer code still decode
+        let decoded: Event = serde_json::from_str(r#"{"c":65535,"a":1}"#).unwrap();
+        assert_eq!(decoded.event_code(), None);
+        assert_eq!(decoded.to_string(), "Unknown event 65535 (1)");
+    }
+
+    #[test]
+    fn events_are_only_sent_a_few_times() {
+        let mut queue = EventQueue::new(2, 8);
+        assert!(queue.next_frame().is_empty());
+
+        let relay = Event::new(EventCode::RelayEnabled);
+        let buzzer = Event::new(EventCode::BuzzerStarted);
+        queue.push(relay);
+        assert_eq!(queue.next_frame(), [relay]);
+
+        queue.push(buzzer);
+        assert_eq!(queue.next_frame(), [relay, buzzer]);
+        assert_eq!(queue.next_frame(), [buzzer]);
+        assert!(queue.next_frame().is_empty());
+    }
+
+    #[test]
+    fn full_queue_drops_the_oldest() {
+        let mut queue = EventQueue::new(2, 2);
+        for arg in 0..2 {
+            assert!(queue.push(Event::with_arg(EventCode::Echo, arg)));
+        }
+        assert!(!queue.push(Event::with_arg(EventCode::Echo, 2)));
+
+        let args: Vec<_> = queue.next_frame().iter().map(|event| event.arg).collect();
+        assert_eq!(args, [Some(1), Some(2)]);
+    }
+}
@@ -0,0 +1,56 @@
+//! Detection of the GPS acquiring a fix, to confirm readiness on the pad and
+//! measure the time to first fix.
+
+/// Reports when the GPS goes from having no fix to having one.
+#[derive(Debug, Clone, Default)]
+pub struct FixAcquisition {
+    report_reacquired: bool,
+    has_fix: bool,
+    reported: bool,
+}
+
+impl FixAcquisition {
+    /// Create a detector which only reports the first fix, or if
+    /// `report_reacquired` is set, also every fix found again after one is
+    /// lost.
+    pub fn new(report_reacquired: bool) -> Self {
+        Self {
+            report_reacquired,
+            ..Self::default()
+        }
+    }
+
+    /// Update with whether the GPS currently has a fix.
+    ///
+    /// Returns `true` if the fix has just been acquired and should be
+    /// reported.
+    pub fn update(&mut self, has_fix: bool) -> bool {
+        let acquired = has_fix && !self.has_fix;
+        self.has_fix = has_fix;
+
+        if !acquired || (self.reported && !self.report_reacquired) {
+            return false;
+        }
+
+        self.reported = true;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_fix_is_reported_once() {
+        let mut acquisition = FixAcquisition::new(false);
+        let fixes = [false, false, true, true, false, true, true];
+        let reported: Vec<_> = fixes.iter().map(|has_fix| acquisition.update(*has_fix)).collect();
+        assert_eq!(reported, [false, false, true, false, false, false, false]);
+
+        // Fixes found again are only reported when asked for
+        let mut acquisition = FixAcquisition::new(true);
+        let reported: Vec<_> = fixes.iter().map(|has_fix| acquisition.update(*has_fix)).collect();
+        assert_eq!(reported, [false, false, true, false, false, true, false]);
+    }
+}
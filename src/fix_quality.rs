@@ -0,0 +1,58 @@
+//! Requirements a GPS fix must meet before it is trusted.
+
+/// The minimum quality of a GPS fix for some purpose.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixRequirements {
+    /// The fewest satellites the fix may use
+    pub min_satellites: u8,
+    /// The worst horizontal dilution of precision the fix may have. A fix
+    /// without an HDOP is only accepted if this is `None`.
+    pub max_hdop: Option<f32>,
+    /// Whether the receiver must report the fix as valid
+    pub require_valid_fix: bool,
+}
+
+impl FixRequirements {
+    /// Accept any fix with a position, however poor.
+    pub const ANY: Self = Self {
+        min_satellites: 0,
+        max_hdop: None,
+        require_valid_fix: false,
+    };
+
+    /// Whether a fix with the given metadata meets these requirements.
+    pub fn is_met(&self, fix_valid: bool, satellites: u8, hdop: Option<f32>) -> bool {
+        let hdop_ok = match self.max_hdop {
+            Some(max) => hdop.is_some_and(|hdop| hdop <= max),
+            None => true,
+        };
+
+        (fix_valid || !self.require_valid_fix) && satellites >= self.min_satellites && hdop_ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOWNLINK: FixRequirements = FixRequirements {
+        min_satellites: 4,
+        max_hdop: Some(5.0),
+        require_valid_fix: true,
+    };
+
+    #[test]
+    fn threshold_suppresses_poor_fixes() {
+        assert!(DOWNLINK.is_met(true, 4, Some(5.0)));
+        assert!(DOWNLINK.is_met(true, 12, Some(0.8)));
+
+        // Too few satellites, too poor an HDOP, or no HDOP at all
+        assert!(!DOWNLINK.is_met(true, 3, Some(1.0)));
+        assert!(!DOWNLINK.is_met(true, 8, Some(5.1)));
+        assert!(!DOWNLINK.is_met(true, 8, None));
+
+        // No fix at all is just the poorest fix
+        assert!(!DOWNLINK.is_met(false, 8, Some(1.0)));
+        assert!(FixRequirements::ANY.is_met(false, 0, None));
+    }
+}
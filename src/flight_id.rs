@@ -0,0 +1,119 @@
+use std::{
+    fs,
+    hash::{BuildHasher, RandomState},
+    io,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use tracing::{info, warn};
+
+/// An existing flight ID is reused if it was last refreshed less than this
+/// long ago, so a reboot in the middle of a flight stays on the same flight.
+const FLIGHT_ID_REUSE_WINDOW: Duration = Duration::from_secs(30 * 60);
+
+/// Load the flight ID persisted at `path` if it is recent enough, otherwise
+/// generate and persist a new one.
+///
+/// Failing to persist a new ID is only logged, as the ID is still useful
+/// for tagging this run.
+pub fn load_or_create(path: &Path) -> String {
+    if let Some(id) = load_recent(path) {
+        info!("Reusing recent flight ID {id}");
+        return id;
+    }
+
+    let id = generate();
+    if let Err(e) = fs::write(path, &id) {
+        warn!("Could not persist flight ID to {}: {e}", path.display());
+    }
+
+    id
+}
+
+/// Mark the flight ID persisted at `path` as still in use.
+pub fn refresh(path: &Path, id: &str) -> io::Result<()> {
+    fs::write(path, id)
+}
+
+fn load_recent(path: &Path) -> Option<String> {
+    let age = fs::metadata(path).ok()?.modified().ok()?.elapsed().ok()?;
+    if age > FLIGHT_ID_REUSE_WINDOW {
+        return None;
+    }
+
+    let id = fs::read_to_string(path).ok()?.trim().to_string();
+    (!id.is_empty()).then_some(id)
+}
+
+/// Generate a short random flight ID, e.g. `3fa91c07`.
+fn generate() -> String {
+    // `RandomState` is seeded randomly by the OS, so hashing the current
+    // time with it is random enough to tell flights apart.
+    let hash = RandomState::new().hash_one(SystemTime::now());
+
+    format!("{:08x}", hash as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use arowss::{decode_frame, encode_frame, frame::SYNC_WORD, recording::{read_records, segments}, TelemetryPacket};
+
+    use super::*;
+    use crate::recorder::{Recorder, RecordingLimits};
+
+    /// A path in the temporary directory, unique to the test.
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("arowss_flight_id_{name}_{}", std::process::id()));
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn recent_id_is_reused() {
+        let path = temp_path("reuse");
+        let _ = fs::remove_file(&path);
+
+        let id = load_or_create(Path::new(&path));
+        assert_eq!(id.len(), 8);
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit()), "{id}");
+
+        // As after a reboot mid-flight
+        assert_eq!(load_or_create(Path::new(&path)), id);
+
+        // Long after the last refresh it is a new flight
+        let stale = SystemTime::now() - FLIGHT_ID_REUSE_WINDOW - Duration::from_secs(60);
+        fs::File::options().write(true).open(&path).unwrap().set_modified(stale).unwrap();
+        assert_ne!(load_or_create(Path::new(&path)), id);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn id_is_in_recording_and_packet() {
+        let path = temp_path("tagged");
+        let id = load_or_create(Path::new(&path));
+
+        let packet = TelemetryPacket {
+            flight_id: Some(id.clone()),
+            ..TelemetryPacket::default()
+        };
+        let frame = encode_frame(0, &packet);
+
+        let prefix = temp_path(&format!("telemetry_{id}"));
+        let mut recorder = Recorder::open(prefix.clone(), RecordingLimits::default()).await;
+        recorder.write(&frame[SYNC_WORD.len()..frame.len() - 1]).await;
+        drop(recorder);
+
+        // Both the name of the recording and the packets in it
+        let (_, segment) = segments(&prefix).unwrap().remove(0);
+        assert!(segment.to_str().unwrap().contains(&id));
+        let records = read_records(&prefix).unwrap();
+        assert_eq!(records[0].1.flight_id.as_ref(), Some(&id));
+
+        let (_, decoded) = decode_frame(&frame).unwrap();
+        assert_eq!(decoded.flight_id, Some(id));
+
+        fs::remove_file(segment).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,643 @@
+//! The framing of packets sent from the rocket to the ground.
+//!
+//! Every frame begins with the [`SYNC_WORD`], followed by the sequence
+//! number of the frame and the CRC of the JSON data as decimal numbers, each
+//! followed by a space, followed by the JSON data, and terminated by a
+//! newline (`\n`).
+//!
+//! The sequence number counts up by one for every frame sent, wrapping from
+//! 255 back to 0, so the ground can tell exactly which frames it missed.
+
+use crate::{schema::decode_packet, utils::crc8, validation::PlausibilityError, TelemetryPacket};
+
+/// Marks the start of every frame.
+///
+/// Neither of these bytes can appear in UTF-8 text, so the sync word can
+/// never appear inside the JSON data of a frame.
+pub const SYNC_WORD: [u8; 2] = [0xFE, 0xFF];
+
+/// Frames longer than this are assumed to have lost their newline, and are
+/// discarded by the [`FrameScanner`].
+const MAX_FRAME_BYTES: usize = 4096;
+
+/// Errors which can occur while decoding a frame received from the rocket.
+#[derive(Debug, thiserror::Error)]
+pub enum FrameError {
+    #[error("Frame does not begin with the sync word")]
+    MissingSyncWord,
+    #[error("Frame is not terminated by a newline")]
+    Unterminated,
+    #[error("Frame has no separator between its header and data")]
+    MissingSeparator,
+    #[error("Frame sequence number is not a valid number")]
+    InvalidSequence,
+    #[error("Frame checksum is not a valid number")]
+    InvalidChecksum,
+    #[error("Frame checksum {expected} does not match calculated checksum {actual}")]
+    ChecksumMismatch { expected: u8, actual: u8 },
+    #[error("Frame data is not a valid packet: {0}")]
+    InvalidData(#[from] serde_json::Error),
+    #[error("Frame contains implausible values: {0:?}")]
+    Implausible(Vec<PlausibilityError>),
+}
+
+/// The header of a frame, between the sync word and the packet data.
+///
+/// The sync word is not part of the header, as it is consumed while finding
+/// the start of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// Sequence number of the frame
+    pub seq: u8,
+    /// CRC of the packet data
+    pub crc: u8,
+}
+
+impl FrameHeader {
+    /// The most bytes an encoded header takes up.
+    pub const MAX_LEN: usize = 8;
+
+    /// Append the encoded header to `out`. The sequence number and CRC are
+    /// written in decimal, each followed by a space.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(format!("{} {} ", self.seq, self.crc).as_bytes());
+    }
+
+    /// Read a header from the start of the part of a frame following the
+    /// sync word, returning it along with the rest of the frame.
+    pub fn decode(body: &[u8]) -> Result<(Self, &[u8]), FrameError> {
+        let (seq, body) = split_number(body).ok_or(FrameError::MissingSeparator)?;
+        let seq = seq.ok_or(FrameError::InvalidSequence)?;
+
+        let (crc, data) = split_number(body).ok_or(FrameError::MissingSeparator)?;
+        let crc = crc.ok_or(FrameError::InvalidChecksum)?;
+
+        Ok((Self { seq, crc }, data))
+    }
+}
+
+/// Split a decimal number followed by a space from the start of `bytes`,
+/// returning the number, if it is valid, and the bytes after the space.
+fn split_number(bytes: &[u8]) -> Option<(Option<u8>, &[u8])> {
+    let separator = bytes.iter().position(|b| *b == b' ')?;
+    let number = std::str::from_utf8(&bytes[..separator])
+        .ok()
+        .and_then(|number| number.parse().ok());
+
+    Some((number, &bytes[separator + 1..]))
+}
+
+/// Encode a packet into frame number `seq`, to be sent to the ground.
+///
+/// A packet which can't be serialized is replaced by its
+/// [`TelemetryPacket::fallback`], so encoding never fails.
+pub fn encode_frame(seq: u8, packet: &TelemetryPacket) -> Vec<u8> {
+    let (data, crc, _) = packet.vec_crc_or_fallback();
+
+    frame_data(seq, &data, crc)
+}
+
+/// Wrap packet data which has already been serialized, along with its CRC,
+/// into frame number `seq`.
+pub fn frame_data(seq: u8, data: &[u8], crc: u8) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(SYNC_WORD.len() + FrameHeader::MAX_LEN + data.len() + 1);
+    frame.extend_from_slice(&SYNC_WORD);
+    FrameHeader { seq, crc }.encode(&mut frame);
+    frame.extend_from_slice(data);
+    frame.push(b'\n');
+
+    frame
+}
+
+/// Decode a single frame created by [`encode_frame`], including its sync
+/// word and terminating newline, and check it against its CRC. Returns the
+/// sequence number of the frame along with its packet.
+pub fn decode_frame(frame: &[u8]) -> Result<(u8, TelemetryPacket), FrameError> {
+    let body = frame
+        .strip_prefix(&SYNC_WORD)
+        .ok_or(FrameError::MissingSyncWord)?;
+
+    decode_body(body)
+}
+
+/// Decode the part of a frame following the sync word.
+fn decode_body(body: &[u8]) -> Result<(u8, TelemetryPacket), FrameError> {
+    let body = body.strip_suffix(b"\n").ok_or(FrameError::Unterminated)?;
+
+    decode_header_and_data(body)
+}
+
+/// Decode the header and data of a frame, without its sync word or newline.
+pub(crate) fn decode_header_and_data(body: &[u8]) -> Result<(u8, TelemetryPacket), FrameError> {
+    let (header, data) = FrameHeader::decode(body)?;
+
+    let expected = header.crc;
+    let actual = crc8(data);
+    if expected != actual {
+        return Err(FrameError::ChecksumMismatch { expected, actual });
+    }
+
+    Ok((header.seq, decode_packet(data)?))
+}
+
+/// Finds and decodes frames in a stream of bytes received from the rocket.
+///
+/// Bytes are discarded until a sync word is found, and then collected until
+/// the terminating newline. If another sync word appears before the newline,
+/// the partial frame is dropped and the new frame is collected instead, so
+/// corruption only loses the frames it touches.
+#[derive(Debug, Clone)]
+pub struct FrameScanner {
+    sync_word: Vec<u8>,
+    buffer: Vec<u8>,
+    locked: bool,
+}
+
+impl Default for FrameScanner {
+    fn default() -> Self {
+        Self::with_sync_word(&SYNC_WORD)
+    }
+}
+
+impl FrameScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a scanner which looks for a sync word other than the default
+    /// [`SYNC_WORD`].
+    ///
+    /// # Panics
+    /// Panics if the sync word is empty.
+    pub fn with_sync_word(sync_word: &[u8]) -> Self {
+        assert!(!sync_word.is_empty(), "Sync word must not be empty");
+
+        Self {
+            sync_word: sync_word.to_vec(),
+            buffer: Vec::new(),
+            locked: false,
+        }
+    }
+
+    /// Add bytes received from the rocket, returning the result of decoding
+    /// each frame they completed.
+    ///
+    /// `bytes` may be split anywhere, including within the sync word. A
+    /// single read holding several frames returns each of them in order,
+    /// and any partial frame at the end is kept for the next call, so every
+    /// frame is decoded exactly once however the stream is chopped up.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<Result<(u8, TelemetryPacket), FrameError>> {
+        let mut frames = Vec::new();
+
+        for &byte in bytes {
+            self.buffer.push(byte);
+
+            if self.buffer.ends_with(&self.sync_word) {
+                // Start of a new frame, abandoning any partial one
+                self.buffer.clear();
+                self.locked = true;
+            } else if !self.locked {
+                // Only keep enough bytes to recognize a sync word split
+                // across reads
+                let keep = self.sync_word.len() - 1;
+                if self.buffer.len() > keep {
+                    self.buffer.drain(..self.buffer.len() - keep);
+                }
+            } else if byte == b'\n' {
+                frames.push(decode_body(&self.buffer));
+                self.buffer.clear();
+                self.locked = false;
+            } else if self.buffer.len() > MAX_FRAME_BYTES {
+                self.buffer.clear();
+                self.locked = false;
+            }
+        }
+
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bno055::mint;
+    use proptest::{collection, option, prelude::*};
+
+    use super::*;
+    use crate::{decimation::SampleRange, events::Event, schema::SchemaVersion, EnvironmentalInfo, GpsInfo, HeadingInfo, Identity, LoopHealth, RawValues, ThermalInfo};
+
+    fn packet() -> TelemetryPacket {
+        TelemetryPacket {
+            gps: Some(GpsInfo {
+                latitude: 40.820_123,
+                longitude: -96.705_654,
+                altitude: 1234.5,
+                satellites: 12,
+                hdop: Some(0.9),
+                geoid_separation: None,
+                speed: Some(152.4),
+                course: None,
+            }),
+            pressure_altitude: Some(1230.25),
+            time: Some(1_767_225_600_000),
+            ..TelemetryPacket::default()
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for seq in [0, 1, 127, 254, 255] {
+            let frame = encode_frame(seq, &packet());
+            let (decoded_seq, decoded) = decode_frame(&frame).unwrap();
+
+            assert_eq!(decoded_seq, seq);
+            assert_eq!(decoded.vec_crc(), packet().vec_crc());
+        }
+    }
+
+    #[test]
+    fn unserializable_packet_sends_fallback() {
+        let mut unserializable = packet();
+        unserializable.computed.insert("broken".to_string(), f64::NAN);
+        assert!(unserializable.try_vec_crc().is_err());
+
+        let (_, _, error) = unserializable.vec_crc_or_fallback();
+        assert!(error.is_some());
+
+        // The frame holds the fallback, which keeps the position and time
+        let (_, decoded) = decode_frame(&encode_frame(3, &unserializable)).unwrap();
+        assert!(decoded.serialization_error);
+        assert!(decoded.computed.is_empty());
+        assert_eq!(decoded.pressure_altitude, None);
+        assert_eq!(decoded.time, packet().time);
+        assert_eq!(decoded.vec_crc(), unserializable.fallback().vec_crc());
+    }
+
+    #[test]
+    fn serialization_is_deterministic() {
+        // The same packet, with its map built up in a different order
+        let build = |computed: &[(&str, f64)]| TelemetryPacket {
+            info: ["first".to_string(), "second".to_string()].into(),
+            events: [Event { code: 10, arg: Some(7) }].into(),
+            computed: computed.iter().map(|&(name, value)| (name.to_string(), value)).collect(),
+            ..packet()
+        };
+        let first = build(&[("da", 1234.5), ("agl", 20.25), ("z", -1.0)]);
+        let second = build(&[("z", -1.0), ("da", 1234.5), ("agl", 20.25)]);
+
+        let (data, crc) = first.vec_crc();
+        assert_eq!(second.vec_crc(), (data.clone(), crc));
+        assert_eq!(encode_frame(3, &first), encode_frame(3, &second));
+
+        // Map keys are sorted, so other languages can reproduce the bytes
+        let json = String::from_utf8(data).unwrap();
+        let keys: Vec<_> = ["\"agl\"", "\"da\"", "\"z\""].iter().map(|key| json.find(key).unwrap()).collect();
+        assert!(keys.is_sorted(), "{json}");
+    }
+
+    #[test]
+    fn header_round_trip() {
+        for seq in [0, 1, 9, 10, 99, 100, 254, 255] {
+            for crc in [0, 7, 200, 255] {
+                let header = FrameHeader { seq, crc };
+                let mut encoded = Vec::new();
+                header.encode(&mut encoded);
+                assert!(encoded.len() <= FrameHeader::MAX_LEN);
+                assert_eq!(encoded, format!("{seq} {crc} ").as_bytes());
+
+                encoded.extend_from_slice(b"{}");
+                let (decoded, rest) = FrameHeader::decode(&encoded).unwrap();
+                assert_eq!(decoded, header);
+                assert_eq!(rest, b"{}");
+            }
+        }
+
+        // One past the largest sequence number is rejected, not wrapped
+        assert!(matches!(FrameHeader::decode(b"256 0 {}"), Err(FrameError::InvalidSequence)));
+        assert!(matches!(FrameHeader::decode(b"255 256 {}"), Err(FrameError::InvalidChecksum)));
+    }
+
+    #[test]
+    fn layout() {
+        let (data, crc) = packet().vec_crc();
+        let frame = encode_frame(42, &packet());
+
+        let mut expected = SYNC_WORD.to_vec();
+        expected.extend_from_slice(format!("42 {crc} ").as_bytes());
+        expected.extend_from_slice(&data);
+        expected.push(b'\n');
+        assert_eq!(frame, expected);
+    }
+
+    #[test]
+    fn malformed_frames() {
+        let (data, _) = packet().vec_crc();
+        let frame = encode_frame(7, &packet());
+        let body = &frame[SYNC_WORD.len()..];
+
+        assert!(matches!(decode_frame(body), Err(FrameError::MissingSyncWord)));
+        assert!(matches!(decode_frame(&frame[..frame.len() - 1]), Err(FrameError::Unterminated)));
+        assert!(matches!(decode_frame(&[0xFE, 0xFF, b'\n']), Err(FrameError::MissingSeparator)));
+        assert!(matches!(decode_frame(b"\xFE\xFF7 {}\n"), Err(FrameError::MissingSeparator)));
+        assert!(matches!(decode_frame(b"\xFE\xFF256 0 {}\n"), Err(FrameError::InvalidSequence)));
+        assert!(matches!(decode_frame(b"\xFE\xFFx 0 {}\n"), Err(FrameError::InvalidSequence)));
+        assert!(matches!(decode_frame(b"\xFE\xFF7 -1 {}\n"), Err(FrameError::InvalidChecksum)));
+
+        // Any single bit flipped in the data is caught by the CRC
+        for i in frame.len() - data.len() - 1..frame.len() - 1 {
+            for bit in 0..8 {
+                let mut corrupted = frame.clone();
+                corrupted[i] ^= 1 << bit;
+                assert!(decode_frame(&corrupted).is_err(), "bit {bit} of byte {i}");
+            }
+        }
+
+        // Valid framing around data which isn't a packet
+        let crc = crc8(b"[1]");
+        let frame = frame_data(0, b"[1]", crc);
+        assert!(matches!(decode_frame(&frame), Err(FrameError::InvalidData(_))));
+    }
+
+    /// Decode a stream of bytes received in the given chunks, returning the
+    /// sequence numbers of the frames decoded.
+    fn scan_chunks<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+        let mut scanner = FrameScanner::new();
+        chunks
+            .into_iter()
+            .flat_map(|chunk| scanner.push(chunk))
+            .map(|frame| {
+                let (seq, decoded) = frame.unwrap();
+                assert_eq!(decoded.vec_crc(), packet().vec_crc());
+                seq
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frames_split_across_reads() {
+        let frames: Vec<_> = (1..=3).map(|seq| encode_frame(seq, &packet())).collect();
+        let stream = [&b"noise"[..], &frames[0], &frames[1], &frames[2]].concat();
+        let second = 5 + frames[0].len();
+
+        // All at once, and a byte at a time
+        assert_eq!(scan_chunks([&stream[..]]), [1, 2, 3]);
+        assert_eq!(scan_chunks(stream.chunks(1)), [1, 2, 3]);
+
+        // Split inside the sync word of the second frame
+        let (first, rest) = stream.split_at(second + 1);
+        assert_eq!(rest[..1], SYNC_WORD[1..]);
+        assert_eq!(scan_chunks([first, rest]), [1, 2, 3]);
+
+        // Split after the first digit of the CRC of the second frame
+        let (_, crc) = packet().vec_crc();
+        let crc_start = second + SYNC_WORD.len() + "2 ".len();
+        assert_eq!(stream[crc_start..].split(|&b| b == b' ').next().unwrap(), crc.to_string().as_bytes());
+        let (first, rest) = stream.split_at(crc_start + 1);
+        assert_eq!(scan_chunks([first, rest]), [1, 2, 3]);
+
+        // And every other split, into two or three reads
+        for split in 0..=stream.len() {
+            let (first, rest) = stream.split_at(split);
+            assert_eq!(scan_chunks([first, rest]), [1, 2, 3], "split at {split}");
+
+            let (middle, last) = rest.split_at(rest.len() / 2);
+            assert_eq!(scan_chunks([first, middle, last]), [1, 2, 3], "split at {split}");
+        }
+    }
+
+    #[test]
+    fn locks_on_after_partial_sync_words() {
+        let frame = encode_frame(7, &packet());
+        let prefixes: [&[u8]; 6] = [
+            &[0xFE],
+            &[0xFE, 0xFE],
+            &[0xFF, 0xFE],
+            &[0xFE, b'x', 0xFE],
+            b"noise\xFE\xFF\xFE",
+            // A frame cut off by the start of the next one
+            b"\xFE\xFF3 12 {\"t\":",
+        ];
+
+        for prefix in prefixes {
+            let stream = [prefix, &frame].concat();
+            assert_eq!(scan_chunks([&stream[..]]), [7], "after {prefix:?}");
+            assert_eq!(scan_chunks(stream.chunks(1)), [7], "after {prefix:?}");
+        }
+    }
+
+    /// A float which [`crate::truncate_float`] keeps exactly, as the
+    /// truncation to hundredths is documented rather than a loss.
+    fn hundredths(range: std::ops::Range<i64>) -> impl Strategy<Value = f64> {
+        range.prop_map(|hundredths| hundredths as f64 / 100.0)
+    }
+
+    fn quaternion() -> impl Strategy<Value = mint::Quaternion<f32>> {
+        (-1.0f32..=1.0, -1.0f32..=1.0, -1.0f32..=1.0, -1.0f32..=1.0)
+            .prop_map(|(x, y, z, s)| mint::Quaternion { v: mint::Vector3 { x, y, z }, s })
+    }
+
+    fn gps() -> impl Strategy<Value = GpsInfo> {
+        (
+            -90.0..=90.0,
+            -180.0..=180.0,
+            -500.0f32..50_000.0,
+            any::<u8>(),
+            option::of(0.0f32..100.0),
+            option::of(-120.0f32..120.0),
+            option::of(0.0f32..1_000.0),
+            option::of(0.0f32..360.0),
+        )
+            .prop_map(|(latitude, longitude, altitude, satellites, hdop, geoid_separation, speed, course)| GpsInfo {
+                latitude,
+                longitude,
+                altitude,
+                satellites,
+                hdop,
+                geoid_separation,
+                speed,
+                course,
+            })
+    }
+
+    fn sample_range() -> impl Strategy<Value = SampleRange> {
+        (hundredths(-10_000_000..10_000_000), hundredths(-10_000_000..10_000_000))
+            .prop_map(|(min, max)| SampleRange { min, max })
+    }
+
+    fn environmental_info() -> impl Strategy<Value = EnvironmentalInfo> {
+        (
+            hundredths(0..12_000_000),
+            option::of(sample_range()),
+            hundredths(-8_000..8_000),
+            option::of(sample_range()),
+            hundredths(0..10_000),
+        )
+            .prop_map(|(pressure, pressure_range, temperature, temperature_range, humidity)| EnvironmentalInfo {
+                pressure,
+                pressure_range,
+                temperature,
+                temperature_range,
+                humidity,
+            })
+    }
+
+    fn identity() -> impl Strategy<Value = Identity> {
+        (any::<u8>(), any::<String>(), collection::vec(any::<String>(), 0..4)).prop_map(
+            |(protocol, firmware, subsystems)| Identity {
+                protocol: SchemaVersion(protocol),
+                firmware,
+                subsystems,
+            },
+        )
+    }
+
+    /// Any packet, with every optional part independently present or not.
+    fn any_packet() -> impl Strategy<Value = TelemetryPacket> {
+        let measurements = (
+            option::of(gps()),
+            option::of(-1.0e5..1.0e5),
+            option::of(environmental_info()),
+            option::of(quaternion()),
+            option::of(-1.0e5..1.0e5),
+            option::of(quaternion()),
+            option::of((0.0f32..360.0, any::<bool>()).prop_map(|(heading_deg, low_confidence)| HeadingInfo {
+                heading_deg,
+                low_confidence,
+            })),
+        );
+        let messages = (
+            collection::vec_deque(any::<String>(), 0..4),
+            collection::vec_deque(
+                (any::<u16>(), option::of(any::<u32>())).prop_map(|(code, arg)| Event { code, arg }),
+                0..4,
+            ),
+            any::<bool>(),
+            option::of(any::<String>()),
+            option::of(identity()),
+            option::of(any::<i64>()),
+        );
+        let extras = (
+            option::of(option::of(-40.0f32..120.0).prop_map(|cpu| ThermalInfo { cpu })),
+            option::of((option::of(any::<u32>()), option::of(any::<i32>()), option::of(any::<i16>())).prop_map(
+                |(bmp_pressure, bmp_temperature, hts_humidity)| RawValues {
+                    bmp_pressure,
+                    bmp_temperature,
+                    hts_humidity,
+                },
+            )),
+            option::of(any::<(u32, u32, u32)>().prop_map(|(last_ms, max_ms, slow)| LoopHealth { last_ms, max_ms, slow })),
+            collection::btree_map(any::<String>(), hundredths(-100_000_000..100_000_000), 0..4),
+            any::<bool>(),
+            any::<bool>(),
+        );
+
+        // Decoding upgrades older versions, and only the current one is sent
+        (measurements, messages, extras).prop_map(
+            |(
+                (gps, pressure_altitude, environmental_info, orientation_info, tared_height, relative_orientation, heading),
+                (info, events, safe_mode, flight_id, identity, time),
+                (thermal, raw, loop_health, computed, fix_acquired, serialization_error),
+            )| TelemetryPacket {
+                schema_version: SchemaVersion::CURRENT,
+                gps,
+                pressure_altitude,
+                environmental_info,
+                orientation_info,
+                info,
+                events,
+                safe_mode,
+                flight_id,
+                identity,
+                time,
+                thermal,
+                raw,
+                loop_health,
+                computed,
+                fix_acquired,
+                serialization_error,
+                tared_height,
+                relative_orientation,
+                heading,
+            },
+        )
+    }
+
+    /// A stretch of a received stream: noise, a whole frame, or a frame with
+    /// one byte corrupted.
+    fn stream_piece() -> impl Strategy<Value = Vec<u8>> {
+        prop_oneof![
+            collection::vec(any::<u8>(), 0..64),
+            (any::<u8>(), any_packet()).prop_map(|(seq, packet)| encode_frame(seq, &packet)),
+            (any::<u8>(), any_packet(), any::<prop::sample::Index>(), any::<u8>()).prop_map(
+                |(seq, packet, at, byte)| {
+                    let mut frame = encode_frame(seq, &packet);
+                    let at = at.index(frame.len());
+                    frame[at] = byte;
+                    frame
+                },
+            ),
+        ]
+    }
+
+    /// The sequence numbers of every sync word in `stream` followed by a
+    /// header and data which match their CRC.
+    fn valid_frame_seqs(stream: &[u8]) -> Vec<u8> {
+        (0..stream.len())
+            .filter_map(|start| stream[start..].strip_prefix(&SYNC_WORD))
+            .filter_map(|rest| rest.split(|&byte| byte == b'\n').next())
+            .filter_map(|body| FrameHeader::decode(body).ok())
+            .filter(|(header, data)| crc8(data) == header.crc)
+            .map(|(header, _)| header.seq)
+            .collect()
+    }
+
+    proptest! {
+        /// Whatever is received, the scanner never panics, and only decodes
+        /// frames which match their CRC.
+        #[test]
+        fn scanner_survives_any_stream(
+            pieces in collection::vec(stream_piece(), 0..8),
+            read_len in 1..64usize,
+        ) {
+            let stream = pieces.concat();
+            let valid = valid_frame_seqs(&stream);
+
+            let mut scanner = FrameScanner::new();
+            for read in stream.chunks(read_len) {
+                for (seq, _) in scanner.push(read).into_iter().flatten() {
+                    prop_assert!(valid.contains(&seq), "frame {seq} decoded without a valid CRC");
+                }
+            }
+        }
+
+        /// However much noise comes first, the frame after it is found, read
+        /// all at once or in pieces.
+        #[test]
+        fn locks_on_after_noise(
+            noise in collection::vec(any::<u8>(), 0..256),
+            read_len in 1..64usize,
+        ) {
+            let stream = [noise, encode_frame(7, &packet())].concat();
+
+            let mut scanner = FrameScanner::new();
+            let frames: Vec<_> = stream.chunks(read_len).flat_map(|read| scanner.push(read)).collect();
+            let Some(Ok((seq, decoded))) = frames.last() else {
+                panic!("frame not found, got {frames:?}");
+            };
+            prop_assert_eq!(*seq, 7);
+            prop_assert_eq!(decoded.vec_crc(), packet().vec_crc());
+        }
+
+        /// Every packet survives a frame intact. Packets have no equality,
+        /// so the decoded packet must serialize to exactly the same JSON.
+        #[test]
+        fn any_packet_round_trips(seq in any::<u8>(), packet in any_packet()) {
+            let (decoded_seq, decoded) = decode_frame(&encode_frame(seq, &packet)).unwrap();
+
+            prop_assert_eq!(decoded_seq, seq);
+            prop_assert_eq!(
+                String::from_utf8(decoded.vec_crc().0).unwrap(),
+                String::from_utf8(packet.vec_crc().0).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! Compass heading from the orientation the BNO055 fuses from its
+//! accelerometer, gyroscope and magnetometer.
+
+use bno055::mint::Quaternion;
+
+/// The magnetometer calibration level, out of 3 as reported by the BNO055,
+/// below which a heading is flagged as low confidence.
+pub const MIN_MAG_CALIBRATION: u8 = 3;
+
+/// Wrap an angle in degrees into `[0, 360)`.
+pub fn wrap_degrees(degrees: f32) -> f32 {
+    let wrapped = degrees.rem_euclid(360.0);
+
+    // Rounding can leave a tiny negative angle at exactly 360
+    if wrapped >= 360.0 { 0.0 } else { wrapped }
+}
+
+/// The heading of the sensor's X axis clockwise from true north, in
+/// degrees, from its fused orientation.
+///
+/// The orientation is relative to magnetic north, with X east, Y north and Z
+/// up. The X axis is projected onto the horizontal, so the heading holds
+/// while the payload is tilted. `declination` is how far magnetic north is
+/// east of true north, in degrees. Returns `None` if the X axis is close to
+/// vertical, so has no heading.
+pub fn heading(orientation: Quaternion<f32>, declination: f32) -> Option<f32> {
+    let Quaternion { v, s } = orientation;
+
+    // The X axis rotated into the earth frame, scaled by the squared norm of
+    // the quaternion so it needn't be normalized
+    let east = s * s + v.x * v.x - v.y * v.y - v.z * v.z;
+    let north = 2.0 * (v.x * v.y + s * v.z);
+    let norm = s * s + v.x * v.x + v.y * v.y + v.z * v.z;
+
+    if !east.is_finite() || !north.is_finite() || east.hypot(north) <= norm * 1e-3 {
+        return None;
+    }
+
+    let magnetic = east.atan2(north).to_degrees();
+
+    Some(wrap_degrees(magnetic + declination))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    use bno055::mint::Vector3;
+
+    use super::*;
+
+    /// An orientation turned `degrees` anticlockwise about the vertical.
+    fn yawed(degrees: f32) -> Quaternion<f32> {
+        let half = degrees.to_radians() / 2.0;
+        Quaternion { v: Vector3 { x: 0.0, y: 0.0, z: half.sin() }, s: half.cos() }
+    }
+
+    fn assert_heading(orientation: Quaternion<f32>, declination: f32, expected: f32) {
+        let heading = heading(orientation, declination).unwrap();
+        assert!((heading - expected).abs() < 1e-3, "heading {heading}, expected {expected}");
+    }
+
+    #[test]
+    fn declination_and_wrap() {
+        // Unturned, the X axis points magnetic east
+        assert_heading(yawed(0.0), 0.0, 90.0);
+        assert_heading(yawed(0.0), 2.5, 92.5);
+
+        // Turned to magnetic north, a westerly declination wraps below 0
+        assert_heading(yawed(90.0), 0.0, 0.0);
+        assert_heading(yawed(90.0), -10.0, 350.0);
+
+        // Just west of magnetic north, an easterly declination wraps past 360
+        assert_heading(yawed(100.0), 0.0, 350.0);
+        assert_heading(yawed(100.0), 15.0, 5.0);
+
+        // Pointing straight up there is no heading
+        let up = Quaternion { v: Vector3 { x: 0.0, y: -FRAC_1_SQRT_2, z: 0.0 }, s: FRAC_1_SQRT_2 };
+        assert_eq!(heading(up, 2.5), None);
+    }
+}
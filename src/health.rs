@@ -0,0 +1,167 @@
+use std::{ffi::CString, fmt, fs, time::Duration};
+
+/// The filesystem whose free space is reported, which holds the recordings.
+const DISK_PATH: &str = "/";
+const MEMINFO_PATH: &str = "/proc/meminfo";
+const LOADAVG_PATH: &str = "/proc/loadavg";
+const UPTIME_PATH: &str = "/proc/uptime";
+const CPU_TEMP_PATH: &str = "/sys/class/thermal/thermal_zone0/temp";
+
+/// A snapshot of the health of the flight computer.
+///
+/// Each value is `None` if it could not be read.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SystemHealth {
+    pub disk_free_bytes: Option<u64>,
+    pub memory_available_kb: Option<u64>,
+    pub cpu_temperature: Option<f32>,
+    pub uptime: Option<Duration>,
+    pub load_average: Option<f32>,
+}
+
+impl SystemHealth {
+    /// Read the current health from `/proc` and `/sys`.
+    pub fn gather() -> Self {
+        let read = |path| fs::read_to_string(path).ok();
+
+        Self {
+            disk_free_bytes: disk_free_bytes(DISK_PATH),
+            memory_available_kb: read(MEMINFO_PATH).as_deref().and_then(parse_meminfo),
+            cpu_temperature: cpu_temperature(),
+            uptime: read(UPTIME_PATH).as_deref().and_then(parse_uptime),
+            load_average: read(LOADAVG_PATH).as_deref().and_then(parse_loadavg),
+        }
+    }
+}
+
+/// A compact summary for the info downlink, e.g.
+/// `disk 1204M mem 512M cpu 45.3C up 1h23m load 0.52`.
+impl fmt::Display for SystemHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.disk_free_bytes {
+            Some(bytes) => write!(f, "disk {}M", bytes / (1024 * 1024))?,
+            None => write!(f, "disk ?")?,
+        }
+        match self.memory_available_kb {
+            Some(kb) => write!(f, " mem {}M", kb / 1024)?,
+            None => write!(f, " mem ?")?,
+        }
+        match self.cpu_temperature {
+            Some(temp) => write!(f, " cpu {temp:.1}C")?,
+            None => write!(f, " cpu ?")?,
+        }
+        match self.uptime {
+            Some(uptime) => {
+                let minutes = uptime.as_secs() / 60;
+                write!(f, " up {}h{:02}m", minutes / 60, minutes % 60)?
+            }
+            None => write!(f, " up ?")?,
+        }
+        match self.load_average {
+            Some(load) => write!(f, " load {load:.2}"),
+            None => write!(f, " load ?"),
+        }
+    }
+}
+
+/// Read the CPU temperature in degrees Celsius, if the thermal zone is
+/// available.
+pub fn cpu_temperature() -> Option<f32> {
+    read_thermal(CPU_TEMP_PATH)
+}
+
+/// Read the temperature in degrees Celsius from a thermal zone's `temp`
+/// file at `path`, if it is available.
+fn read_thermal(path: &str) -> Option<f32> {
+    parse_thermal(&fs::read_to_string(path).ok()?)
+}
+
+/// Read the available memory in kB from the contents of `/proc/meminfo`.
+fn parse_meminfo(meminfo: &str) -> Option<u64> {
+    meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Read the 1 minute load average from the contents of `/proc/loadavg`.
+fn parse_loadavg(loadavg: &str) -> Option<f32> {
+    loadavg.split_whitespace().next()?.parse().ok()
+}
+
+/// Read the uptime from the contents of `/proc/uptime`.
+fn parse_uptime(uptime: &str) -> Option<Duration> {
+    let seconds: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+/// Read a temperature in degrees Celsius from the contents of a thermal
+/// zone's `temp` file, which is in millidegrees.
+fn parse_thermal(temp: &str) -> Option<f32> {
+    let millidegrees: i32 = temp.trim().parse().ok()?;
+    Some(millidegrees as f32 / 1000.0)
+}
+
+/// The space available to unprivileged users on the filesystem at `path`.
+fn disk_free_bytes(path: &str) -> Option<u64> {
+    let path = CString::new(path).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+
+    // SAFETY: `path` is a valid C string, and `stat` is only read after
+    // `statvfs` reports that it has filled it in.
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEMINFO: &str = "\
+MemTotal:        3884316 kB
+MemFree:          215400 kB
+MemAvailable:    2923184 kB
+Buffers:          142504 kB
+";
+
+    #[test]
+    fn parses_proc_files() {
+        assert_eq!(parse_meminfo(MEMINFO), Some(2_923_184));
+        assert_eq!(parse_loadavg("0.52 0.58 0.59 1/312 4242\n"), Some(0.52));
+        assert_eq!(parse_uptime("5012.34 19523.01\n"), Some(Duration::from_millis(5_012_340)));
+    }
+
+    #[test]
+    fn malformed_proc_files_are_none() {
+        assert_eq!(parse_meminfo(""), None);
+        assert_eq!(parse_meminfo("MemTotal:        3884316 kB\n"), None);
+        assert_eq!(parse_meminfo("MemAvailable:    lots kB\n"), None);
+        assert_eq!(parse_loadavg(""), None);
+        assert_eq!(parse_loadavg("high 0.58 0.59\n"), None);
+        assert_eq!(parse_uptime(""), None);
+        assert_eq!(parse_uptime("-5012.34 19523.01\n"), None);
+        assert_eq!(parse_uptime("NaN 19523.01\n"), None);
+    }
+
+    #[test]
+    fn parses_thermal_zone() {
+        assert_eq!(parse_thermal("45312\n"), Some(45.312));
+        assert_eq!(parse_thermal("-5250\n"), Some(-5.25));
+        assert_eq!(parse_thermal("hot\n"), None);
+    }
+
+    #[test]
+    fn missing_thermal_zone_is_none() {
+        assert_eq!(read_thermal("/sys/class/thermal/thermal_zone_missing/temp"), None);
+    }
+}
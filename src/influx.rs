@@ -0,0 +1,194 @@
+//! Formatting of decoded packets as InfluxDB line protocol, for loading
+//! telemetry into a time series database.
+
+use std::fmt::Write as _;
+
+use crate::TelemetryPacket;
+
+/// A value of a field in line protocol.
+enum FieldValue {
+    Float(f64),
+    /// Kept separate from [`FieldValue::Float`] so it is written without the
+    /// noise of widening to an `f64`
+    Single(f32),
+    Integer(i64),
+    Boolean(bool),
+}
+
+impl TelemetryPacket {
+    /// Format this packet as a single line of InfluxDB line protocol under
+    /// `measurement`, without a trailing newline.
+    ///
+    /// Values which are absent from the packet are omitted. The flight ID is
+    /// written as a tag when present, and the packet's time as a timestamp
+    /// in milliseconds, so the data should be written with `precision=ms`.
+    /// Returns `None` if the packet has no values at all, as a line must
+    /// have at least one field.
+    pub fn to_line_protocol(&self, measurement: &str) -> Option<String> {
+        let mut fields = Vec::new();
+
+        if let Some(gps) = self.gps {
+            fields.push(("lat", FieldValue::Float(gps.latitude)));
+            fields.push(("lon", FieldValue::Float(gps.longitude)));
+            fields.push(("alt", FieldValue::Single(gps.altitude)));
+            fields.push(("sats", FieldValue::Integer(i64::from(gps.satellites))));
+            let optional = [
+                ("hdop", gps.hdop),
+                ("geoid", gps.geoid_separation),
+                ("spd", gps.speed),
+                ("crs", gps.course),
+            ];
+            for (key, value) in optional {
+                if let Some(value) = value {
+                    fields.push((key, FieldValue::Single(value)));
+                }
+            }
+        }
+
+        if let Some(p_alt) = self.pressure_altitude {
+            fields.push(("p_alt", FieldValue::Float(p_alt)));
+        }
+
+        if let Some(env) = self.environmental_info {
+            fields.push(("pres", FieldValue::Float(env.pressure)));
+            fields.push(("temp", FieldValue::Float(env.temperature)));
+            fields.push(("humid", FieldValue::Float(env.humidity)));
+        }
+
+        if let Some(imu) = self.orientation_info {
+            fields.push(("imu_w", FieldValue::Single(imu.s)));
+            fields.push(("imu_x", FieldValue::Single(imu.v.x)));
+            fields.push(("imu_y", FieldValue::Single(imu.v.y)));
+            fields.push(("imu_z", FieldValue::Single(imu.v.z)));
+        }
+
+        if let Some(cpu) = self.thermal.and_then(|thermal| thermal.cpu) {
+            fields.push(("cpu_temp", FieldValue::Single(cpu)));
+        }
+
+        // Infinite and NaN floats are not valid line protocol
+        fields.retain(|(_, value)| match value {
+            FieldValue::Float(float) => float.is_finite(),
+            FieldValue::Single(float) => float.is_finite(),
+            _ => true,
+        });
+
+        // Without any other values, the safe mode flag is not worth a line
+        if fields.is_empty() {
+            return None;
+        }
+        fields.push(("safe", FieldValue::Boolean(self.safe_mode)));
+
+        let mut line = escape(measurement, &[',', ' ']);
+        if let Some(flight_id) = &self.flight_id {
+            let _ = write!(line, ",flight={}", escape(flight_id, &[',', '=', ' ']));
+        }
+
+        for (i, (key, value)) in fields.iter().enumerate() {
+            line.push(if i == 0 { ' ' } else { ',' });
+            let _ = match value {
+                FieldValue::Float(float) => write!(line, "{key}={float}"),
+                FieldValue::Single(float) => write!(line, "{key}={float}"),
+                FieldValue::Integer(integer) => write!(line, "{key}={integer}i"),
+                FieldValue::Boolean(boolean) => write!(line, "{key}={boolean}"),
+            };
+        }
+
+        if let Some(time) = self.time {
+            let _ = write!(line, " {time}");
+        }
+
+        Some(line)
+    }
+}
+
+/// Escape the given characters, and backslashes, with a backslash.
+fn escape(value: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '\\' || special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{EnvironmentalInfo, GpsInfo};
+
+    use super::*;
+
+    /// Split a line on the spaces which aren't escaped.
+    fn split_unescaped(line: &str) -> Vec<&str> {
+        let mut parts = Vec::new();
+        let mut start = 0;
+        let mut escaped = false;
+        for (i, c) in line.char_indices() {
+            match c {
+                '\\' if !escaped => escaped = true,
+                ' ' if !escaped => {
+                    parts.push(&line[start..i]);
+                    start = i + 1;
+                }
+                _ => escaped = false,
+            }
+        }
+        parts.push(&line[start..]);
+
+        parts
+    }
+
+    #[test]
+    fn packet_is_valid_line_protocol() {
+        let packet = TelemetryPacket {
+            gps: Some(GpsInfo {
+                latitude: 40.8,
+                longitude: -96.7,
+                altitude: 350.5,
+                satellites: 9,
+                hdop: None,
+                geoid_separation: None,
+                speed: Some(2.5),
+                course: None,
+            }),
+            environmental_info: Some(EnvironmentalInfo {
+                pressure: 97_000.25,
+                pressure_range: None,
+                temperature: f64::NAN,
+                temperature_range: None,
+                humidity: 40.5,
+            }),
+            flight_id: Some("test flight".to_string()),
+            time: Some(1_760_000_000_000),
+            ..TelemetryPacket::default()
+        };
+
+        let line = packet.to_line_protocol("arowss").unwrap();
+        assert_eq!(
+            line,
+            "arowss,flight=test\\ flight lat=40.8,lon=-96.7,alt=350.5,sats=9i,spd=2.5,pres=97000.25,humid=40.5,safe=false 1760000000000"
+        );
+
+        // Measurement and tags, fields, then the timestamp
+        let [series, fields, timestamp] = split_unescaped(&line)[..] else {
+            panic!("line {line:?} doesn't have three parts");
+        };
+        assert!(series.starts_with("arowss,"));
+        assert!(timestamp.parse::<i64>().is_ok());
+        for field in fields.split(',') {
+            let (key, value) = field.split_once('=').unwrap();
+            assert!(!key.is_empty());
+            let valid = value.parse::<f64>().is_ok_and(f64::is_finite)
+                || value.strip_suffix('i').is_some_and(|int| int.parse::<i64>().is_ok())
+                || value == "true"
+                || value == "false";
+            assert!(valid, "field {field:?} has an invalid value");
+        }
+
+        // A line needs at least one field
+        assert_eq!(TelemetryPacket::default().to_line_protocol("arowss"), None);
+    }
+}
@@ -0,0 +1,125 @@
+//! Metering of info messages onto the downlink, so a burst of messages can't
+//! crowd the sensor data out of the packets.
+
+use std::collections::VecDeque;
+
+/// Holds info messages waiting to be sent, along with the recent messages
+/// repeated in every packet so one isn't lost with a single dropped packet.
+#[derive(Debug, Clone)]
+pub struct InfoQueue {
+    pending: VecDeque<String>,
+    sent: VecDeque<String>,
+    max_pending: usize,
+    max_repeated: usize,
+    max_bytes_per_frame: usize,
+}
+
+impl InfoQueue {
+    /// Create a queue which puts at most `max_repeated` messages, totalling
+    /// at most `max_bytes_per_frame`, in each packet, and holds at most
+    /// `max_pending` messages waiting for room.
+    pub fn new(max_pending: usize, max_repeated: usize, max_bytes_per_frame: usize) -> Self {
+        Self {
+            pending: VecDeque::new(),
+            sent: VecDeque::new(),
+            max_pending,
+            max_repeated,
+            max_bytes_per_frame,
+        }
+    }
+
+    /// Queue a message to be sent once there is room.
+    ///
+    /// Returns `false` if the queue was full, in which case the oldest
+    /// waiting message was dropped to make room.
+    pub fn push(&mut self, message: String) -> bool {
+        self.pending.push_back(message);
+
+        if self.pending.len() > self.max_pending {
+            self.pending.pop_front();
+            return false;
+        }
+
+        true
+    }
+
+    /// The number of messages waiting to be sent for the first time.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Choose the messages to put in the next packet.
+    ///
+    /// New messages take the place of ones which have already been sent at
+    /// least once. Messages which don't fit wait for a later packet, unless
+    /// the packet has no info at all, so a message larger than the limit
+    /// can't block the queue.
+    pub fn next_frame(&mut self) -> VecDeque<String> {
+        let mut added = 0;
+
+        while let Some(next) = self.pending.front() {
+            let fits = |sent: &VecDeque<String>| {
+                sent.len() < self.max_repeated
+                    && sent.iter().map(String::len).sum::<usize>() + next.len() <= self.max_bytes_per_frame
+            };
+
+            // Make room by dropping repeats, but never a message added to
+            // this packet
+            while self.sent.len() > added && !fits(&self.sent) {
+                self.sent.pop_front();
+            }
+
+            if !self.sent.is_empty() && !fits(&self.sent) {
+                break;
+            }
+
+            if let Some(message) = self.pending.pop_front() {
+                self.sent.push_back(message);
+                added += 1;
+            }
+        }
+
+        self.sent.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_is_spread_across_frames() {
+        let mut queue = InfoQueue::new(32, 4, 100);
+        let burst: Vec<_> = (0..10).map(|i| format!("{i:02} {}", "x".repeat(27))).collect();
+        for message in &burst {
+            assert!(queue.push(message.clone()));
+        }
+
+        let mut seen = Vec::new();
+        let mut frames = 0;
+        while queue.pending() > 0 {
+            let frame = queue.next_frame();
+            frames += 1;
+            assert!(frame.iter().map(String::len).sum::<usize>() <= 100, "{frame:?}");
+            for message in frame {
+                if !seen.contains(&message) {
+                    seen.push(message);
+                }
+            }
+        }
+
+        // Three 30 byte messages fit in each frame
+        assert_eq!(frames, 4);
+        assert_eq!(seen, burst);
+    }
+
+    #[test]
+    fn oversized_message_is_sent_alone() {
+        let mut queue = InfoQueue::new(32, 4, 10);
+        queue.push("a".repeat(20));
+        queue.push("short".to_string());
+
+        assert_eq!(queue.next_frame(), ["a".repeat(20)]);
+        assert_eq!(queue.next_frame(), ["short"]);
+    }
+}
@@ -0,0 +1,123 @@
+//! Scheduling of the parts of each downlink frame, so data which is needed
+//! less often is interleaved without crowding out what is needed in every
+//! frame.
+
+/// A part of the telemetry with its own rate, such as the position or the
+/// identity of the payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lane {
+    pub name: &'static str,
+    /// The lane is due in every this many frames
+    pub every: u32,
+}
+
+/// Chooses the lanes to put in each frame, within a byte budget.
+///
+/// Lanes are given highest priority first, and are added to a frame in that
+/// order while they fit. A due lane which doesn't fit waits for the next
+/// frame with room, rather than waiting until it is next due, so every lane
+/// is eventually sent. The highest priority lane which is due is always
+/// included, even if it alone is over the budget, so no frame goes out
+/// without its most important data.
+#[derive(Debug, Clone)]
+pub struct LaneScheduler {
+    lanes: Vec<Lane>,
+    pending: Vec<bool>,
+    budget: usize,
+    frame: u32,
+}
+
+impl LaneScheduler {
+    /// Create a scheduler for `lanes`, highest priority first, which fills
+    /// each frame with at most `budget` bytes.
+    pub fn new(lanes: &[Lane], budget: usize) -> Self {
+        Self {
+            lanes: lanes.to_vec(),
+            pending: vec![false; lanes.len()],
+            budget,
+            frame: 0,
+        }
+    }
+
+    /// Choose the lanes for the next frame, given how many bytes each lane
+    /// would take up in it.
+    ///
+    /// Returns whether each lane is included, in the order the lanes were
+    /// given.
+    pub fn next_frame(&mut self, costs: &[usize]) -> Vec<bool> {
+        for (pending, lane) in self.pending.iter_mut().zip(&self.lanes) {
+            if self.frame.is_multiple_of(lane.every.max(1)) {
+                *pending = true;
+            }
+        }
+        self.frame = self.frame.wrapping_add(1);
+
+        let mut remaining = self.budget;
+        let mut included = vec![false; self.lanes.len()];
+        for (index, pending) in self.pending.iter_mut().enumerate() {
+            if !*pending {
+                continue;
+            }
+
+            let cost = costs.get(index).copied().unwrap_or(0);
+            let first = !included.contains(&true);
+            if cost > remaining && !first {
+                continue;
+            }
+
+            remaining = remaining.saturating_sub(cost);
+            included[index] = true;
+            *pending = false;
+        }
+
+        included
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LANES: [Lane; 3] = [
+        Lane { name: "position", every: 1 },
+        Lane { name: "power", every: 4 },
+        Lane { name: "identity", every: 20 },
+    ];
+
+    /// The frames each lane was included in, out of `frames`.
+    fn schedule(scheduler: &mut LaneScheduler, costs: &[usize], frames: u32) -> Vec<Vec<u32>> {
+        let mut included = vec![Vec::new(); costs.len()];
+        for frame in 0..frames {
+            let lanes = scheduler.next_frame(costs);
+            let bytes: usize = lanes.iter().zip(costs).filter(|(lane, _)| **lane).map(|(_, cost)| cost).sum();
+            assert!(bytes <= scheduler.budget, "frame {frame} has {bytes} bytes");
+
+            for (index, _) in lanes.iter().enumerate().filter(|(_, lane)| **lane) {
+                included[index].push(frame);
+            }
+        }
+
+        included
+    }
+
+    #[test]
+    fn lanes_appear_at_their_frequency() {
+        let mut scheduler = LaneScheduler::new(&LANES, 150);
+        let included = schedule(&mut scheduler, &[60, 30, 50], 40);
+
+        assert_eq!(included[0], (0..40).collect::<Vec<_>>());
+        assert_eq!(included[1], (0..40).step_by(4).collect::<Vec<_>>());
+        assert_eq!(included[2], [0, 20]);
+    }
+
+    #[test]
+    fn lane_without_room_waits_for_the_next_frame() {
+        // The identity doesn't fit alongside the power, so follows it
+        let mut scheduler = LaneScheduler::new(&LANES, 100);
+        let included = schedule(&mut scheduler, &[40, 30, 50], 40);
+
+        assert_eq!(included[0], (0..40).collect::<Vec<_>>());
+        assert_eq!(included[1], (0..40).step_by(4).collect::<Vec<_>>());
+        assert_eq!(included[2], [1, 21]);
+    }
+}
@@ -1,17 +1,73 @@
+pub mod altitude;
+pub mod apogee;
+pub mod bus_recovery;
+pub mod capture;
+pub mod clock;
+pub mod consistency;
+pub mod coordinates;
+pub mod datagram;
+pub mod decimation;
+pub mod diagnostics;
+pub mod duty_cycle;
+pub mod events;
+pub mod fix_acquisition;
+pub mod fix_quality;
+pub mod frame;
+pub mod heading;
+pub mod influx;
+pub mod info_queue;
+pub mod lanes;
+pub mod link_report;
+pub mod log_files;
+pub mod loop_health;
+pub mod pacing;
+pub mod position_filter;
+pub mod recording;
+pub mod retransmit;
+pub mod rfd;
+pub mod schema;
+pub mod stationary;
+pub mod tare;
+pub mod timesync;
+pub mod uplink;
 pub mod utils;
+pub mod validation;
+pub mod virtual_sensor;
 
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use bno055::mint;
-use serde::{Deserialize, Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use coordinates::{CoordinateFormat, Origin};
+use decimation::SampleRange;
+use events::Event;
+use schema::SchemaVersion;
 use utils::crc8;
 
+pub use frame::{decode_frame, encode_frame, frame_data, FrameError, FrameHeader, FrameScanner};
+
 /// A packet sent from the rocket to the ground station.
 ///
 /// Contains information about position and internal payload conditions.
 /// Most fields are optional, as it is possible for any part of the payload
 /// to be not functioning while still grabbing some data from it.
+///
+/// # Serialization order
+/// The CRC is calculated over the serialized JSON, so the JSON of a packet
+/// must always be the same bytes. Structs serialize their fields in
+/// declaration order and sequences like `info` in their own order, so this
+/// holds as long as:
+///  - Fields are never reordered, only added at the end of a struct.
+///  - No `HashMap` or other unordered collection is ever serialized. Use a
+///    `BTreeMap` if a map is needed.
+///
+/// Other implementations checking the CRC should check it against the
+/// received bytes, as [`decode_frame`] does, rather than re-serializing.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TelemetryPacket {
+    /// The schema version this packet was serialized with
+    #[serde(rename = "v", default = "SchemaVersion::untagged")]
+    pub schema_version: SchemaVersion,
+
     /// Full GPS telemetry information
     pub gps: Option<GpsInfo>,
 
@@ -29,59 +85,344 @@ pub struct TelemetryPacket {
 
     /// Arbitrary information to transfer to the ground
     pub info: VecDeque<String>,
+
+    /// Recent events, sent as compact codes rather than text
+    #[serde(rename = "ev", default, skip_serializing_if = "VecDeque::is_empty")]
+    pub events: VecDeque<Event>,
+
+    /// Whether too many subsystems have failed and the payload is in safe
+    /// mode
+    #[serde(rename = "safe")]
+    pub safe_mode: bool,
+
+    /// Identifies which flight this packet belongs to. Only sent
+    /// periodically to save bandwidth.
+    #[serde(rename = "fid", default, skip_serializing_if = "Option::is_none")]
+    pub flight_id: Option<String>,
+
+    /// What is sending the telemetry, sent alongside the flight ID so a
+    /// ground station joining mid-flight soon knows whether it can decode
+    /// the packets
+    #[serde(rename = "id", default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<Identity>,
+
+    /// When this packet was sent, in milliseconds since the Unix epoch
+    #[serde(rename = "t", default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<i64>,
+
+    /// Temperatures of the payload electronics, only sent when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thermal: Option<ThermalInfo>,
+
+    /// Raw sensor values, only sent when requested as they cost bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<RawValues>,
+
+    /// How long the sending loop is taking, only sent when requested
+    #[serde(rename = "loop", default, skip_serializing_if = "Option::is_none")]
+    pub loop_health: Option<LoopHealth>,
+
+    /// Values computed by [`virtual_sensor::VirtualSensor`]s, by name
+    #[serde(
+        rename = "cmp",
+        default,
+        skip_serializing_if = "BTreeMap::is_empty",
+        serialize_with = "truncate_floats",
+        deserialize_with = "parse_floats"
+    )]
+    pub computed: BTreeMap<String, f64>,
+
+    /// Whether the GPS acquired a fix just before this packet
+    #[serde(rename = "facq", default, skip_serializing_if = "std::ops::Not::not")]
+    pub fix_acquired: bool,
+
+    /// Whether this is a [`TelemetryPacket::fallback`] sent in place of a
+    /// packet which could not be serialized
+    #[serde(rename = "serr", default, skip_serializing_if = "std::ops::Not::not")]
+    pub serialization_error: bool,
+
+    /// Height in meters above where the sensors were tared
+    #[serde(rename = "agl", default, skip_serializing_if = "Option::is_none")]
+    pub tared_height: Option<f64>,
+
+    /// Rotation since the sensors were tared
+    #[serde(rename = "imu_rel", default, skip_serializing_if = "Option::is_none")]
+    pub relative_orientation: Option<mint::Quaternion<f32>>,
+
+    /// Compass heading from the magnetometer
+    #[serde(rename = "hdg", default, skip_serializing_if = "Option::is_none")]
+    pub heading: Option<HeadingInfo>,
 }
 
 impl TelemetryPacket {
-    pub fn vec_crc(&self) -> (Vec<u8>, u8) {
-        let self_json = serde_json::to_vec(self).unwrap();
+    /// Serialize the packet to JSON, returning the JSON along with its CRC.
+    pub fn try_vec_crc(&self) -> Result<(Vec<u8>, u8), TelemetryError> {
+        let self_json = serde_json::to_vec(self)?;
         let crc = crc8(&self_json);
 
-        (self_json, crc)
+        Ok((self_json, crc))
     }
 
     /// Calculate CRC from json serialized packet data.
+    pub fn try_crc(&self) -> Result<u8, TelemetryError> {
+        self.try_vec_crc().map(|(_, crc)| crc)
+    }
+
+    /// Infallible version of [`Self::try_vec_crc`].
+    ///
+    /// # Panics
+    /// Panics if the packet can't be serialized, which only happens if one
+    /// of its computed values isn't finite.
+    pub fn vec_crc(&self) -> (Vec<u8>, u8) {
+        self.try_vec_crc().expect("Packet should always serialize")
+    }
+
+    /// A minimal packet to send in place of this one if it can't be
+    /// serialized, so the ground still gets the position and knows
+    /// something is wrong.
+    ///
+    /// Only fields made of plain numbers and strings are kept, which always
+    /// serialize.
+    pub fn fallback(&self) -> Self {
+        Self {
+            schema_version: self.schema_version,
+            gps: self.gps,
+            events: self.events.clone(),
+            safe_mode: self.safe_mode,
+            flight_id: self.flight_id.clone(),
+            identity: self.identity.clone(),
+            time: self.time,
+            serialization_error: true,
+            ..Self::default()
+        }
+    }
+
+    /// Serialize the packet like [`Self::try_vec_crc`], serializing its
+    /// [`Self::fallback`] instead if it can't be.
+    ///
+    /// The error is returned alongside the fallback so it can be logged.
+    pub fn vec_crc_or_fallback(&self) -> (Vec<u8>, u8, Option<TelemetryError>) {
+        match self.try_vec_crc() {
+            Ok((data, crc)) => (data, crc, None),
+            Err(e) => {
+                let (data, crc) = self.fallback().vec_crc();
+                (data, crc, Some(e))
+            }
+        }
+    }
+
+    /// Infallible version of [`Self::try_crc`].
+    ///
+    /// # Panics
+    /// Panics under the same conditions as [`Self::vec_crc`].
     pub fn crc(&self) -> u8 {
-        let self_json = serde_json::to_vec(self).unwrap();
-        crc8(&self_json)
+        self.try_crc().expect("Packet should always serialize")
     }
 
     /// Validate the packet against its CRC.
+    ///
+    /// A packet which can't be serialized is never valid.
     #[must_use]
     pub fn validate(&self, crc: u8) -> bool {
-        let self_json = serde_json::to_string(self).unwrap();
-        let new_crc = crc8(self_json.as_bytes());
-
         // If they aren't equal, the data is invalid!
-        new_crc == crc
+        self.try_crc().is_ok_and(|new_crc| new_crc == crc)
     }
 }
 
+/// Errors which can occur while encoding a packet.
+#[derive(Debug, thiserror::Error)]
+pub enum TelemetryError {
+    #[error("Packet could not be serialized: {0}")]
+    Serialize(#[from] serde_json::Error),
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[serde(rename = "env")]
 pub struct EnvironmentalInfo {
     /// Pressure of the inside of the payload
-    #[serde(serialize_with = "truncate_float")]
+    #[serde(serialize_with = "truncate_float", deserialize_with = "parse_float")]
     #[serde(rename = "pres")]
     pub pressure: f64,
+    /// Range of the pressure over the send interval, if requested
+    #[serde(rename = "pres_rng", default, skip_serializing_if = "Option::is_none")]
+    pub pressure_range: Option<SampleRange>,
     /// Temperature of the inside of the payload
-    #[serde(serialize_with = "truncate_float")]
+    #[serde(serialize_with = "truncate_float", deserialize_with = "parse_float")]
     #[serde(rename = "temp")]
     pub temperature: f64,
+    /// Range of the temperature over the send interval, if requested
+    #[serde(rename = "temp_rng", default, skip_serializing_if = "Option::is_none")]
+    pub temperature_range: Option<SampleRange>,
     /// humidity of the inside of the payload
-    #[serde(serialize_with = "truncate_float")]
+    #[serde(serialize_with = "truncate_float", deserialize_with = "parse_float")]
     #[serde(rename = "humid")]
     pub humidity: f64,
 }
 
+/// Temperatures of the payload electronics, as opposed to the air
+/// temperature in [`EnvironmentalInfo`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ThermalInfo {
+    /// Raspberry Pi CPU temperature in degrees Celsius
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<f32>,
+}
+
+/// Sensor values as output by the sensors, before conversion into
+/// engineering units, for recalibration on the ground.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawValues {
+    /// BMP581 pressure output, in 1/64 Pa
+    #[serde(rename = "bmp_p", default, skip_serializing_if = "Option::is_none")]
+    pub bmp_pressure: Option<u32>,
+    /// BMP581 temperature output, in 1/65536 °C
+    #[serde(rename = "bmp_t", default, skip_serializing_if = "Option::is_none")]
+    pub bmp_temperature: Option<i32>,
+    /// HTS221 humidity ADC count, before the sensor's calibration
+    #[serde(rename = "hts_h", default, skip_serializing_if = "Option::is_none")]
+    pub hts_humidity: Option<i16>,
+}
+
+/// Describes the payload sending the telemetry, rather than what it is
+/// measuring.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Identity {
+    /// The schema version of the packets being sent
+    #[serde(rename = "proto")]
+    pub protocol: SchemaVersion,
+    /// The version of the payload software, followed by the git hash it was
+    /// built from, e.g. `0.1.0+1a2b3c4`
+    #[serde(rename = "fw")]
+    pub firmware: String,
+    /// The subsystems and optional telemetry which are enabled
+    #[serde(rename = "sys")]
+    pub subsystems: Vec<String>,
+}
+
+impl Identity {
+    /// The identity of this build of the payload software, with the given
+    /// subsystems enabled.
+    pub fn new(subsystems: Vec<String>) -> Self {
+        Self {
+            protocol: SchemaVersion::CURRENT,
+            firmware: concat!(env!("CARGO_PKG_VERSION"), "+", env!("AROWSS_GIT_HASH")).to_string(),
+            subsystems,
+        }
+    }
+}
+
+/// Compass heading of the payload, from the orientation fused by the BNO055.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct HeadingInfo {
+    /// Heading clockwise from true north in degrees
+    #[serde(rename = "deg")]
+    pub heading_deg: f32,
+    /// Whether the magnetometer wasn't fully calibrated, so the heading may
+    /// be off
+    #[serde(rename = "low", default, skip_serializing_if = "std::ops::Not::not")]
+    pub low_confidence: bool,
+}
+
+/// How long the payload is taking to build and send packets, to spot the
+/// sending loop falling behind on a loaded Raspberry Pi.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoopHealth {
+    /// How long the last packet took, in milliseconds
+    #[serde(rename = "last")]
+    pub last_ms: u32,
+    /// The longest any packet has taken, in milliseconds
+    #[serde(rename = "max")]
+    pub max_ms: u32,
+    /// The number of packets which came close to the send interval
+    pub slow: u32,
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct GpsInfo {
     pub latitude: f64,
     pub longitude: f64,
+    /// Altitude above mean sea level (the geoid) in meters, as given by the
+    /// GGA sentence
     pub altitude: f32,
     #[serde(rename = "sats")]
     pub satellites: u8,
+    /// Horizontal dilution of precision of the fix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hdop: Option<f32>,
+    /// Height of the geoid above the WGS84 ellipsoid in meters, as given by
+    /// the GGA sentence
+    #[serde(rename = "geoid", default, skip_serializing_if = "Option::is_none")]
+    pub geoid_separation: Option<f32>,
+    /// Speed over ground in meters per second, as measured by the receiver
+    #[serde(rename = "spd", default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f32>,
+    /// Course over ground in degrees from true north, as measured by the
+    /// receiver
+    #[serde(rename = "crs", default, skip_serializing_if = "Option::is_none")]
+    pub course: Option<f32>,
+}
+
+impl GpsInfo {
+    /// Format the position of this fix for display on the ground.
+    pub fn format_position(&self, format: CoordinateFormat) -> String {
+        format.format(self.latitude, self.longitude)
+    }
+
+    /// Format the position of this fix relative to the launch site, falling
+    /// back to decimal degrees if no origin is set.
+    pub fn format_local(&self, origin: Option<&Origin>) -> String {
+        match origin {
+            Some(origin) => origin
+                .to_enu(self.latitude, self.longitude, f64::from(self.altitude))
+                .to_string(),
+            None => self.format_position(CoordinateFormat::Decimal),
+        }
+    }
+
+    /// Altitude above the WGS84 ellipsoid in meters, if the geoid separation
+    /// is known.
+    pub fn ellipsoidal_altitude(&self) -> Option<f32> {
+        self.geoid_separation.map(|separation| self.altitude + separation)
+    }
 }
 
 fn truncate_float<S: Serializer>(float: &f64, serializer: S) -> Result<S::Ok, S::Error> {
     serializer.serialize_str(&format!("{float:.2}"))
 }
+
+/// Read back a float written by [`truncate_float`], also accepting plain
+/// numbers.
+fn parse_float<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Float {
+        Number(f64),
+        String(String),
+    }
+
+    match Float::deserialize(deserializer)? {
+        Float::Number(float) => Ok(float),
+        Float::String(float) => float.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+/// Write each float of a map in the same way as [`truncate_float`].
+///
+/// A value which isn't finite is an error rather than a string the ground
+/// can't use, so the packet is replaced by its [`TelemetryPacket::fallback`].
+fn truncate_floats<S: Serializer>(floats: &BTreeMap<String, f64>, serializer: S) -> Result<S::Ok, S::Error> {
+    if let Some((name, _)) = floats.iter().find(|(_, float)| !float.is_finite()) {
+        return Err(serde::ser::Error::custom(format!("computed value {name} is not finite")));
+    }
+
+    serializer.collect_map(floats.iter().map(|(name, float)| (name, format!("{float:.2}"))))
+}
+
+/// Read back a map written by [`truncate_floats`].
+fn parse_floats<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, f64>, D::Error> {
+    #[derive(Deserialize)]
+    struct Parsed(#[serde(deserialize_with = "parse_float")] f64);
+
+    let floats = BTreeMap::<String, Parsed>::deserialize(deserializer)?;
+    Ok(floats.into_iter().map(|(name, Parsed(float))| (name, float)).collect())
+}
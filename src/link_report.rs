@@ -0,0 +1,239 @@
+//! Comparison of the packets recorded on the payload with those the ground
+//! received, to measure how the link performed against ground truth.
+//!
+//! Every packet recorded was also sent, unless the downlink was blanked or
+//! out of capacity, so aligning the two by the sequence numbers of their
+//! frames shows exactly which the ground missed. Sequence
+//! numbers wrap every 256 frames, so frames are matched by their sequence
+//! number together with the time in their packet, which tells apart frames
+//! which share a sequence number a wrap apart.
+
+use std::{collections::HashSet, fmt, time::Duration};
+
+use crate::TelemetryPacket;
+
+/// How many frames the ground received out of a number sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reception {
+    pub sent: usize,
+    pub received: usize,
+}
+
+impl Reception {
+    /// The fraction of frames received, from 0 to 1.
+    pub fn rate(&self) -> f64 {
+        if self.sent == 0 {
+            return 1.0;
+        }
+
+        self.received as f64 / self.sent as f64
+    }
+}
+
+/// Reception over one window of the flight, starting `start` after the
+/// first packet recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowReception {
+    pub start: Duration,
+    pub reception: Reception,
+}
+
+/// A run of consecutive frames which the ground missed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outage {
+    /// Sequence number of the first frame missed
+    pub first_seq: u8,
+    /// Number of frames missed
+    pub frames: usize,
+    /// Time from the first frame missed to the next one received, if the
+    /// packets have times
+    pub duration: Option<Duration>,
+}
+
+/// How well the link carried the frames of a flight.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkReport {
+    pub total: Reception,
+    /// Reception over each window of the flight, in order
+    pub windows: Vec<WindowReception>,
+    /// The longest run of frames the ground missed
+    pub longest_outage: Option<Outage>,
+}
+
+impl LinkReport {
+    /// Compare the frames recorded on the payload with those the ground
+    /// received, both as sequence numbers and packets, splitting the flight
+    /// into windows of `window`.
+    pub fn compare(air: &[(u8, TelemetryPacket)], ground: &[(u8, TelemetryPacket)], window: Duration) -> Self {
+        let received: HashSet<_> = ground.iter().map(|(seq, packet)| (*seq, packet.time)).collect();
+        let start = air.iter().find_map(|(_, packet)| packet.time);
+        let window_ms = window.as_millis().max(1) as i64;
+
+        let mut total = Reception { sent: 0, received: 0 };
+        let mut windows: Vec<WindowReception> = Vec::new();
+        let mut outage: Option<(Outage, Option<i64>)> = None;
+        let mut longest_outage: Option<Outage> = None;
+
+        for (seq, packet) in air {
+            let got = received.contains(&(*seq, packet.time));
+            total.sent += 1;
+            total.received += usize::from(got);
+
+            if let Some((start, time)) = start.zip(packet.time) {
+                let index = ((time - start).max(0) / window_ms) as usize;
+                while windows.len() <= index {
+                    windows.push(WindowReception {
+                        start: window * windows.len() as u32,
+                        reception: Reception { sent: 0, received: 0 },
+                    });
+                }
+                windows[index].reception.sent += 1;
+                windows[index].reception.received += usize::from(got);
+            }
+
+            match (&mut outage, got) {
+                (Some((current, _)), false) => current.frames += 1,
+                (None, false) => {
+                    let current = Outage {
+                        first_seq: *seq,
+                        frames: 1,
+                        duration: None,
+                    };
+                    outage = Some((current, packet.time));
+                }
+                (Some(_), true) => {
+                    if let Some((mut ended, began)) = outage.take() {
+                        ended.duration = began
+                            .zip(packet.time)
+                            .map(|(began, ended)| Duration::from_millis((ended - began).max(0) as u64));
+                        longest_outage = longest(longest_outage, ended);
+                    }
+                }
+                (None, true) => (),
+            }
+        }
+
+        // The ground may still have been missing frames when the
+        // recording ended
+        if let Some((ended, _)) = outage {
+            longest_outage = longest(longest_outage, ended);
+        }
+
+        Self {
+            total,
+            windows,
+            longest_outage,
+        }
+    }
+
+    /// The windows which lost the largest fraction of their frames, worst
+    /// first.
+    pub fn worst_windows(&self, count: usize) -> Vec<WindowReception> {
+        let mut windows: Vec<_> = self.windows.iter().filter(|w| w.reception.sent > 0).copied().collect();
+        windows.sort_by(|a, b| a.reception.rate().total_cmp(&b.reception.rate()));
+        windows.truncate(count);
+
+        windows
+    }
+}
+
+/// The longer of two outages, keeping the earlier one if they are equal.
+fn longest(current: Option<Outage>, new: Outage) -> Option<Outage> {
+    match current {
+        Some(current) if current.frames >= new.frames => Some(current),
+        _ => Some(new),
+    }
+}
+
+impl fmt::Display for Reception {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} ({:.1}%)", self.received, self.sent, self.rate() * 100.0)
+    }
+}
+
+impl fmt::Display for LinkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Received {}", self.total)?;
+
+        match self.longest_outage {
+            Some(outage) => {
+                write!(f, "Longest outage: {} frames from {}", outage.frames, outage.first_seq)?;
+                match outage.duration {
+                    Some(duration) => writeln!(f, " ({:.1}s)", duration.as_secs_f64())?,
+                    None => writeln!(f)?,
+                }
+            }
+            None => writeln!(f, "No frames missed")?,
+        }
+
+        writeln!(f, "Worst windows:")?;
+        for window in self.worst_windows(3) {
+            writeln!(f, "  +{}s: {}", window.start.as_secs(), window.reception)?;
+        }
+
+        writeln!(f, "Reception over time:")?;
+        for window in &self.windows {
+            writeln!(f, "  +{}s: {}", window.start.as_secs(), window.reception)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames sent every 250ms, with sequence numbers starting at `first`.
+    fn frames(first: u8, count: usize) -> Vec<(u8, TelemetryPacket)> {
+        (0..count)
+            .map(|i| {
+                let packet = TelemetryPacket {
+                    time: Some(1_000_000 + i as i64 * 250),
+                    ..TelemetryPacket::default()
+                };
+                (first.wrapping_add(i as u8), packet)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn known_gap_across_wraparound() {
+        let air = frames(250, 40);
+
+        // The ground misses 253 to 2, across the wrap, and 20 alone
+        let ground: Vec<_> = air
+            .iter()
+            .filter(|(seq, _)| !(253..=255).contains(seq) && !(0..=2).contains(seq) && *seq != 20)
+            .cloned()
+            .collect();
+
+        let report = LinkReport::compare(&air, &ground, Duration::from_secs(5));
+        assert_eq!(report.total, Reception { sent: 40, received: 33 });
+        assert_eq!(
+            report.longest_outage,
+            Some(Outage {
+                first_seq: 253,
+                frames: 6,
+                duration: Some(Duration::from_millis(1500)),
+            })
+        );
+
+        assert_eq!(report.windows.len(), 2);
+        assert_eq!(report.windows[0].reception, Reception { sent: 20, received: 14 });
+        assert_eq!(report.windows[1].reception, Reception { sent: 20, received: 19 });
+        assert_eq!(report.worst_windows(1)[0].start, Duration::ZERO);
+    }
+
+    #[test]
+    fn same_sequence_a_wrap_apart() {
+        let air = frames(0, 512);
+
+        // Only the second frame numbered 7 reached the ground
+        let ground = vec![air[256 + 7].clone()];
+
+        let report = LinkReport::compare(&air, &ground, Duration::from_secs(10));
+        assert_eq!(report.total.received, 1);
+        assert_eq!(report.longest_outage.unwrap().frames, 256 + 7);
+    }
+}
@@ -0,0 +1,137 @@
+//! Log files which rotate by size, so logging can never fill the SD card.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// A log file which is rotated once it reaches a maximum size, keeping a
+/// limited number of old files.
+///
+/// The current file is `<prefix>.log`, and older ones are `<prefix>.log.1`,
+/// `<prefix>.log.2` and so on, oldest last. The logs therefore take up at
+/// most `max_files` times `max_bytes`, however long the payload runs.
+#[derive(Debug)]
+pub struct RotatingLog {
+    dir: PathBuf,
+    prefix: String,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    written: u64,
+}
+
+impl RotatingLog {
+    /// Open the log named `prefix` in `dir`, creating the directory if
+    /// needed, and appending to a current file left by an earlier run.
+    pub fn open(dir: &Path, prefix: &str, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        let path = dir.join(format!("{prefix}.log"));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            prefix: prefix.to_string(),
+            max_bytes,
+            max_files: max_files.max(1),
+            file,
+            written,
+        })
+    }
+
+    /// The path of the current log file, or of the old file `index` rotations
+    /// back.
+    pub fn path(&self, index: usize) -> PathBuf {
+        match index {
+            0 => self.dir.join(format!("{}.log", self.prefix)),
+            index => self.dir.join(format!("{}.log.{index}", self.prefix)),
+        }
+    }
+
+    /// Move every file back one place, deleting the oldest, and start a new
+    /// current file.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        let oldest = self.path(self.max_files - 1);
+        if oldest.exists() {
+            fs::remove_file(oldest)?;
+        }
+        for index in (0..self.max_files - 1).rev() {
+            let path = self.path(index);
+            if path.exists() {
+                fs::rename(path, self.path(index + 1))?;
+            }
+        }
+
+        self.file = OpenOptions::new().create(true).append(true).open(self.path(0))?;
+        self.written = 0;
+
+        Ok(())
+    }
+}
+
+impl Write for RotatingLog {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory for a single test, emptied before it runs.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("arowss_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn creates_file_and_writes_line() {
+        let dir = test_dir("log_smoke");
+        let mut log = RotatingLog::open(&dir, "arowss", 1024, 4).unwrap();
+
+        writeln!(log, "payload started").unwrap();
+        log.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(dir.join("arowss.log")).unwrap(), "payload started\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn total_size_is_bounded() {
+        let dir = test_dir("log_bound");
+        let mut log = RotatingLog::open(&dir, "arowss", 100, 3).unwrap();
+
+        for i in 0..100 {
+            writeln!(log, "line {i:04} of the log").unwrap();
+        }
+        log.flush().unwrap();
+
+        let files: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+        let total: u64 = files.iter().map(|file| fs::metadata(file).unwrap().len()).sum();
+        assert_eq!(files.len(), 3);
+        assert!(total <= 300);
+
+        // The newest line is in the current file
+        let current = fs::read_to_string(log.path(0)).unwrap();
+        assert!(current.ends_with("line 0099 of the log\n"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,78 @@
+//! Timing of the sending loop, to warn of it falling behind before packets
+//! start being skipped.
+
+use std::time::Duration;
+
+use crate::LoopHealth;
+
+/// A packet is slow once building and sending it takes this fraction of the
+/// send interval, leaving little headroom before a tick is missed.
+const SLOW_FRACTION: f64 = 0.8;
+
+/// Keeps track of how long each packet takes to build and send.
+#[derive(Debug, Clone)]
+pub struct LoopMonitor {
+    budget: Duration,
+    health: LoopHealth,
+}
+
+impl LoopMonitor {
+    /// Create a monitor for a loop which must finish each packet within
+    /// `budget`.
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            health: LoopHealth::default(),
+        }
+    }
+
+    /// Record that a packet took `duration` to build and send.
+    ///
+    /// Returns `true` if it came close to, or went over, the budget.
+    pub fn record(&mut self, duration: Duration) -> bool {
+        let millis = u32::try_from(duration.as_millis()).unwrap_or(u32::MAX);
+        self.health.last_ms = millis;
+        self.health.max_ms = self.health.max_ms.max(millis);
+
+        let slow = duration >= self.budget.mul_f64(SLOW_FRACTION);
+        if slow {
+            self.health.slow = self.health.slow.saturating_add(1);
+        }
+
+        slow
+    }
+
+    /// The timing of the packets recorded so far.
+    pub fn health(&self) -> LoopHealth {
+        self.health
+    }
+
+    /// The time each packet must be finished within.
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, FakeClock};
+
+    #[test]
+    fn slow_build_is_reported() {
+        let clock = FakeClock::new(0);
+        let mut monitor = LoopMonitor::new(Duration::from_millis(250));
+
+        // A build which takes most of the interval
+        let start = clock.now();
+        clock.advance(Duration::from_millis(230));
+        assert!(monitor.record(clock.now() - start));
+        assert_eq!(monitor.health(), LoopHealth { last_ms: 230, max_ms: 230, slow: 1 });
+
+        // A fast one afterwards keeps the slowest
+        let start = clock.now();
+        clock.advance(Duration::from_millis(40));
+        assert!(!monitor.record(clock.now() - start));
+        assert_eq!(monitor.health(), LoopHealth { last_ms: 40, max_ms: 230, slow: 1 });
+    }
+}
@@ -1,18 +1,29 @@
+mod args;
 mod commands;
-use aerospace_rocketry_lib::geospatial::calculate_barometric_altitude;
+mod flight_id;
+mod health;
+#[cfg(all(feature = "realtime", target_os = "linux"))]
+mod realtime;
+mod recorder;
+mod supervisor;
 use bmp581::{Bmp581, I2cAddr, types::{DeepDis, Odr, Osr, PowerMode}};
-use commands::CommandParser;
+use args::Args;
+use commands::{parse_command_buffer, CommandParser, Commands, GpsRequest, NamedOutput, RadioRequest, RecorderRequest, FailsafeAction, LinkWatchdog};
+use recorder::Recorder;
+use supervisor::{Subsystem, Supervisor};
 
-use arowss::{utils::crc8, EnvironmentalInfo, GpsInfo, TelemetryPacket};
+use arowss::{apogee::{ApogeeConfidence, ApogeeDetector}, bus_recovery::{BusEvent, BusRecovery}, capture::CapturedFix, datagram::DEFAULT_MAX_DATAGRAM_BYTES, diagnostics::Diagnostics, duty_cycle::{DutyCycle, DutyPolicy}, clock::{Clock, SystemClock}, consistency::AltitudeConsistency, altitude::{pressure_altitude, SeaLevelCalibration, STANDARD_SEA_LEVEL_PRESSURE}, decimation::{DecimationPolicy, Decimator}, encode_frame, frame::SYNC_WORD, frame_data, FrameHeader, events::{Event, EventCode, EventQueue, Message}, fix_acquisition::FixAcquisition, fix_quality::FixRequirements, heading::{self, MIN_MAG_CALIBRATION}, info_queue::InfoQueue, lanes::{Lane, LaneScheduler}, log_files::RotatingLog, loop_health::LoopMonitor, pacing::{LinkBudget, LinkPacer}, position_filter::PositionFilter, retransmit::RetransmitBuffer, recording::parse_record, rfd::{self, RadioSettings}, schema::SchemaVersion, stationary::StationaryDetector, tare::Tare, utils::{self, nmea_sentence, NmeaLines}, virtual_sensor::{DensityAltitude, TelemetrySnapshot, VirtualSensors}, EnvironmentalInfo, GpsInfo, HeadingInfo, Identity, RawValues, TelemetryPacket, ThermalInfo};
 use linux_embedded_hal::I2cdev;
-use tracing::{warn, debug, error, info, instrument, Level};
+use tracing::{warn, debug, error, info, instrument};
+use tracing_subscriber::{filter::LevelFilter, fmt, prelude::*};
 use nmea::{Nmea, SentenceType};
 use rppal::gpio::Gpio;
-use std::{collections::VecDeque, sync::{Arc, mpsc::{self, Receiver, Sender}}, time::Duration};
-use tokio::{io::AsyncWriteExt as _, join, net::UdpSocket, sync::watch, time::{self, sleep}};
+use std::{collections::{HashMap, VecDeque}, io::Write, net::SocketAddr, path::{Path, PathBuf}, sync::{Arc, PoisonError, mpsc::{self, Receiver, Sender, TryRecvError}}, time::Duration};
+use tokio::{io::AsyncWriteExt as _, join, net::UdpSocket, sync::{mpsc::UnboundedSender, watch}, time::{self, sleep, Instant}};
 use serialport::SerialPort;
 use std::sync::Mutex;
 use bno055::{mint, BNO055PowerMode};
+use embedded_hal::i2c::I2c;
 use embedded_hal_bus::i2c::MutexDevice;
 use embedded_hal_compat::Reverse;
 use hts221::UpdateMode::Block;
@@ -20,29 +31,306 @@ use chrono::prelude::*;
 
 const RFD_PATH: &str = "/dev/ttyAMA2";
 const RFD_BAUD: u32 = 57600;
+/// The air data rate in kbps both RFD-900x radios are configured with.
+const RFD_AIR_RATE: u32 = 64;
+/// The transmit power in dBm the RFD-900x is configured with.
+const RFD_TX_POWER: u32 = 30;
+/// The percentage of the RFD-900x link kept free for commands from the
+/// ground.
+const RFD_RESERVED_UPLINK_PERCENT: u32 = 10;
+/// What the RFD-900x link can carry, given the packet rate and uplink
+/// reservation.
+const RFD_LINK_BUDGET: LinkBudget = LinkBudget::rfd900x(
+    RFD_BAUD,
+    (1000 / SEND_INTERVAL.as_millis()) as u32,
+    RFD_RESERVED_UPLINK_PERCENT,
+);
 /// This is the maximum number of bytes that can be sent by the RFD-900 per
 /// packet without dropping behind
-const MAX_PACKET_BYTES: usize = (RFD_BAUD as usize / 9) / 4;
+const MAX_PACKET_BYTES: usize = RFD_LINK_BUDGET.bytes_per_packet();
+
+/// The bytes per second the RFD-900x link carries for the downlink.
+const RFD_BYTES_PER_SECOND: f64 = RFD_LINK_BUDGET.downlink_bytes_per_second() as f64;
+/// How much data can be sent to the RFD-900x at once, beyond its steady
+/// throughput, before frames are dropped.
+const RFD_BURST: Duration = Duration::from_millis(500);
+
+/// How often a telemetry packet is sent to the ground.
+const SEND_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the I²C sensors are read, unless in low power mode.
+const SENSOR_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// In low power mode, how long before each packet is sent that the
+/// BNO055 and HTS221 are read.
+const SENSOR_READ_LEAD: Duration = Duration::from_millis(10);
+/// In low power mode, how long before each packet is sent that the BMP581
+/// is woken for a single conversion. This covers the conversion time at the
+/// configured oversampling along with the read itself.
+const BMP_FORCED_LEAD: Duration = Duration::from_millis(20);
+/// How long a single forced BMP581 conversion takes at the configured
+/// oversampling, with margin.
+const BMP_CONVERSION_TIME: Duration = Duration::from_millis(10);
+/// In low power mode, how long before each packet is sent that the HTS221
+/// is started on a one-shot conversion, covering the conversion and read.
+const HTS_ONE_SHOT_LEAD: Duration = Duration::from_millis(25);
+/// How long a one-shot HTS221 conversion takes at the default averaging,
+/// with margin.
+const HTS_CONVERSION_TIME: Duration = Duration::from_millis(15);
+/// The rate the BMP581 is sampled at in hertz until changed from the ground.
+const BARO_DEFAULT_RATE: u32 = 20;
+
+/// The maximum length in bytes of a single info message.
+const MAX_INFO_BYTES: usize = 96;
+/// Replaces the end of an info message which was too long.
+const INFO_TRUNCATION_MARKER: &str = "…(truncated)";
+/// The most bytes of info messages sent in a single packet, so a burst of
+/// messages can't push out the sensor data.
+const MAX_INFO_BYTES_PER_FRAME: usize = 2 * MAX_INFO_BYTES;
+/// The number of recent info messages repeated in every packet.
+const MAX_REPEATED_INFO: usize = 3;
+/// The number of info messages held waiting for room in a packet.
+const MAX_PENDING_INFO: usize = 32;
+
+/// The most virtual sensors which can be evaluated for each packet.
+const MAX_VIRTUAL_SENSORS: usize = 8;
+/// The most bytes the values of the virtual sensors may add to a packet.
+const MAX_COMPUTED_BYTES: usize = 128;
+
+/// The number of packets in a row each event is sent in, so an event is not
+/// lost with a single dropped packet.
+const EVENT_REPEATS: u32 = 3;
+/// The number of events held waiting to be sent.
+const MAX_QUEUED_EVENTS: usize = 8;
+
+/// Where the flight ID is persisted, so it survives a reboot mid-flight.
+const FLIGHT_ID_PATH: &str = "flight_id";
+/// The flight ID, and the identity of the payload, are included in every
+/// this many packets. At the normal send interval this is every 5 seconds.
+const FLIGHT_ID_PACKET_INTERVAL: u32 = 20;
+
+/// The lanes each packet is assembled from, highest priority first. The
+/// sensor data and events are in every packet, while the rest fill the room
+/// left over, each at most as often as given.
+const DOWNLINK_LANES: [Lane; 4] = [
+    Lane { name: "core", every: 1 },
+    Lane { name: "identity", every: FLIGHT_ID_PACKET_INTERVAL },
+    Lane { name: "extras", every: 1 },
+    Lane { name: "info", every: 1 },
+];
+/// The index of the flight ID and identity in [`DOWNLINK_LANES`].
+const IDENTITY_LANE: usize = 1;
+/// The index of the optional telemetry, such as raw and computed values,
+/// in [`DOWNLINK_LANES`].
+const EXTRAS_LANE: usize = 2;
+/// The index of the info messages in [`DOWNLINK_LANES`].
+const INFO_LANE: usize = 3;
+/// The bytes of each packet available to the lanes, leaving room for the
+/// framing.
+const LANE_BUDGET_BYTES: usize = MAX_PACKET_BYTES - SYNC_WORD.len() - FrameHeader::MAX_LEN - 1;
+
+/// How the BMP581 pressure samples within a send interval are reduced.
+const PRESSURE_DECIMATION: DecimationPolicy = DecimationPolicy::MinMax;
+/// The number of BMP581 pressure samples the median filter applied before
+/// decimation covers.
+const PRESSURE_MEDIAN_WINDOW: usize = 3;
+/// How the BMP581 temperature samples within a send interval are reduced.
+const TEMPERATURE_DECIMATION: DecimationPolicy = DecimationPolicy::Latest;
 
 const GPS_PATH: &str = "/dev/ttyS0";
 const GPS_BAUD: u32 = 9600;
 
+/// PMTK commands sent to the primary GPS at startup and on request.
+const GPS_CONFIG_COMMANDS: &[&str] = &[
+    // Output RMC, VTG, GGA, GSA and GSV sentences on every fix
+    "PMTK314,0,1,1,1,1,1,0,0,0,0,0,0,0,0,0,0,0,0,0",
+];
+/// The primary GPS fix rate in hertz until changed from the ground.
+const GPS_DEFAULT_RATE: u32 = 1;
+/// Roughly how many bytes of sentences the primary GPS outputs per fix, which
+/// limits how fast it can fix over [`GPS_BAUD`].
+const GPS_BYTES_PER_FIX: u32 = 450;
+
+/// How long to wait for a good enough fix to capture before giving up.
+const CAPTURE_FIX_TIMEOUT: Duration = Duration::from_secs(120);
+/// The quality a fix must have to be captured.
+const CAPTURE_FIX_REQUIREMENTS: FixRequirements = FixRequirements {
+    min_satellites: 6,
+    max_hdop: Some(1.5),
+    require_valid_fix: true,
+};
+
+/// The quality a fix must have to be downlinked at all. Poorer fixes are
+/// sent as no fix, so they can't mislead a landing prediction. Use
+/// [`FixRequirements::ANY`] to always send the latest position.
+const GPS_DOWNLINK_REQUIREMENTS: FixRequirements = FixRequirements {
+    min_satellites: 4,
+    max_hdop: Some(5.0),
+    require_valid_fix: true,
+};
+/// Whether a GPS fix which is lost and then found again is reported like the
+/// first one, rather than only the first fix being reported.
+const REPORT_REACQUIRED_FIX: bool = false;
+
+/// Put the RFD-900x into command mode and send it AT commands, which must
+/// end by rebooting it back out of command mode.
+async fn send_at_commands(rfd_send: &mut Box<dyn SerialPort>, at_commands: &[String]) -> std::io::Result<()> {
+    sleep(rfd::GUARD_TIME).await;
+    rfd_send.write_all(rfd::COMMAND_MODE)?;
+    rfd_send.flush()?;
+    sleep(rfd::GUARD_TIME).await;
+
+    // The replies are read by the command task, which discards them
+    for command in at_commands {
+        rfd_send.write_all(command.as_bytes())?;
+        rfd_send.flush()?;
+        sleep(rfd::COMMAND_DELAY).await;
+    }
+
+    Ok(())
+}
+
+/// Converts the speed over ground given by NMEA sentences into m/s.
+const KNOTS_TO_METERS_PER_SECOND: f32 = 1852.0 / 3600.0;
+
 const GPS_SECONDARY: &str = "/dev/ttyAMA2";
 const GPS_SECONDARY_BAUD: u32 = 115_200;
 
 const UDP_PORT: &str = "0.0.0.0:39390";
-const UDP_TARGET: &str = "192.168.199.1:3939";
+/// Every address which telemetry is sent to over UDP. These may be unicast,
+/// multicast, or broadcast addresses.
+const UDP_TARGETS: &[&str] = &["192.168.199.1:3939"];
+/// How often the RFD-900x transmits while the payload is stationary. Every
+/// packet is transmitted, so there is no duty cycling. A
+/// [`DutyPolicy::Thermal`] policy would cut back to keep the RFD-900x cool in
+/// a sealed payload, with the CPU temperature standing in for the
+/// temperature inside the payload. Every packet is always transmitted while
+/// the payload is moving, so launch and apogee are never missed.
+const RFD_DUTY_POLICY: DutyPolicy = DutyPolicy::Always;
+
+/// Frames larger than this are fragmented by the IP layer when sent over
+/// UDP, and rejected by the ground.
+const UDP_MAX_DATAGRAM_BYTES: usize = DEFAULT_MAX_DATAGRAM_BYTES;
 
 const HIGH_POWER_RELAY_PIN_NUM: u8 = 26;
 
+/// General purpose outputs which can be set and cleared from the ground.
+/// Each is set by [`Commands::SetOutput`] and cleared by
+/// [`Commands::ClearOutput`], with its index in this list as the argument.
+/// Outputs start low.
+const GPIO_OUTPUTS: &[OutputConfig] = &[
+    OutputConfig { name: "status_led", pin: 20, interlocked: false },
+    OutputConfig { name: "aux_relay", pin: 19, interlocked: true },
+];
+
+/// A general purpose output in [`GPIO_OUTPUTS`].
+#[derive(Debug)]
+struct OutputConfig {
+    name: &'static str,
+    pin: u8,
+    /// Whether the output can only be set after [`Commands::ArmOutputs`]
+    interlocked: bool,
+}
+
+impl std::fmt::Display for OutputConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name, self.pin)?;
+        if self.interlocked {
+            write!(f, " interlocked")?;
+        }
+
+        Ok(())
+    }
+}
+
+const BUZZER_PIN_NUM: u8 = 21;
+/// How the recovery buzzer is pulsed.
+const BUZZER_PATTERN: BuzzerPattern = BuzzerPattern {
+    on: Duration::from_millis(200),
+    off: Duration::from_millis(800),
+    timeout: Duration::from_secs(30 * 60),
+};
+
+/// Number of times an I²C read is attempted before the reading is marked
+/// stale.
+const I2C_READ_ATTEMPTS: u32 = 3;
+/// Delay between attempts of a failed I²C read.
+const I2C_RETRY_DELAY: Duration = Duration::from_millis(5);
+/// Number of consecutive stale readings after which a sensor is
+/// re-initialized.
+const I2C_REINIT_FAILURES: u32 = 20;
+/// Delay before trying to initialize a sensor again after failing to.
+const I2C_REINIT_DELAY: Duration = Duration::from_secs(5);
+/// How far magnetic north is east of true north at the launch site, in
+/// degrees, to correct the BNO055 heading. This is around 2.5° east
+/// in eastern Nebraska.
+const MAGNETIC_DECLINATION: f32 = 2.5;
+/// The I²C bus all of the sensors are on.
+const I2C_BUS_PATH: &str = "/dev/i2c-1";
+/// The GPIO pins of the I²C bus, which are driven directly to free the bus
+/// when a device is holding it.
+const I2C_SDA_PIN_NUM: u8 = 2;
+const I2C_SCL_PIN_NUM: u8 = 3;
+/// The most clock pulses sent while freeing the bus. A device part way
+/// through sending a byte needs at most 9 to finish it.
+const I2C_RECOVERY_CLOCKS: u32 = 9;
+/// Half of the period of the clock pulses, giving 50kHz.
+const I2C_RECOVERY_HALF_PERIOD: Duration = Duration::from_micros(10);
+
+/// How often a position beacon is sent in recovery mode.
+const BEACON_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The directory the rotating log files are written to, unless another is
+/// given with `--log-dir`.
+const LOG_DIR: &str = "/var/log/arowss";
+/// A log file is rotated once it reaches this size.
+const LOG_MAX_FILE_BYTES: u64 = 8 * 1024 * 1024;
+/// The number of log files kept, which along with [`LOG_MAX_FILE_BYTES`]
+/// bounds the space logs take up on the SD card.
+const LOG_MAX_FILES: usize = 8;
+
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::fmt()
-        .with_max_level(Level::DEBUG)
-        .with_file(false)
+    let args = Args::parse();
+
+    // Logs are written to rotating files as well as the console, so they
+    // survive a reboot. The guard must live until the end of main, or the
+    // last lines may not be written.
+    let log_dir = args.log_dir.clone().unwrap_or_else(|| PathBuf::from(LOG_DIR));
+    let (file_writer, _log_guard) = match RotatingLog::open(&log_dir, "arowss", LOG_MAX_FILE_BYTES, LOG_MAX_FILES) {
+        Ok(log) => {
+            let (writer, guard) = tracing_appender::non_blocking(log);
+            (Some(writer), Some(guard))
+        }
+        Err(e) => {
+            eprintln!("Could not open log files in {}, logging to the console only: {e}", log_dir.display());
+            (None, None)
+        }
+    };
+
+    tracing_subscriber::registry()
+        .with(LevelFilter::from_level(args.log_level))
+        .with(fmt::layer().with_file(false))
+        .with(file_writer.map(|writer| fmt::layer().with_file(false).with_ansi(false).with_writer(writer)))
         .init();
 
     info!("AROWSS (Automatic Remote Onboard Wireless Streaming System) initialized.");
+    info!(
+        "Version {} ({}), logging at {}",
+        env!("CARGO_PKG_VERSION"),
+        env!("AROWSS_GIT_HASH"),
+        args.log_level
+    );
+    let config = config_entries(&args);
+    info!("Config: {}", config.join(", "));
+
+    // Still run with a broken CRC, as the recorder and UDP output are
+    // useful even when the ground can't check packets
+    if !utils::checksums_match_reference() {
+        error!("FATAL: CRC self-check failed, checksums no longer match the ground station");
+    }
+
+    let flight_id = flight_id::load_or_create(Path::new(FLIGHT_ID_PATH));
+    info!("Flight ID {flight_id}");
 
     let rfd_port = serialport::new(RFD_PATH, RFD_BAUD)
         .parity(serialport::Parity::None)
@@ -54,16 +342,78 @@ async fn main() {
 
     info!("RFD-900x serial port open on {RFD_PATH}");
 
-    info!("UDP connection opened on {UDP_PORT} targeting {UDP_TARGET}");
+    if args.recovery {
+        warn!("Recovery boot, only sending GPS beacons");
+        let gps_port = open_gps_port()
+            .inspect_err(|e| error!("Could not open GPS, sending beacons without a position: {e}"))
+            .ok();
+        beacon_loop(rfd_port, gps_port, flight_id, SystemClock).await;
+        return;
+    }
+
+    info!("UDP output opened on {UDP_PORT} targeting {UDP_TARGETS:?}");
 
     let rfd_send = rfd_port.try_clone().unwrap();
     let rfd_recv = rfd_port.try_clone().unwrap();
 
     let (info_send, info_recv) = mpsc::channel();
+    let (blank_send, blank_recv) = watch::channel(None);
+    let (gps_send, gps_recv) = mpsc::channel();
+    let (baro_rate_send, baro_rate_recv) = watch::channel(BARO_DEFAULT_RATE);
+    let (recorder_send, recorder_recv) = mpsc::channel();
+    let (diagnostics_send, diagnostics_recv) = mpsc::channel();
+    let (tare_send, tare_recv) = mpsc::channel();
+    let (retransmit_send, retransmit_recv) = mpsc::channel();
+    let (radio_send, radio_recv) = mpsc::channel();
+
+    let command_sender = CommandSenders {
+        blank_until: blank_send,
+        gps: gps_send,
+        baro_rate: baro_rate_send,
+        recorder: recorder_send,
+        diagnostics: diagnostics_send,
+        tare: tare_send,
+        retransmit: retransmit_send,
+        radio: radio_send,
+    };
+    let command_receiver = CommandReceivers {
+        blank_until: blank_recv,
+        gps: gps_recv,
+        baro_rate: baro_rate_recv,
+        recorder: recorder_recv,
+        diagnostics: diagnostics_recv,
+        tare: tare_recv,
+        retransmit: retransmit_recv,
+        radio: radio_recv,
+    };
+
+    let recv_task = command_loop(
+        rfd_recv,
+        info_send.clone(),
+        command_sender,
+        args.failsafe.clone(),
+        args.low_power,
+        config,
+        SystemClock,
+    );
+    let send_task = sending_loop(
+        rfd_send,
+        info_recv,
+        info_send,
+        command_receiver,
+        flight_id,
+        args,
+        SystemClock,
+    );
 
     // Spawn and wait on the tasks until they finish, which they should never
-    let send = tokio::spawn(sending_loop(rfd_send, info_recv));
-    let recv = tokio::spawn(command_loop(rfd_recv, info_send));
+    #[cfg(all(feature = "realtime", target_os = "linux"))]
+    let (send, recv) = (
+        realtime::spawn_prioritized("send", realtime::SEND_TASK_CPU, send_task),
+        realtime::spawn_prioritized("command", realtime::COMMAND_TASK_CPU, recv_task),
+    );
+    #[cfg(not(all(feature = "realtime", target_os = "linux")))]
+    let (send, recv) = (tokio::spawn(send_task), tokio::spawn(recv_task));
 
     info!("Waiting on tasks...");
     #[allow(unused_must_use)]
@@ -72,61 +422,212 @@ async fn main() {
     }
 }
 
+/// The identity of the payload, naming the sensors and the optional
+/// telemetry which is enabled.
+fn identity(args: &Args) -> Identity {
+    let mut subsystems: Vec<String> = ["gps", "bmp581", "bno055", "hts221"].map(String::from).into();
+    for (enabled, name) in [
+        (args.raw_values, "raw"),
+        (args.thermal, "thermal"),
+        (args.computed, "computed"),
+        (args.loop_health, "loop"),
+        (args.low_power, "low_power"),
+    ] {
+        if enabled {
+            subsystems.push(name.to_string());
+        }
+    }
+
+    Identity::new(subsystems)
+}
+
+/// A summary of the active configuration as a list of short entries, to
+/// make logs self-identifying and to answer a `GetConfig` command.
+fn config_entries(args: &Args) -> Vec<String> {
+    vec![
+        format!("RFD-900x {RFD_PATH} @ {RFD_BAUD}, {RFD_AIR_RATE}kbps air, {RFD_TX_POWER}dBm"),
+        format!("GPS {GPS_PATH} @ {GPS_BAUD}"),
+        format!("secondary GPS {GPS_SECONDARY} @ {GPS_SECONDARY_BAUD}"),
+        format!("UDP {UDP_PORT} -> {UDP_TARGETS:?}"),
+        format!("send interval {}ms", SEND_INTERVAL.as_millis()),
+        format!("max packet {MAX_PACKET_BYTES} bytes"),
+        format!("uplink reserve {RFD_RESERVED_UPLINK_PERCENT}%"),
+        format!("relay pin {HIGH_POWER_RELAY_PIN_NUM}"),
+        format!("failsafe {:?}", args.failsafe),
+        format!("buzzer pin {BUZZER_PIN_NUM}"),
+        format!("outputs {}", GPIO_OUTPUTS.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")),
+        "sensors BMP581 BNO055 HTS221".to_string(),
+        format!("declination {MAGNETIC_DECLINATION}"),
+        format!("recording limit {}MiB", args.recording_limits.total_bytes() / (1024 * 1024)),
+        format!("logs {}", args.log_dir.as_deref().unwrap_or(Path::new(LOG_DIR)).display()),
+        format!(
+            "raw {} thermal {} computed {} loop health {} low power {}",
+            args.raw_values, args.thermal, args.computed, args.loop_health, args.low_power
+        ),
+    ]
+}
+
+/// The sending ends of the channels through which ground commands reach the
+/// sending task and the sensors.
+struct CommandSenders {
+    blank_until: watch::Sender<Option<Instant>>,
+    gps: Sender<GpsRequest>,
+    baro_rate: watch::Sender<u32>,
+    recorder: Sender<RecorderRequest>,
+    diagnostics: Sender<Diagnostics>,
+    tare: Sender<()>,
+    retransmit: Sender<u8>,
+    radio: Sender<RadioRequest>,
+}
+
+/// The receiving ends of [`CommandSenders`].
+struct CommandReceivers {
+    blank_until: watch::Receiver<Option<Instant>>,
+    gps: Receiver<GpsRequest>,
+    baro_rate: watch::Receiver<u32>,
+    recorder: Receiver<RecorderRequest>,
+    diagnostics: Receiver<Diagnostics>,
+    tare: Receiver<()>,
+    retransmit: Receiver<u8>,
+    radio: Receiver<RadioRequest>,
+}
+
 #[instrument(skip_all)]
-async fn sending_loop(mut rfd_send: Box<dyn SerialPort>, info_recv: Receiver<String>) {
+async fn sending_loop(
+    mut rfd_send: Box<dyn SerialPort>,
+    info_recv: Receiver<Message>,
+    info_send: Sender<Message>,
+    commands: CommandReceivers,
+    flight_id: String,
+    args: Args,
+    clock: impl Clock,
+) {
+    let CommandReceivers {
+        blank_until: blank_recv,
+        gps: gps_requests,
+        baro_rate,
+        recorder: recorder_requests,
+        diagnostics: diagnostics_requests,
+        tare: tare_requests,
+        retransmit: retransmit_requests,
+        radio: radio_requests,
+    } = commands;
+
     info!("Initalized telemetry sending");
 
+    // Every packet is sent a whole number of send intervals after this, so
+    // sensors in low power mode can be read just before each one
+    let send_start = Instant::now();
+    let schedule = if args.low_power {
+        info!("Sensors in low power mode");
+        SensorSchedule::LowPower { send_start }
+    } else {
+        SensorSchedule::Continuous
+    };
+
     let timestamp = Utc::now().to_rfc3339();
 
-    let mut telemetry_file = tokio::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(format!("telemetry_{}.json", timestamp))
-        .await.ok();
+    let mut recorder = Recorder::open(format!("telemetry_{flight_id}_{timestamp}"), args.recording_limits).await;
 
-    let mut udp_send = UdpSocket::bind(UDP_PORT).await.expect("Couldn't bind to socket address");
-    let _ = udp_send.connect(UDP_TARGET).await;
+    let udp_send = UdpSocket::bind(UDP_PORT).await.expect("Couldn't bind to socket address");
+    if let Err(e) = udp_send.set_broadcast(true) {
+        warn!("Could not enable UDP broadcast: {e}");
+    }
+
+    let udp_targets = UDP_TARGETS
+        .iter()
+        .filter_map(|target| match target.parse() {
+            Ok(addr) => Some(addr),
+            Err(e) => {
+                error!("Invalid UDP target {target}: {e}");
+                None
+            }
+        })
+        .collect();
+    let udp = UdpOutput {
+        socket: udp_send,
+        targets: udp_targets,
+    };
 
-    let i2c = Arc::new(Mutex::new(I2cdev::new("/dev/i2c-1").unwrap()));
+    let i2c = Arc::new(Mutex::new(I2cdev::new(I2C_BUS_PATH).unwrap()));
 
     // Spawn GPS task
-    let (gps_send, gps_recv) = watch::channel(None);
-    tokio::spawn(gps_loop(gps_send));
+    let (gps_send, mut gps_recv) = watch::channel(None);
+    tokio::spawn(gps_loop(gps_send, gps_requests, info_send.clone()));
     info!("Spawned GPS task");
 
     tokio::spawn(gps_secondary_loop());
     info!("Spawned Secondary GPS task");
 
     // Spawn BMP task
-    let (bmp_send, bmp_recv) = watch::channel((None,None));
+    let (bmp_send, mut bmp_recv) = tokio::sync::mpsc::unbounded_channel();
     let bmpi2c = Arc::clone(&i2c);
+    let bmp_info_send = info_send.clone();
     tokio::spawn(async move {
         let bmpi2c = MutexDevice::new(&*bmpi2c);
-        bmp_loop(bmp_send, bmpi2c).await;
+        bmp_loop(bmp_send, bmpi2c, schedule, baro_rate, bmp_info_send).await;
     });
     info!("Spawned BMP task");
 
     // Spawn BNO task
-    let (bno_send, bno_recv) = watch::channel(None);
+    let (bno_send, mut bno_recv) = watch::channel(None);
     let bnoi2c = Arc::clone(&i2c);
     tokio::spawn(async move {
         let bnoi2c = MutexDevice::new(&*bnoi2c);
-        bno055_loop(bno_send, bnoi2c).await;
+        bno055_loop(bno_send, bnoi2c, schedule).await;
     });
     info!("Spawned BNO task");
 
     // Spawn HTS task
-    let (hts_send, hts_recv) = watch::channel(None);
+    let (hts_send, mut hts_recv) = watch::channel(None);
     let htsi2c = Arc::clone(&i2c);
     tokio::spawn(async move {
         let htsi2c = MutexDevice::new(&*htsi2c);
-        hts221_loop(hts_send, htsi2c).await;
+        hts221_loop(hts_send, htsi2c, schedule).await;
     });
     info!("Spawned HTS task");
 
-    let mut info_deque = VecDeque::new();
+    let mut rfd_pacer = LinkPacer::new(RFD_BYTES_PER_SECOND, RFD_BURST);
+
+    let mut scenario = match &args.scenario {
+        Some(path) => load_scenario(path).await,
+        None => VecDeque::new(),
+    };
+
+    let mut info_queue = InfoQueue::new(MAX_PENDING_INFO, MAX_REPEATED_INFO, MAX_INFO_BYTES_PER_FRAME);
+    let mut event_queue = EventQueue::new(EVENT_REPEATS, MAX_QUEUED_EVENTS);
+    let mut seq: u8 = 0;
+    let mut retransmit_buffer = RetransmitBuffer::new();
+    let mut radio_settings = RadioSettings::new(RFD_AIR_RATE, RFD_TX_POWER);
+    let mut blanked = false;
+    let mut duty_cycle = DutyCycle::new(RFD_DUTY_POLICY);
+    let mut was_hot = false;
+    let mut supervisor = Supervisor::new(send_start);
+    let mut pressure_decimator = Decimator::new(PRESSURE_DECIMATION)
+        .with_median_filter(PRESSURE_MEDIAN_WINDOW);
+    let mut temperature_decimator = Decimator::new(TEMPERATURE_DECIMATION);
+    let mut held_bmp = None;
+    let mut lane_scheduler = LaneScheduler::new(&DOWNLINK_LANES, LANE_BUDGET_BYTES);
+    let identity = identity(&args);
+    let mut sea_level = SeaLevelCalibration::new();
+    let mut stationary = StationaryDetector::new();
+    let mut was_stationary = false;
+    let mut fix_acquisition = FixAcquisition::new(REPORT_REACQUIRED_FIX);
+    let started = clock.now();
+    let mut altitude_consistency = AltitudeConsistency::new();
+    let mut apogee_detector = ApogeeDetector::new();
+    let mut tare = Tare::new();
+    let mut bus_recovery = BusRecovery::new();
+    let mut loop_monitor = LoopMonitor::new(SEND_INTERVAL);
+
+    let mut virtual_sensors = VirtualSensors::new(MAX_VIRTUAL_SENSORS, MAX_COMPUTED_BYTES);
+    if args.computed
+        && let Err(e) = virtual_sensors.add(DensityAltitude)
+    {
+        error!("Could not add virtual sensor: {e}");
+    }
 
-    let mut sending_interval = time::interval(Duration::from_millis(250));
+    let mut sending_interval = time::interval_at(send_start, SEND_INTERVAL);
     sending_interval.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
 
     // Main packet sending loop. A packet should be sent 4 times per second,
@@ -134,228 +635,968 @@ async fn sending_loop(mut rfd_send: Box<dyn SerialPort>, info_recv: Receiver<Str
     // the packet information to be unavailable so any single part failing
     // cannot take down the whole system.
     //
-    // Every packet begins with a CRC as a decimal number, followed by a space
-    // followed by the JSON data, and terminated by a newline (`\n`).
+    // See `encode_frame` for the format of each packet on the wire.
     loop {
-        if let Ok(i) = info_recv.try_recv() {
-            info_deque.push_back(i);
+        let build_start = clock.now();
+
+        while let Ok(message) = info_recv.try_recv() {
+            match message {
+                Message::Text(i) => push_info(&mut info_queue, i),
+                Message::Event(event) => push_event(&mut event_queue, event),
+            }
+        }
+
+        // Any subsystem which has produced new data since the last packet is
+        // healthy. A task which has exited has dropped its sender, so it
+        // will stop counting as healthy once it times out.
+        let now = Instant::from_std(clock.now());
+        let gps_fresh = gps_recv.has_changed().unwrap_or(false);
+        let gps_data = take_latest(&mut gps_recv, Subsystem::Gps, &mut supervisor, now);
+        let bno_reading = take_latest(&mut bno_recv, Subsystem::Bno, &mut supervisor, now);
+        let bno_data = bno_reading.map(|reading| reading.orientation);
+        let hts_data = take_latest(&mut hts_recv, Subsystem::Hts, &mut supervisor, now);
+
+        // Only fixes good enough to downlink are sent, so that is when the
+        // fix counts as acquired
+        let fix_acquired = fix_acquisition.update(gps_data.is_some());
+        if fix_acquired {
+            let uptime = now.into_std().saturating_duration_since(started).as_secs();
+            info!("GPS fix acquired after {uptime}s");
+            push_event(
+                &mut event_queue,
+                Event::with_arg(EventCode::GpsFixAcquired, u32::try_from(uptime).unwrap_or(u32::MAX)),
+            );
+        }
+
+        // The BMP581 samples faster than packets are sent, so every sample
+        // since the last packet is reduced into the values sent
+        let mut bmp_latest = None;
+        while let Ok((pres, temp)) = bmp_recv.try_recv() {
+            pressure_decimator.push(pres);
+            temperature_decimator.push(temp);
+            bmp_latest = Some((pres, temp));
+        }
+        let pressure = pressure_decimator.finish();
+        let temperature = temperature_decimator.finish();
+        if pressure.is_some() {
+            supervisor.report(Subsystem::Bmp, now);
+        }
+
+        // At low sample rates the BMP581 has nothing new for some packets,
+        // so its last values are sent again until it stops responding
+        if let Some((pres, temp)) = pressure.zip(temperature) {
+            held_bmp = Some((pres.value, temp.value));
+        } else if !supervisor.is_healthy(Subsystem::Bmp, now) {
+            held_bmp = None;
+        }
+
+        if supervisor.update(now) {
+            if supervisor.safe_mode() {
+                warn!(
+                    "Entering SAFE MODE, only {} subsystems are healthy",
+                    supervisor.healthy_count(now)
+                );
+            } else {
+                info!("Leaving safe mode");
+            }
+        }
 
-            if info_deque.len() >= 4 {
-                info_deque.pop_front();
+        // Every sensor failing at once points to a device holding the bus,
+        // rather than to the sensors themselves
+        let i2c_healthy = [Subsystem::Bmp, Subsystem::Bno, Subsystem::Hts]
+            .iter()
+            .any(|subsystem| supervisor.is_healthy(*subsystem, now));
+        match bus_recovery.update(i2c_healthy, now.into_std()) {
+            Some(BusEvent::Locked(attempt)) => {
+                warn!("Every I²C sensor has stopped responding, recovering bus (attempt {attempt})");
+                push_event(&mut event_queue, Event::with_arg(EventCode::I2cBusLocked, attempt));
+
+                // The sensor tasks reinitialize themselves once the bus
+                // works again
+                let i2c = Arc::clone(&i2c);
+                tokio::task::spawn_blocking(move || recover_i2c_bus(&i2c));
+            }
+            Some(BusEvent::Recovered) => {
+                info!("I²C bus recovered");
+                push_event(&mut event_queue, Event::new(EventCode::I2cBusRecovered));
+            }
+            Some(BusEvent::GaveUp) => {
+                error!("Could not recover I²C bus after {} attempts", bus_recovery.attempts());
+                push_event(&mut event_queue, Event::new(EventCode::I2cBusRecoveryFailed));
             }
+            None => (),
         }
 
-        let bmp_data = *bmp_recv.borrow();
-        let pressure = bmp_data.0.unwrap_or(0.0);
-        let temperature = bmp_data.1.unwrap_or(0.0);
+        // Only the stability of the pressure altitude matters, so the
+        // reference it is calculated with doesn't
+        let is_stationary = stationary.update(
+            pressure.map(|p| pressure_altitude(p.value, STANDARD_SEA_LEVEL_PRESSURE)),
+            gps_data.and_then(|gps| gps.speed),
+        );
+        if is_stationary != was_stationary {
+            was_stationary = is_stationary;
+            info!("Payload is {}", if is_stationary { "stationary" } else { "moving" });
+        }
+
+        // Pressure Altitude Calculation, calibrated once against new GPS
+        // fixes while stationary on the pad
+        if let Some(p) = pressure
+            && sea_level.update(
+                p.value,
+                gps_data.as_ref().filter(|_| gps_fresh && is_stationary),
+                now.into_std(),
+            )
+        {
+            push_event(
+                &mut event_queue,
+                Event::with_arg(EventCode::SeaLevelLocked, sea_level.reference() as u32),
+            );
+        }
+        let p_alt = pressure.map(|p| sea_level.altitude(p.value));
 
-        // Pressure Altitude Calculation
-        let p_alt = bmp_data.0.map(|p| calculate_barometric_altitude(p as f32) as f64);
+        // Before calibration the pressure altitude can be far off, so the
+        // altitudes are only compared once it is locked
+        let gps_alt = gps_data.map(|gps| f64::from(gps.altitude));
+        if sea_level.is_locked()
+            && let Some(diverged) = altitude_consistency.update(gps_alt, p_alt, now.into_std())
+        {
+            let event = if diverged {
+                let divergence = gps_alt.zip(p_alt).map_or(0.0, |(gps, baro)| (gps - baro).abs());
+                warn!("GPS and barometric altitudes disagree by {divergence:.0}m");
+                Event::with_arg(EventCode::AltitudeDiverged, divergence as u32)
+            } else {
+                info!("GPS and barometric altitudes agree again");
+                Event::new(EventCode::AltitudeAgreed)
+            };
+            push_event(&mut event_queue, event);
+        }
 
-        let hts_data = *hts_recv.borrow();
-        let humidity = hts_data.unwrap_or(0.0);
+        while tare_requests.try_recv().is_ok() {
+            match tare.capture(pressure.map(|p| p.value), bno_data, is_stationary) {
+                Ok(()) => {
+                    let baseline = tare.pressure().unwrap_or_default();
+                    info!("Sensors tared to {baseline:.0}Pa");
+                    push_event(&mut event_queue, Event::with_arg(EventCode::SensorsTared, baseline as u32));
+                }
+                Err(e) => {
+                    warn!("Rejected command {}: {e}", Commands::TareSensors as u8);
+                    let event = Event::with_arg(EventCode::CommandRejected, Commands::TareSensors as u32);
+                    push_event(&mut event_queue, event);
+                }
+            }
+        }
+
+        if let Some(apogee) = apogee_detector.update(p_alt, gps_alt, now.into_std()) {
+            let code = match apogee.confidence {
+                ApogeeConfidence::High => EventCode::Apogee,
+                ApogeeConfidence::Low => EventCode::ApogeeLowConfidence,
+            };
+            info!("Apogee at {:.0}m ({:?} confidence)", apogee.altitude, apogee.confidence);
+            push_event(&mut event_queue, Event::with_arg(code, apogee.altitude.max(0.0) as u32));
+        }
+
+        let humidity = hts_data.map_or(0.0, |hts| f64::from(hts.humidity_x2) / 2.0);
 
         // Construct a packet from the data
-        let env_info = EnvironmentalInfo {
-            pressure,
-            temperature,
+        let env_info = held_bmp.map(|(held_pressure, held_temperature)| EnvironmentalInfo {
+            pressure: held_pressure,
+            pressure_range: pressure.and_then(|p| p.range),
+            temperature: held_temperature,
+            temperature_range: temperature.and_then(|t| t.range),
             humidity,
-        };
+        });
 
-        let packet = TelemetryPacket {
-            gps: *gps_recv.borrow(),
+        let computed = virtual_sensors.compute(&TelemetrySnapshot {
+            pressure: pressure.map(|p| p.value),
+            temperature: temperature.map(|t| t.value),
+            humidity: hts_data.map(|_| humidity),
             pressure_altitude: p_alt,
-            environmental_info: Some(env_info),
-            orientation_info: *bno_recv.borrow(),
-            info: info_deque.clone(),
+            gps: gps_data,
+        });
+
+        // Scripted packets replace live ones until they run out. They are
+        // taken first, so queued info and events wait for the live packets
+        let scripted = scenario.pop_front();
+
+        let mut packet = TelemetryPacket {
+            schema_version: SchemaVersion::CURRENT,
+            gps: gps_data,
+            pressure_altitude: p_alt,
+            environmental_info: env_info,
+            orientation_info: bno_data,
+            events: match scripted {
+                Some(_) => VecDeque::new(),
+                None => event_queue.next_frame(),
+            },
+            safe_mode: supervisor.safe_mode(),
+            time: Some(clock.unix_millis()),
+            fix_acquired,
+            ..TelemetryPacket::default()
+        };
+
+        let extras = TelemetryPacket {
+            thermal: args.thermal.then(|| ThermalInfo {
+                cpu: health::cpu_temperature(),
+            }),
+            raw: raw_sensor_values(args.raw_values, bmp_latest, hts_data),
+            // The timing of this packet isn't known until it is sent, so
+            // the previous packets are reported
+            loop_health: args.loop_health.then(|| loop_monitor.health()),
+            computed,
+            tared_height: pressure.and_then(|p| tare.height(p.value)),
+            relative_orientation: bno_data.and_then(|q| tare.relative_orientation(q)),
+            heading: bno_reading.and_then(|reading| reading.heading),
+            ..TelemetryPacket::default()
+        };
+
+        // The info lane is costed at its limit, as choosing the messages
+        // takes them off the queue
+        let lanes = lane_scheduler.next_frame(&[
+            json_len(&packet),
+            json_len(&flight_id) + json_len(&identity),
+            json_len(&extras).saturating_sub(json_len(&TelemetryPacket::default())),
+            MAX_INFO_BYTES_PER_FRAME,
+        ]);
+
+        // The flight ID is refreshed on disk whenever it is sent, so it is
+        // reused after a reboot
+        if lanes[IDENTITY_LANE] {
+            if let Err(e) = flight_id::refresh(Path::new(FLIGHT_ID_PATH), &flight_id) {
+                warn!("Could not refresh flight ID: {e}");
+            }
+            packet.flight_id = Some(flight_id.clone());
+            packet.identity = Some(identity.clone());
+        }
+        if lanes[EXTRAS_LANE] {
+            packet = TelemetryPacket {
+                thermal: extras.thermal,
+                raw: extras.raw,
+                loop_health: extras.loop_health,
+                computed: extras.computed,
+                tared_height: extras.tared_height,
+                relative_orientation: extras.relative_orientation,
+                heading: extras.heading,
+                ..packet
+            };
+        }
+        if lanes[INFO_LANE] && scripted.is_none() {
+            packet.info = info_queue.next_frame();
+        }
+
+        // Scripted packets are sent exactly as given
+        let packet = match scripted {
+            Some(scripted) => {
+                if scenario.is_empty() {
+                    info!("Scenario finished, sending live data");
+                }
+                scripted
+            }
+            None => packet,
         };
 
-        write_data(
+        // Transmit blanking only ever takes effect between whole frames, so
+        // the RFD-900x can never be left with a partially written packet.
+        let now_blanked = is_blanked(&blank_recv, now);
+        if now_blanked != blanked {
+            blanked = now_blanked;
+            if blanked {
+                info!("RFD-900x transmit blanked");
+            } else {
+                info!("RFD-900x transmit resumed");
+            }
+        }
+
+        let temperature = RFD_DUTY_POLICY.uses_temperature().then(health::cpu_temperature).flatten();
+        let transmit = duty_cycle.should_send(temperature, !is_stationary);
+        if duty_cycle.is_hot() != was_hot {
+            was_hot = duty_cycle.is_hot();
+            if was_hot {
+                warn!("Payload is hot, cutting back RFD-900x transmissions");
+            } else {
+                info!("Payload has cooled, transmitting every packet");
+            }
+        }
+
+        // Handled between packets, so a rotation never splits a record
+        while let Ok(request) = recorder_requests.try_recv() {
+            match request {
+                RecorderRequest::Enable => recorder.set_enabled(true).await,
+                RecorderRequest::Disable => recorder.set_enabled(false).await,
+                RecorderRequest::Rotate => match recorder.restart().await {
+                    Some((path, written)) => {
+                        let _ = info_send.send(format!("REC {} {written}", path.display()).into());
+                    }
+                    None => {
+                        let event = Event::with_arg(EventCode::CommandRejected, Commands::RecorderRotate as u32);
+                        let _ = info_send.send(event.into());
+                    }
+                },
+            }
+        }
+
+        // Changing the radios stops telemetry for several seconds while
+        // they are in command mode and rebooting, which the link would be
+        // down for anyway
+        while let Ok(request) = radio_requests.try_recv() {
+            match request {
+                RadioRequest::Change(setting, command) => match radio_settings.change(setting, now.into_std()) {
+                    Ok(at_commands) => {
+                        info!("Changing radios to {setting:?}, pending confirmation");
+                        if let Err(e) = send_at_commands(&mut rfd_send, &at_commands).await {
+                            error!("Failed to change radios: {e}");
+                        }
+                        push_event(&mut event_queue, Event::new(EventCode::RadioChangePending));
+                    }
+                    Err(e) => {
+                        warn!("Rejected command {}: {e}", command as u8);
+                        push_event(&mut event_queue, Event::with_arg(EventCode::CommandRejected, command as u32));
+                    }
+                },
+                RadioRequest::Confirm => {
+                    if radio_settings.confirm() {
+                        info!("Radio change confirmed");
+                        push_event(&mut event_queue, Event::new(EventCode::RadioChangeConfirmed));
+                    } else {
+                        warn!("Rejected command {}: no radio change to confirm", Commands::ConfirmRadio as u8);
+                        let event = Event::with_arg(EventCode::CommandRejected, Commands::ConfirmRadio as u32);
+                        push_event(&mut event_queue, event);
+                    }
+                }
+            }
+        }
+        if let Some(at_commands) = radio_settings.expired(now.into_std()) {
+            warn!("Radio change was not confirmed, reverting");
+            if let Err(e) = send_at_commands(&mut rfd_send, &at_commands).await {
+                error!("Failed to revert radios: {e}");
+            }
+            push_event(&mut event_queue, Event::new(EventCode::RadioChangeReverted));
+        }
+
+        // Sensors count as working if they have produced data recently,
+        // so checking them doesn't interrupt their readings
+        while let Ok(mut diagnostics) = diagnostics_requests.try_recv() {
+            for (bit, subsystem) in [
+                (Diagnostics::GPS, Subsystem::Gps),
+                (Diagnostics::BMP, Subsystem::Bmp),
+                (Diagnostics::BNO, Subsystem::Bno),
+                (Diagnostics::HTS, Subsystem::Hts),
+            ] {
+                diagnostics.set(bit, supervisor.is_healthy(subsystem, now));
+            }
+            diagnostics.set(Diagnostics::RECORDER, recorder.is_recording());
+
+            info!("Diagnostics: {diagnostics}");
+            push_event(
+                &mut event_queue,
+                Event::with_arg(EventCode::DiagnosticsResult, diagnostics.bits().into()),
+            );
+        }
+
+        while let Ok(requested) = retransmit_requests.try_recv() {
+            // The ground can't tell which frames held only realtime data,
+            // so requests for frames which aren't kept are expected
+            if !retransmit_buffer.request(requested) {
+                debug!("Frame {requested} is not kept for retransmission");
+            }
+        }
+
+        let frame = write_data(
+            seq,
             &packet,
-            &mut rfd_send,
-            &mut udp_send,
-            &mut telemetry_file
+            (!blanked && transmit).then_some(&mut rfd_send),
+            &mut rfd_pacer,
+            &udp,
+            &mut recorder,
+            now.into_std(),
         ).await;
 
+        // Only frames holding data which isn't realtime are worth sending
+        // again, and only with the link capacity the live frames leave
+        if !packet.info.is_empty() || !packet.events.is_empty() {
+            retransmit_buffer.push(seq, frame.clone());
+        }
+        if !blanked
+            && transmit
+            && let Some(resend) = retransmit_buffer.next_requested()
+            && rfd_pacer.try_send_spare(resend.len(), frame.len(), now.into_std())
+        {
+            if let Err(e) = rfd_send.write_all(resend).and_then(|()| rfd_send.flush()) {
+                warn!("Failed to retransmit frame to RFD-900x: {e}");
+            }
+            retransmit_buffer.sent();
+        }
+        seq = seq.wrapping_add(1);
+
+        let build_time = clock.now().saturating_duration_since(build_start);
+        if loop_monitor.record(build_time) {
+            warn!(
+                "Packet took {}ms of the {}ms send interval",
+                build_time.as_millis(),
+                loop_monitor.budget().as_millis()
+            );
+        }
+
         sending_interval.tick().await;
     }
 }
 
+/// The length of a value serialized as JSON, to estimate how much of a
+/// packet it takes up.
+fn json_len<T: serde::Serialize>(value: &T) -> usize {
+    serde_json::to_vec(value).map_or(0, |json| json.len())
+}
+
+/// Load the packets of a scenario file, which has the same format as a
+/// recording.
+async fn load_scenario(path: &Path) -> VecDeque<TelemetryPacket> {
+    let contents = match tokio::fs::read_to_string(path).await {
+        Ok(contents) => contents,
+        Err(e) => {
+            error!("Could not read scenario {}: {e}", path.display());
+            return VecDeque::new();
+        }
+    };
+
+    let mut packets = VecDeque::new();
+    for (number, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        match parse_record(line.as_bytes()) {
+            Ok((_, packet)) => packets.push_back(packet),
+            Err(e) => warn!("Skipping invalid packet on line {} of scenario: {e}", number + 1),
+        }
+    }
+
+    info!("Loaded scenario of {} packets from {}", packets.len(), path.display());
+    packets
+}
+
+/// Queue an info message to be sent in the following packets.
+///
+/// Messages longer than [`MAX_INFO_BYTES`] are truncated, so one long
+/// message can't dominate the packet.
+fn push_info(info: &mut InfoQueue, mut message: String) {
+    if message.len() > MAX_INFO_BYTES {
+        let mut end = MAX_INFO_BYTES - INFO_TRUNCATION_MARKER.len();
+        while !message.is_char_boundary(end) {
+            end -= 1;
+        }
+        message.truncate(end);
+        message.push_str(INFO_TRUNCATION_MARKER);
+    }
+
+    if !info.push(message) {
+        warn!("Too many info messages waiting, dropped the oldest");
+    }
+}
+
+/// Queue an event to be sent in the following packets.
+fn push_event(events: &mut EventQueue, event: Event) {
+    info!("Event: {event}");
+
+    if !events.push(event) {
+        warn!("Too many events waiting, dropped the oldest");
+    }
+}
+
+/// Whether the RFD-900x downlink is inside a blanking window at `now`.
+fn is_blanked(blank_until: &watch::Receiver<Option<Instant>>, now: Instant) -> bool {
+    blank_until.borrow().is_some_and(|until| now < until)
+}
+
+/// Take the latest value from a sensor task.
+///
+/// If the task has produced a new, valid value since the last call, the
+/// subsystem is reported to the supervisor as healthy.
+fn take_latest<T: Copy>(
+    recv: &mut watch::Receiver<Option<T>>,
+    subsystem: Subsystem,
+    supervisor: &mut Supervisor,
+    now: Instant,
+) -> Option<T> {
+    let changed = recv.has_changed().unwrap_or(false);
+    let value = *recv.borrow_and_update();
+
+    if changed && value.is_some() {
+        supervisor.report(subsystem, now);
+    }
+
+    value
+}
+
+/// Recover the raw sensor outputs from the latest readings, if they are
+/// `enabled`.
+///
+/// The BMP581 outputs fixed point values which the driver only scales, and
+/// which fit exactly in an `f32`, so scaling them back is lossless.
+fn raw_sensor_values(enabled: bool, bmp: Option<(f64, f64)>, hts: Option<HtsReading>) -> Option<RawValues> {
+    enabled.then(|| RawValues {
+        bmp_pressure: bmp.map(|(pres, _)| (pres * 64.0).round() as u32),
+        bmp_temperature: bmp.map(|(_, temp)| (temp * 65536.0).round() as i32),
+        hts_humidity: hts.map(|hts| hts.raw),
+    })
+}
+
+/// Send the GPS position to the ground, and nothing else, for recovering a
+/// payload which may be damaged.
+///
+/// Nothing but the GPS and RFD-900x is touched, and no failure stops the
+/// beacons, so as much hardware as possible can be broken without losing
+/// the position. Every beacon carries the flight ID, so the ground knows
+/// which payload it is hearing.
+#[instrument(skip_all)]
+async fn beacon_loop(
+    mut rfd_send: impl Write,
+    gps_port: Option<impl std::io::Read + Write + Send + 'static>,
+    flight_id: String,
+    clock: impl Clock,
+) {
+    // The GPS task is only missing if its port couldn't be opened, in which
+    // case beacons are still sent so the ground knows the payload is alive
+    let (gps_send, gps_recv) = watch::channel(None);
+    let (_gps_requests_send, gps_requests) = mpsc::channel();
+    let (info_send, info_recv) = mpsc::channel();
+    if let Some(gps_port) = gps_port {
+        tokio::spawn(async move { read_gps(gps_port, &gps_send, &gps_requests, &info_send) });
+    }
+
+    let mut event_queue = EventQueue::new(EVENT_REPEATS, MAX_QUEUED_EVENTS);
+    let mut seq: u8 = 0;
+    let mut beacon_interval = time::interval(BEACON_INTERVAL);
+    beacon_interval.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
+
+    loop {
+        beacon_interval.tick().await;
+
+        while let Ok(message) = info_recv.try_recv() {
+            if let Message::Event(event) = message {
+                push_event(&mut event_queue, event);
+            }
+        }
+
+        let packet = TelemetryPacket {
+            gps: *gps_recv.borrow(),
+            events: event_queue.next_frame(),
+            safe_mode: true,
+            flight_id: Some(flight_id.clone()),
+            time: Some(clock.unix_millis()),
+            ..TelemetryPacket::default()
+        };
+
+        let frame = encode_frame(seq, &packet);
+        if let Err(e) = rfd_send.write_all(&frame).and_then(|()| rfd_send.flush()) {
+            warn!("Failed to write beacon to RFD-900x: {e}");
+        }
+        seq = seq.wrapping_add(1);
+    }
+}
+
+/// The UDP socket telemetry is sent from, and the addresses it is sent to.
+struct UdpOutput {
+    socket: UdpSocket,
+    targets: Vec<SocketAddr>,
+}
+
 /// Write data out to the outputs which need it
+///
+/// If `rfd_send` is `None` the RFD-900x is being blanked, and the packet is
+/// only sent over UDP and recorded locally. The same happens if sending the
+/// packet to the RFD-900x would exceed its throughput. Returns the frame the
+/// packet was sent in.
 #[instrument(skip_all)]
 async fn write_data(
+    seq: u8,
     packet: &TelemetryPacket,
-    rfd_send: &mut Box<dyn SerialPort>,
-    udp_send: &mut UdpSocket,
-    telemetry_file: &mut Option<tokio::fs::File>
-) {
-    // Calculate the CRC of the packet based on its data.
-    let (packet_bytes, packet_crc) = packet.vec_crc();
+    rfd_send: Option<&mut impl Write>,
+    rfd_pacer: &mut LinkPacer,
+    udp: &UdpOutput,
+    recorder: &mut Recorder,
+    now: std::time::Instant,
+) -> Vec<u8> {
+    // A packet which can't be serialized must not stop the sending loop, so
+    // a minimal one flagging the error is sent instead
+    let (packet_bytes, crc, error) = packet.vec_crc_or_fallback();
+    if let Some(e) = error {
+        error!("Sending fallback packet: {e}");
+    }
+    let frame = frame_data(seq, &packet_bytes, crc);
 
-    if packet_bytes.len() > MAX_PACKET_BYTES {
-        warn!("Packet size of {} bytes exceeds max of {MAX_PACKET_BYTES}", packet_bytes.len());
+    if frame.len() > MAX_PACKET_BYTES {
+        warn!("Packet size of {} bytes exceeds max of {MAX_PACKET_BYTES}", frame.len());
     }
 
-    // Write the data out to the RFD-900x
-    let _ = rfd_send.write_all(packet_crc.to_string().as_bytes());
-    let _ = rfd_send.write_all(b" ");
-    let _ = rfd_send.write_all(&packet_bytes);
-    let _ = rfd_send.write_all(b"\n");
-    let _ = rfd_send.flush();
+    // Write the data out to the RFD-900x. `write_all` retries short writes,
+    // so a frame is only ever cut off by an error, and the ground scanner
+    // discards a cut off frame once it sees the sync word of the next one.
+    //
+    // Frames beyond what the link can carry are dropped rather than left to
+    // back up in the radio.
+    if let Some(rfd_send) = rfd_send {
+        if !rfd_pacer.try_send(frame.len(), now) {
+            debug!("Dropped frame of {} bytes to stay within the RFD-900x throughput", frame.len());
+        } else if let Err(e) = rfd_send.write_all(&frame).and_then(|()| rfd_send.flush()) {
+            warn!("Failed to write frame to RFD-900x: {e}");
+        }
+    }
 
-    // Write the same data out to the UDP port
-    if udp_send.peer_addr().is_err() {
-        let _ = udp_send.connect(UDP_TARGET).await;
-    } else {
-        let _ = udp_send.send(packet_crc.to_string().as_bytes()).await;
-        let _ = udp_send.send(b" ").await;
-        let _ = udp_send.send(&packet_bytes).await;
-        let _ = udp_send.send(b"\n").await;
+    // Write the same data out to every UDP target. A target which fails
+    // must not stop the others from receiving the frame.
+    if frame.len() > UDP_MAX_DATAGRAM_BYTES {
+        warn!("Frame of {} bytes is too large for a single UDP datagram", frame.len());
+    }
+    for target in &udp.targets {
+        match udp.socket.send_to(&frame, target).await {
+            Ok(sent) if sent < frame.len() => {
+                warn!("Only sent {sent} of {} bytes to {target}", frame.len());
+            }
+            Ok(_) => (),
+            Err(e) => debug!("Failed to send frame to {target}: {e}"),
+        }
     }
 
-    debug!("Sent {:?} of {} bytes, checksum {}", packet, packet_bytes.len(), packet_crc);
+    debug!("Sent {:?} in a frame of {} bytes", packet, frame.len());
 
-    if let Some(t_file) = telemetry_file.as_mut() {
-        let _ = t_file.write_all(&packet_bytes).await;
-        let _ = t_file.write_all(b"\n").await;
-        let _ = t_file.flush().await;
-    }
+    // The local recording holds each frame without its sync word and
+    // newline, so it has the sequence numbers to compare against what the
+    // ground received
+    recorder.write(&frame[SYNC_WORD.len()..frame.len() - 1]).await;
+
+    frame
 }
 
 #[instrument(skip_all)]
-async fn command_loop(mut rfd_recv: Box<dyn SerialPort>, info_send: Sender<String>) {
+async fn command_loop(
+    mut rfd_recv: Box<dyn SerialPort>,
+    info_send: Sender<Message>,
+    commands: CommandSenders,
+    failsafe: Vec<FailsafeAction>,
+    low_power: bool,
+    config: Vec<String>,
+    clock: impl Clock,
+) {
     info!("Initalized command receiving");
 
     // Set up relay GPIO pin
     let gpio = Gpio::new().expect("Unable to initalize GPIO pins");
-    let mut relay_pin = gpio.get(HIGH_POWER_RELAY_PIN_NUM)
-        .unwrap()
-        .into_output();
-    relay_pin.set_reset_on_drop(false);
-    relay_pin.set_high();
+    let relay_pin = match gpio.get(HIGH_POWER_RELAY_PIN_NUM) {
+        Ok(pin) => {
+            let mut pin = pin.into_output();
+            pin.set_reset_on_drop(false);
+            pin.set_high();
+            Some(pin)
+        }
+        Err(e) => {
+            error!("Unable to set up relay pin: {e}");
+            None
+        }
+    };
+
+    let (buzzer_send, buzzer_recv) = watch::channel(false);
+    match gpio.get(BUZZER_PIN_NUM) {
+        Ok(pin) => {
+            let mut pin = pin.into_output_low();
+            let set_pin = move |high: bool| pin.write(high.into());
+            tokio::spawn(buzzer_loop(set_pin, BUZZER_PATTERN, buzzer_recv, info_send.clone()));
+        }
+        Err(e) => error!("Unable to set up buzzer pin: {e}"),
+    }
+
+    let outputs = GPIO_OUTPUTS
+        .iter()
+        .map(|output| {
+            let name = output.name;
+            let pin = match gpio.get(output.pin) {
+                Ok(pin) => {
+                    let mut pin = pin.into_output_low();
+                    pin.set_reset_on_drop(false);
+                    Some(pin)
+                }
+                Err(e) => {
+                    error!("Unable to set up output {name}: {e}");
+                    None
+                }
+            };
+            NamedOutput { name, pin, interlocked: output.interlocked }
+        })
+        .collect();
 
     // Create command parser with devices
     let mut command_parser = CommandParser {
         relay_pin,
         info_sender: info_send,
+        blank_until: commands.blank_until,
+        buzzer: buzzer_send,
+        gps: commands.gps,
+        baro_rate: commands.baro_rate,
+        outputs,
+        armed: false,
+        recorder: commands.recorder,
+        diagnostics: commands.diagnostics,
+        tare: commands.tare,
+        retransmit: commands.retransmit,
+        radio: commands.radio,
+        failsafe,
+        low_power,
+        config,
+        last_run: HashMap::new(),
+        clock,
     };
 
-    // Each buffer must consist of 3 bytes:
-    //  1. Command
-    //  2. Checksum
-    //  3. Space b' '
-    //
-    //  If the buffer violates this at any time, it must be discarded as
-    //  invalid.
+    let mut watchdog = LinkWatchdog::new(command_parser.now());
+
     let mut buf = Vec::new();
     loop {
+        if watchdog.check(command_parser.now()) {
+            command_parser.run_failsafe();
+        }
+
         let mut byte_buf = [0];
         if rfd_recv.read_exact(&mut byte_buf).is_err() {
             continue;
         }
 
-        buf.push(byte_buf[0]);
-
-        if buf.len() > 3 || (buf.last() != Some(&b' ') && buf.len() == 3) {
-            warn!("Buffer invalid: {:?}", buf);
-            buf.clear();
-            continue;
+        if watchdog.received(command_parser.now()) {
+            info!("Uplink restored");
+            let _ = command_parser.info_sender.send(EventCode::LinkRestored.into());
         }
 
-        if buf.first() == Some(&b' ') || buf.get(1) == Some(&b' ') {
-            warn!("Buffer invalid: {:?}", buf);
-            buf.clear();
+        let Some(command) = parse_command_buffer(&mut buf, byte_buf[0]) else {
             continue;
+        };
+
+        match command_parser.parse_command(command).await {
+            Ok(()) => (),
+            Err(e) => error!("ERR: {e:?}, {e}"),
         }
+    }
+}
 
-        if buf.len() == 3 && buf.last() == Some(&b' ') {
-            info!("Got command {:?}", buf);
+/// How the recovery buzzer is pulsed.
+#[derive(Debug, Clone, Copy)]
+struct BuzzerPattern {
+    /// How long the buzzer is on for in each pulse
+    on: Duration,
+    /// How long the buzzer is off for between pulses
+    off: Duration,
+    /// The buzzer turns itself off after this long to conserve battery
+    timeout: Duration,
+}
 
-            let data = buf[0];
-            let check = buf[1];
+/// Pulse the recovery buzzer, driven by `set_pin`, while it is enabled, up
+/// to the timeout of the pattern.
+#[instrument(skip_all)]
+async fn buzzer_loop(
+    mut set_pin: impl FnMut(bool),
+    pattern: BuzzerPattern,
+    mut enabled: watch::Receiver<bool>,
+    info_send: Sender<Message>,
+) {
+    loop {
+        set_pin(false);
 
-            let new_cksum = crc8(&[data]);
+        // Wait until the buzzer is turned on
+        if enabled.changed().await.is_err() {
+            return;
+        }
+        if !*enabled.borrow_and_update() {
+            continue;
+        }
 
-            if check != new_cksum {
-                warn!(
-                    "Checksums do not match ({} != {}), discarding packet",
-                    check,
-                    new_cksum
-                );
-                continue;
-            }
+        info!("Buzzer started");
+        let deadline = Instant::now() + pattern.timeout;
 
-            match command_parser.parse_command(data).await {
-                Ok(()) => (),
-                Err(e) => error!("ERR: {e:?}, {e}"),
+        let mut stopped = false;
+        while !stopped && Instant::now() < deadline {
+            set_pin(true);
+            sleep(pattern.on).await;
+            set_pin(false);
+
+            tokio::select! {
+                () = sleep(pattern.off) => (),
+                changed = enabled.changed() => {
+                    stopped = changed.is_err() || !*enabled.borrow_and_update();
+                }
             }
+        }
 
-            buf.clear();
+        if stopped {
+            info!("Buzzer stopped");
+        } else {
+            info!("Buzzer timed out");
+            let _ = info_send.send(EventCode::BuzzerTimedOut.into());
         }
     }
 }
 
+/// Send the configuration commands to the Adafruit Ultimate GPS module.
+fn configure_gps(gps_port: &mut dyn Write, rate: u32) -> std::io::Result<()> {
+    for command in GPS_CONFIG_COMMANDS {
+        gps_port.write_all(nmea_sentence(command).as_bytes())?;
+    }
+
+    set_gps_rate(gps_port, rate)
+}
+
+/// Set the fix rate of the primary GPS in hertz.
+fn set_gps_rate(gps_port: &mut dyn Write, rate: u32) -> std::io::Result<()> {
+    let command = format!("PMTK220,{}", 1000 / rate.max(1));
+    gps_port.write_all(nmea_sentence(&command).as_bytes())?;
+
+    gps_port.flush()
+}
+
 /// Function to read the Adafruit Ultimate GPS module.
 #[instrument(skip_all)]
-async fn gps_loop(data: watch::Sender<Option<GpsInfo>>) {
-    // Set up the GPS serial port. This must utilize the proper port on the
-    // raspberry pi.
-    let mut gps_port = serialport::new(GPS_PATH, GPS_BAUD)
+async fn gps_loop(
+    data: watch::Sender<Option<GpsInfo>>,
+    requests: Receiver<GpsRequest>,
+    info_send: Sender<Message>,
+) {
+    let gps_port = open_gps_port().unwrap();
+
+    read_gps(gps_port, &data, &requests, &info_send);
+}
+
+/// Open the serial port of the primary GPS. This must utilize the proper
+/// port on the raspberry pi.
+fn open_gps_port() -> serialport::Result<Box<dyn SerialPort>> {
+    serialport::new(GPS_PATH, GPS_BAUD)
         .timeout(Duration::from_millis(50))
         .open()
-        .unwrap();
+}
 
+/// Configure the primary GPS and read fixes from it, until the sender of
+/// `requests` is dropped.
+fn read_gps(
+    mut gps_port: impl std::io::Read + Write,
+    data: &watch::Sender<Option<GpsInfo>>,
+    requests: &Receiver<GpsRequest>,
+    info_send: &Sender<Message>,
+) {
     // Set up and configure the NMEA parser.
     let mut nmea_parser = Nmea::create_for_navigation(&[
         SentenceType::GGA, SentenceType::GLL, SentenceType::GNS,
         SentenceType::VTG, SentenceType::RMC
     ]).unwrap();
 
-    let mut buffer = Vec::new();
+    let mut rate = GPS_DEFAULT_RATE;
+    if let Err(e) = configure_gps(&mut gps_port, rate) {
+        error!("Could not configure GPS: {e}");
+    }
+
+    let mut lines = NmeaLines::new();
+    let mut position_filter = PositionFilter::new();
+    let mut capture_deadline = None;
     let mut byte_buf = [0u8; 1];
 
     loop {
-        let bytes_read = gps_port.read(&mut byte_buf).unwrap_or_default();
-
-        if bytes_read == 0 {
-            continue;
+        // Only reconfigure between sentences, so a sentence in progress is
+        // not lost.
+        let request = match lines.is_empty().then(|| requests.try_recv()) {
+            Some(Ok(request)) => Some(request),
+            Some(Err(TryRecvError::Disconnected)) => return,
+            Some(Err(TryRecvError::Empty)) | None => None,
+        };
+        if let Some(request) = request {
+            let message = match request {
+                GpsRequest::Reconfigure => match configure_gps(&mut gps_port, rate) {
+                    Ok(()) => EventCode::GpsReconfigured.into(),
+                    Err(e) => {
+                        error!("GPS reconfiguration failed: {e}");
+                        format!("GPS reconfiguration failed: {e}").into()
+                    }
+                },
+                GpsRequest::CaptureFix => {
+                    capture_deadline = Some(std::time::Instant::now() + CAPTURE_FIX_TIMEOUT);
+                    continue;
+                }
+                GpsRequest::SetRate(new_rate) => match set_gps_rate(&mut gps_port, new_rate) {
+                    Ok(()) => {
+                        rate = new_rate;
+                        Event::with_arg(EventCode::GpsRateSet, rate).into()
+                    }
+                    Err(e) => {
+                        error!("Setting GPS rate failed: {e}");
+                        format!("Setting GPS rate failed: {e}").into()
+                    }
+                },
+            };
+            let _ = info_send.send(message);
         }
 
-        // NMEA messages must end with '\r\n'
-        if byte_buf[0] != b'\n' {
-            buffer.push(byte_buf[0]);
-            continue;
+        if capture_deadline.is_some_and(|deadline| std::time::Instant::now() > deadline) {
+            capture_deadline = None;
+            warn!("No good enough GPS fix to capture");
+            let _ = info_send.send(EventCode::FixCaptureTimedOut.into());
         }
 
-        // NMEA messages must start with '$'
-        if buffer[0] != b'$' {
-            buffer.clear();
+        let bytes_read = gps_port.read(&mut byte_buf).unwrap_or_default();
+
+        if bytes_read == 0 {
             continue;
         }
 
-        // Create a String from the buffer and clear the buffer
-        let new_string = String::from_utf8_lossy(&buffer).into_owned();
-        let new_string = new_string.trim_end();
-        buffer.clear();
+        let Some(new_string) = lines.push(byte_buf[0]) else {
+            continue;
+        };
 
         // info!("Got NMEA: {}", new_string);
 
         #[allow(clippy::single_match)]
-        match nmea_parser.parse_for_fix(new_string) {
+        match nmea_parser.parse_for_fix(&new_string) {
             Ok(_) => (),
             Err(_) => (),
         }
 
-        if let Some(lat) = nmea_parser.latitude
-            && let Some(lon) = nmea_parser.longitude
-            && let Some(alt) = nmea_parser.altitude
-        {
+        let satellites = nmea_parser.satellites().len() as u8;
+        let fix_valid = nmea_parser.fix_type.is_some_and(|fix| fix.is_valid());
+
+        // A fix too poor to downlink is treated the same as no fix at all
+        let position = nmea_parser.latitude
+            .zip(nmea_parser.longitude)
+            .zip(nmea_parser.altitude)
+            .filter(|_| GPS_DOWNLINK_REQUIREMENTS.is_met(fix_valid, satellites, nmea_parser.hdop));
+
+        // The altitude from GGA is already relative to mean sea level, so the
+        // geoid separation is passed along for reference, not applied.
+        //
+        // A fix which jumps impossibly far is dropped, leaving the last good
+        // position in place.
+        if let Some(((lat, lon), alt)) = position {
+            if !position_filter.accept(lat, lon, std::time::Instant::now()) {
+                debug!("Rejected implausible GPS fix {lat}, {lon}");
+                continue;
+            }
+
+            if capture_deadline.is_some()
+                && CAPTURE_FIX_REQUIREMENTS.is_met(fix_valid, satellites, nmea_parser.hdop)
+            {
+                capture_deadline = None;
+                let fix = CapturedFix {
+                    latitude: lat,
+                    longitude: lon,
+                    altitude: alt,
+                    satellites,
+                    hdop: nmea_parser.hdop,
+                    fix_type: nmea_parser.fix_type.map_or("-".to_string(), |fix| format!("{fix:?}")),
+                    utc: nmea_parser.fix_time.map(|time| time.format("%H:%M:%S%.3f").to_string()),
+                };
+                info!("Captured GPS fix: {fix:?}");
+                let _ = info_send.send(fix.position_message().into());
+                let _ = info_send.send(fix.quality_message().into());
+            }
+
             let _ = data.send(Some(GpsInfo {
                 latitude: lat,
                 longitude: lon,
                 altitude: alt,
-                satellites: nmea_parser.satellites().len() as u8
+                satellites,
+                hdop: nmea_parser.hdop,
+                geoid_separation: nmea_parser.geoid_separation,
+                // Derived by the receiver from full precision data
+                speed: nmea_parser.speed_over_ground.map(|knots| knots * KNOTS_TO_METERS_PER_SECOND),
+                course: nmea_parser.true_course,
             }));
+        } else {
+            data.send_if_modified(|fix| fix.take().is_some());
         }
     }
 }
@@ -391,82 +1632,1033 @@ async fn gps_secondary_loop() {
 
 }
 
+/// When the I²C sensor loops take their readings.
+#[derive(Debug, Clone, Copy)]
+enum SensorSchedule {
+    /// Read every [`SENSOR_POLL_INTERVAL`]
+    Continuous,
+    /// Read once per packet, just before it is sent
+    LowPower { send_start: Instant },
+}
+
+impl SensorSchedule {
+    fn is_low_power(self) -> bool {
+        matches!(self, SensorSchedule::LowPower { .. })
+    }
+
+    /// Create the interval at which to read a sensor which must be started
+    /// `lead` before a packet is sent for its reading to be ready in time.
+    fn interval(self, lead: Duration) -> time::Interval {
+        self.interval_every(lead, SENSOR_POLL_INTERVAL)
+    }
+
+    /// Like [`Self::interval`], but reading every `poll` rather than every
+    /// [`SENSOR_POLL_INTERVAL`] when not in low power mode.
+    fn interval_every(self, lead: Duration, poll: Duration) -> time::Interval {
+        let mut interval = match self {
+            SensorSchedule::Continuous => time::interval(poll),
+            SensorSchedule::LowPower { send_start } => {
+                let start = send_start.checked_sub(lead).unwrap_or(send_start);
+                time::interval_at(start, SEND_INTERVAL)
+            }
+        };
+
+        // Skipping keeps the ticks aligned with the packets after a delay
+        interval.set_missed_tick_behavior(time::MissedTickBehavior::Skip);
+        interval
+    }
+}
+
+/// Attempt an I²C read up to [`I2C_READ_ATTEMPTS`] times, so a transient
+/// NAK on a busy bus doesn't lose a reading.
+async fn retry_i2c<T, E>(mut read: impl FnMut() -> Result<T, E>) -> Result<T, E> {
+    let mut result = read();
+    for _ in 1..I2C_READ_ATTEMPTS {
+        if result.is_ok() {
+            break;
+        }
+
+        sleep(I2C_RETRY_DELAY).await;
+        result = read();
+    }
+
+    result
+}
+
+/// Free a device holding SDA low by clocking SCL until it lets go, then
+/// reopen the bus.
+///
+/// The bus is locked for the whole recovery, so no sensor task can use it
+/// part way through. The pins return to their I²C function once they are
+/// dropped, and the start condition of the next transfer resets every
+/// device on the bus.
+fn recover_i2c_bus(i2c: &Mutex<I2cdev>) {
+    let mut bus = i2c.lock().unwrap_or_else(PoisonError::into_inner);
+
+    let released = match Gpio::new() {
+        Ok(gpio) => match (gpio.get(I2C_SDA_PIN_NUM), gpio.get(I2C_SCL_PIN_NUM)) {
+            (Ok(sda), Ok(scl)) => {
+                let sda = sda.into_input();
+                let mut scl = scl.into_output_high();
+
+                for _ in 0..I2C_RECOVERY_CLOCKS {
+                    if sda.is_high() {
+                        break;
+                    }
+
+                    scl.set_low();
+                    std::thread::sleep(I2C_RECOVERY_HALF_PERIOD);
+                    scl.set_high();
+                    std::thread::sleep(I2C_RECOVERY_HALF_PERIOD);
+                }
+
+                sda.is_high()
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                error!("Could not take I²C pins to recover bus: {e}");
+                return;
+            }
+        },
+        Err(e) => {
+            error!("Could not initialize GPIO to recover I²C bus: {e}");
+            return;
+        }
+    };
+
+    if !released {
+        warn!("SDA is still held low after {I2C_RECOVERY_CLOCKS} clocks");
+    }
+
+    match I2cdev::new(I2C_BUS_PATH) {
+        Ok(reopened) => *bus = reopened,
+        Err(e) => error!("Could not reopen I²C bus: {e}"),
+    }
+}
+
+/// The closest BMP581 output data rate to `rate` hertz, without going over.
+fn bmp_rate(rate: u32) -> u32 {
+    match rate {
+        20.. => 20,
+        10.. => 10,
+        5.. => 5,
+        _ => 1,
+    }
+}
+
+/// The BMP581 output data rate register value for a rate given by
+/// [`bmp_rate`].
+fn bmp_odr(rate: u32) -> Odr {
+    match rate {
+        20 => Odr::Hz20_000,
+        10 => Odr::Hz10_000,
+        5 => Odr::Hz5_000,
+        _ => Odr::Hz1_000,
+    }
+}
+
 /// Function to read the BMP581 pressure and temp sensor.
+///
+/// In low power mode the sensor sits in standby, and is woken for a single
+/// forced conversion just before each packet is sent.
 #[instrument(skip_all)]
-async fn bmp_loop(data: watch::Sender<(Option<f64>, Option<f64>)>, i2c: MutexDevice<'_, I2cdev>) {
+async fn bmp_loop(
+    data: UnboundedSender<(f64, f64)>,
+    i2c: MutexDevice<'_, I2cdev>,
+    schedule: SensorSchedule,
+    mut rate: watch::Receiver<u32>,
+    info_send: Sender<Message>,
+) {
     let mut bmp = Bmp581::new_i2c(i2c, I2cAddr::Alternative);
     let mut delay = linux_embedded_hal::Delay;
-
-    if let Err(e) = bmp.init(&mut delay) {
-        error!("Could not initialize BMP581: {:?}", e);
-        return
+    let mut current_rate = bmp_rate(*rate.borrow_and_update());
+    let mut interval = schedule.interval_every(BMP_FORCED_LEAD, Duration::from_secs(1) / current_rate);
+    let power_mode = if schedule.is_low_power() {
+        PowerMode::Standby
+    } else {
+        PowerMode::Normal
     };
 
-    // Set up measurement settings
-    bmp.set_osr_config(bmp581::types::OsrConfig {
-        pressure_enable: true,
-        osr_pressure: Osr::Osr8,
-        osr_temperature: Osr::Osr1,
-    }).unwrap();
+    loop {
+        if let Err(e) = bmp.init(&mut delay) {
+            error!("Could not initialize BMP581: {:?}", e);
+            sleep(I2C_REINIT_DELAY).await;
+            continue;
+        };
 
-    // Set up output rate settings
-    bmp.set_odr_config(bmp581::types::OdrConfig {
-        deep_dis: DeepDis::Disabled,
-        odr: Odr::Hz20_000,
-        power_mode: PowerMode::Normal,
-    }).unwrap();
+        // Set up measurement settings
+        let osr_ok = bmp.set_osr_config(bmp581::types::OsrConfig {
+            pressure_enable: true,
+            osr_pressure: Osr::Osr8,
+            osr_temperature: Osr::Osr1,
+        }).is_ok();
+
+        // Set up output rate settings
+        let odr_ok = bmp.set_odr_config(bmp581::types::OdrConfig {
+            deep_dis: DeepDis::Disabled,
+            odr: bmp_odr(current_rate),
+            power_mode,
+        }).is_ok();
+
+        if !osr_ok || !odr_ok {
+            error!("Could not configure BMP581");
+            sleep(I2C_REINIT_DELAY).await;
+            continue;
+        }
 
-    loop {
-        sleep(Duration::from_millis(50)).await;
+        let mut failures = 0;
+        while failures < I2C_REINIT_FAILURES {
+            interval.tick().await;
+
+            // The rate can't be changed from the ground in low power mode,
+            // as the sensor is only read once per packet.
+            if rate.has_changed().unwrap_or(false) && !schedule.is_low_power() {
+                let new_rate = bmp_rate(*rate.borrow_and_update());
+                let set = retry_i2c(|| bmp.set_odr_config(bmp581::types::OdrConfig {
+                    deep_dis: DeepDis::Disabled,
+                    odr: bmp_odr(new_rate),
+                    power_mode,
+                })).await;
+
+                if set.is_err() {
+                    warn!("Could not change BMP581 output rate");
+                    let _ = info_send.send("Setting barometer rate failed".to_string().into());
+                    failures += 1;
+                    continue;
+                }
+
+                current_rate = new_rate;
+                interval = schedule.interval_every(BMP_FORCED_LEAD, Duration::from_secs(1) / current_rate);
+                let _ = info_send.send(Event::with_arg(EventCode::BaroRateSet, current_rate).into());
+            }
+
+            if schedule.is_low_power() {
+                let forced = retry_i2c(|| bmp.set_odr_config(bmp581::types::OdrConfig {
+                    deep_dis: DeepDis::Disabled,
+                    odr: bmp_odr(current_rate),
+                    power_mode: PowerMode::Forced,
+                })).await;
+
+                if forced.is_err() {
+                    failures += 1;
+                    continue;
+                }
+
+                sleep(BMP_CONVERSION_TIME).await;
+            }
+
+            let reading = retry_i2c(|| {
+                bmp.read_temperature()
+                    .and_then(|temp| bmp.read_pressure().map(|pres| (pres, temp)))
+            }).await;
 
-        if let Ok(temp) = bmp.read_temperature() && let Ok(pres) = bmp.read_pressure() {
-            let _ = data.send((Some(pres as f64), Some(temp as f64)));
+            if let Ok((pres, temp)) = reading {
+                failures = 0;
+                let _ = data.send((pres as f64, temp as f64));
+            } else {
+                failures += 1;
+            }
         }
+
+        warn!("BMP581 failed {failures} reads in a row, reinitializing");
     }
 }
 
+/// A reading of the BNO055.
+#[derive(Debug, Clone, Copy)]
+struct BnoReading {
+    orientation: mint::Quaternion<f32>,
+    /// The heading taken from the orientation, flagged as low confidence
+    /// while the magnetometer is uncalibrated
+    heading: Option<HeadingInfo>,
+}
+
 #[instrument(skip_all)]
-async fn bno055_loop(data: watch::Sender<Option<mint::Quaternion<f32>>>, i2c: MutexDevice<'_, I2cdev>) {
+async fn bno055_loop(
+    data: watch::Sender<Option<BnoReading>>,
+    i2c: MutexDevice<'_, I2cdev>,
+    schedule: SensorSchedule,
+) {
     let mut bno055 = bno055::Bno055::new(i2c).with_alternative_address();
     let mut delay = linux_embedded_hal::Delay;
-    if let Err(e) = bno055.init(&mut delay) {
-        error!("Could not initialize BNO055: {}", e);
-        return
-    };
-    bno055.set_mode(bno055::BNO055OperationMode::NDOF, &mut delay).unwrap();
-    bno055.set_power_mode(BNO055PowerMode::NORMAL).unwrap();
+    let mut interval = schedule.interval(SENSOR_READ_LEAD);
 
     loop {
-        sleep(Duration::from_millis(50)).await;
+        if let Err(e) = bno055.init(&mut delay) {
+            error!("Could not initialize BNO055: {}", e);
+            sleep(I2C_REINIT_DELAY).await;
+            continue;
+        };
+
+        // In low power mode the BNO055 sleeps its gyroscope and
+        // magnetometer while the accelerometer sees no motion
+        let power_mode = if schedule.is_low_power() {
+            BNO055PowerMode::LOW_POWER
+        } else {
+            BNO055PowerMode::NORMAL
+        };
+        let configured = bno055.set_mode(bno055::BNO055OperationMode::NDOF, &mut delay).is_ok()
+            && bno055.set_power_mode(power_mode).is_ok();
+
+        if !configured {
+            error!("Could not configure BNO055");
+            sleep(I2C_REINIT_DELAY).await;
+            continue;
+        }
 
-        if let Ok(quat) = bno055.quaternion() {
-            let _ = data.send(Some(quat));
+        let mut failures = 0;
+        while failures < I2C_REINIT_FAILURES {
+            interval.tick().await;
+
+            if let Ok(quat) = retry_i2c(|| bno055.quaternion()).await {
+                failures = 0;
+
+                let calibrated = bno055
+                    .get_calibration_status()
+                    .is_ok_and(|status| status.mag >= MIN_MAG_CALIBRATION);
+                let heading = heading::heading(quat, MAGNETIC_DECLINATION).map(|heading_deg| HeadingInfo {
+                    heading_deg,
+                    low_confidence: !calibrated,
+                });
+
+                let _ = data.send(Some(BnoReading {
+                    orientation: quat,
+                    heading,
+                }));
+            } else {
+                failures += 1;
+                let _ = data.send(None);
+            }
         }
+
+        warn!("BNO055 failed {failures} reads in a row, reinitializing");
     }
 }
 
+/// A reading of the HTS221.
+#[derive(Debug, Clone, Copy)]
+struct HtsReading {
+    /// Relative humidity in 1/2 %, after calibration
+    humidity_x2: u16,
+    /// The humidity ADC count the calibration was applied to
+    raw: i16,
+}
+
+/// Read the humidity from the HTS221, first starting a conversion and
+/// waiting for it if the sensor is in `one_shot` mode.
+///
+/// The ADC count is read directly so it can be downlinked as a raw value,
+/// and calibrated the same way the driver would.
+async fn read_hts221<D: I2c>(
+    hts221: &mut hts221::HTS221<Reverse<D>, D::Error>,
+    i2c: &mut Reverse<D>,
+    one_shot: bool,
+) -> Result<HtsReading, D::Error> {
+    if one_shot {
+        retry_i2c(|| {
+            let mut cr2 = hts221.cr2(i2c)?;
+            cr2.modify(&mut hts221.tie(i2c), |w| w.set_one_shot())
+        }).await?;
+        sleep(HTS_CONVERSION_TIME).await;
+    }
+
+    let raw = retry_i2c(|| hts221::device::HumidityOut::new(&mut hts221.tie(i2c))).await?.value();
+
+    Ok(HtsReading {
+        humidity_x2: hts221.convert_humidity_x2(raw),
+        raw,
+    })
+}
+
+/// Function to read the HTS221 humidity sensor.
+///
+/// In low power mode the sensor sits idle, and makes a single one-shot
+/// conversion just before each packet is sent.
 #[instrument(skip_all)]
-async fn hts221_loop(data: watch::Sender<Option<f64>>, i2c: MutexDevice<'_, I2cdev>) {
+async fn hts221_loop(
+    data: watch::Sender<Option<HtsReading>>,
+    i2c: MutexDevice<'_, I2cdev>,
+    schedule: SensorSchedule,
+) {
     let mut i2c = Reverse::new(i2c);
-    let mut hts221 = match hts221::Builder::new()
-        .with_update_mode(Block)
-        .with_data_rate(hts221::DataRate::Continuous1Hz)
-        .with_boot()
-        .build(&mut i2c)
-    {
-        Ok(hts) => hts,
-        Err(e) => {
-            error!("Could not initalize HTS221: {e}");
-            return
-        }
+    let (mut interval, data_rate) = if schedule.is_low_power() {
+        (schedule.interval(HTS_ONE_SHOT_LEAD), hts221::DataRate::OneShot)
+    } else {
+        (schedule.interval(SENSOR_READ_LEAD), hts221::DataRate::Continuous1Hz)
     };
-    
+
     loop {
-        sleep(Duration::from_millis(50)).await;
+        let mut hts221 = match hts221::Builder::new()
+            .with_update_mode(Block)
+            .with_data_rate(data_rate)
+            .with_boot()
+            .build(&mut i2c)
+        {
+            Ok(hts) => hts,
+            Err(e) => {
+                error!("Could not initalize HTS221: {e}");
+                sleep(I2C_REINIT_DELAY).await;
+                continue;
+            }
+        };
+
+        let mut failures = 0;
+        while failures < I2C_REINIT_FAILURES {
+            interval.tick().await;
+
+            if let Ok(reading) = read_hts221(&mut hts221, &mut i2c, schedule.is_low_power()).await {
+                failures = 0;
+                let _ = data.send(Some(reading));
+            } else {
+                failures += 1;
+                let _ = data.send(None);
+            }
+        }
+
+        warn!("HTS221 failed {failures} reads in a row, reinitializing");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arowss::{recording::segment_path, FrameScanner};
+    use embedded_hal::i2c::{ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation};
+
+    use super::*;
+    use crate::{commands::TRANSMIT_BLANK_DURATION, recorder::RecordingLimits};
+
+    /// An I²C device which doesn't acknowledge a number of transfers before
+    /// responding.
+    struct FlakyDevice {
+        naks: u32,
+    }
+
+    impl ErrorType for FlakyDevice {
+        type Error = ErrorKind;
+    }
+
+    impl I2c for FlakyDevice {
+        fn transaction(&mut self, _address: u8, operations: &mut [Operation<'_>]) -> Result<(), ErrorKind> {
+            if self.naks > 0 {
+                self.naks -= 1;
+                return Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+            }
+
+            for operation in operations {
+                if let Operation::Read(buf) = operation {
+                    buf.fill(0x42);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn transient_naks_are_retried() {
+        let mut device = FlakyDevice { naks: I2C_READ_ATTEMPTS - 1 };
+        let mut buf = [0; 2];
+
+        retry_i2c(|| device.read(0x76, &mut buf)).await.unwrap();
+        assert_eq!(buf, [0x42; 2]);
+    }
+
+    #[tokio::test]
+    async fn persistent_naks_fail_the_read() {
+        let mut device = FlakyDevice { naks: I2C_READ_ATTEMPTS };
+        let mut buf = [0; 2];
+
+        let result = retry_i2c(|| device.read(0x76, &mut buf)).await;
+        assert!(matches!(result, Err(ErrorKind::NoAcknowledge(_))));
+        assert_eq!(device.naks, 0);
+    }
+
+    /// The registers of an HTS221, which only converts a humidity reading
+    /// of `raw` when a one-shot conversion is started.
+    struct OneShotHts221 {
+        registers: [u8; 0x40],
+        raw: i16,
+        conversions: u32,
+    }
+
+    impl OneShotHts221 {
+        fn new(raw: i16) -> Self {
+            let mut registers = [0; 0x40];
+            // 20% at an ADC count of 0, 80% at 10000
+            registers[0x30] = 40;
+            registers[0x31] = 160;
+            registers[0x3A..0x3C].copy_from_slice(&10_000i16.to_le_bytes());
+
+            Self { registers, raw, conversions: 0 }
+        }
+    }
+
+    impl ErrorType for OneShotHts221 {
+        type Error = ErrorKind;
+    }
+
+    impl I2c for OneShotHts221 {
+        fn transaction(&mut self, _address: u8, operations: &mut [Operation<'_>]) -> Result<(), ErrorKind> {
+            // The first byte written selects the register, with the top bit
+            // set to auto-increment through the following ones
+            let mut register = 0;
+            for operation in operations {
+                match operation {
+                    Operation::Write([selected, values @ ..]) => {
+                        register = usize::from(*selected & 0x7F);
+                        for value in values {
+                            self.registers[register] = *value;
+                            register += 1;
+                        }
+                    }
+                    Operation::Write([]) => (),
+                    Operation::Read(buf) => {
+                        for byte in buf.iter_mut() {
+                            *byte = self.registers[register];
+                            register += 1;
+                        }
+                    }
+                }
+            }
+
+            // CTRL_REG2 bit 0 starts a conversion, and clears once done
+            if self.registers[0x21] & 1 != 0 {
+                self.registers[0x21] &= !1;
+                self.registers[0x28..0x2A].copy_from_slice(&self.raw.to_le_bytes());
+                self.conversions += 1;
+            }
 
-        if let Ok(humid) = hts221.humidity_x2(&mut i2c) {
-            let humidity_percent: f64 = (humid / 2) as f64;
-            let _ = data.send(Some(humidity_percent));
+            Ok(())
         }
     }
+
+    #[tokio::test]
+    async fn one_shot_humidity_read() {
+        let mut i2c = Reverse::new(OneShotHts221::new(5_000));
+        let mut hts221 = hts221::Builder::new()
+            .with_update_mode(Block)
+            .with_data_rate(hts221::DataRate::OneShot)
+            .build(&mut i2c)
+            .unwrap();
+
+        let reading = read_hts221(&mut hts221, &mut i2c, true).await.unwrap();
+        assert_eq!(reading.raw, 5_000);
+        assert_eq!(reading.humidity_x2, 100);
+        assert_eq!(i2c.inner().conversions, 1);
+    }
+
+    /// A UDP output which sends to nowhere.
+    async fn local_udp() -> UdpOutput {
+        UdpOutput {
+            socket: UdpSocket::bind("127.0.0.1:0").await.unwrap(),
+            targets: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn ground_decodes_what_the_air_sends() {
+        let packet = TelemetryPacket {
+            gps: Some(GpsInfo {
+                latitude: 40.820_123_4,
+                longitude: -96.705_654_3,
+                altitude: 1234.5,
+                satellites: 12,
+                hdop: Some(0.9),
+                geoid_separation: Some(-27.1),
+                speed: Some(152.4),
+                course: Some(271.5),
+            }),
+            pressure_altitude: Some(1230.25),
+            environmental_info: Some(EnvironmentalInfo {
+                pressure: 87_654.32,
+                pressure_range: None,
+                temperature: 12.5,
+                temperature_range: None,
+                humidity: 40.5,
+            }),
+            orientation_info: Some(mint::Quaternion { v: mint::Vector3 { x: 0.5, y: -0.5, z: 0.25 }, s: 0.625 }),
+            info: VecDeque::from(["Launch detected".to_string()]),
+            events: VecDeque::from([Event::with_arg(EventCode::Apogee, 3048)]),
+            flight_id: Some("1a2b3c4d".to_string()),
+            time: Some(1_760_000_000_000),
+            ..TelemetryPacket::default()
+        };
+
+        let prefix = std::env::temp_dir().join(format!("arowss_round_trip_{}", std::process::id()));
+        let prefix = prefix.to_str().unwrap().to_string();
+        let mut recorder = Recorder::open(prefix.clone(), RecordingLimits::default()).await;
+        let udp = local_udp().await;
+        let mut pacer = LinkPacer::new(RFD_BYTES_PER_SECOND, RFD_BURST);
+
+        // The air side writes into one end of a pipe standing in for the
+        // radios, and the ground reads the other end a few bytes at a time
+        let (mut air, mut ground) = std::os::unix::net::UnixStream::pair().unwrap();
+        let seqs = [254, 255, 0, 1];
+        for (i, seq) in seqs.into_iter().enumerate() {
+            let now = Instant::now().into_std() + RFD_BURST * i as u32;
+            write_data(seq, &packet, Some(&mut air), &mut pacer, &udp, &mut recorder, now).await;
+        }
+        drop(air);
+
+        let mut scanner = FrameScanner::new();
+        let mut received = Vec::new();
+        let mut chunk = [0; 7];
+        loop {
+            let read = std::io::Read::read(&mut ground, &mut chunk).unwrap();
+            if read == 0 {
+                break;
+            }
+            received.extend(scanner.push(&chunk[..read]));
+        }
+
+        assert_eq!(received.len(), seqs.len());
+        for (frame, seq) in received.into_iter().zip(seqs) {
+            let (decoded_seq, decoded) = frame.unwrap();
+            assert_eq!(decoded_seq, seq);
+            assert_eq!(decoded.vec_crc(), packet.vec_crc());
+        }
+
+        drop(recorder);
+        std::fs::remove_file(segment_path(&prefix, 0)).unwrap();
+    }
+
+    /// A serial port which accepts only a few bytes at a time, and is
+    /// interrupted between every write.
+    #[derive(Default)]
+    struct ChunkedPort {
+        written: Vec<u8>,
+        interrupted: bool,
+    }
+
+    impl Write for ChunkedPort {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.interrupted = !self.interrupted;
+            if self.interrupted {
+                return Err(std::io::ErrorKind::Interrupted.into());
+            }
+
+            let accepted = buf.len().min(3);
+            self.written.extend_from_slice(&buf[..accepted]);
+            Ok(accepted)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A GPS which outputs scripted bytes, optionally sends a request to the
+    /// GPS loop once some of them are read, and closes the request channel
+    /// at the end.
+    struct ScriptedGps {
+        output: VecDeque<u8>,
+        read: usize,
+        request: Option<(usize, GpsRequest)>,
+        requests: Option<Sender<GpsRequest>>,
+        written: Vec<u8>,
+        /// How many bytes had been read each time configuration began
+        configured_at: Vec<usize>,
+    }
+
+    impl ScriptedGps {
+        fn new(output: &str, request: Option<(usize, GpsRequest)>) -> (Self, Receiver<GpsRequest>) {
+            let (send, recv) = mpsc::channel();
+            let gps = Self {
+                output: output.bytes().collect(),
+                read: 0,
+                request,
+                requests: Some(send),
+                written: Vec::new(),
+                configured_at: Vec::new(),
+            };
+            (gps, recv)
+        }
+    }
+
+    impl std::io::Read for ScriptedGps {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.request.as_ref().is_some_and(|(at, _)| *at == self.read)
+                && let Some(((_, request), requests)) = self.request.take().zip(self.requests.as_ref())
+            {
+                requests.send(request).unwrap();
+            }
+
+            let Some(byte) = self.output.pop_front() else {
+                self.requests = None;
+                return Ok(0);
+            };
+            buf[0] = byte;
+            self.read += 1;
+            Ok(1)
+        }
+    }
+
+    impl Write for ScriptedGps {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if buf.starts_with(nmea_sentence(GPS_CONFIG_COMMANDS[0]).as_bytes()) {
+                self.configured_at.push(self.read);
+            }
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn gps_reconfigures_between_sentences() {
+        let sentence = nmea_sentence("GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,");
+        let (mut gps, requests) = ScriptedGps::new(&sentence, Some((10, GpsRequest::Reconfigure)));
+        let (data, _) = watch::channel(None);
+        let (info_send, info_recv) = mpsc::channel();
+
+        read_gps(&mut gps, &data, &requests, &info_send);
+
+        // Configured once on start, then again only after the sentence
+        // which was in progress when the request came in, as soon as its
+        // carriage return is read
+        assert_eq!(gps.configured_at, [0, sentence.len() - 1]);
+        let config = String::from_utf8(gps.written).unwrap();
+        for command in GPS_CONFIG_COMMANDS.iter().copied().chain(["PMTK220,1000"]) {
+            assert_eq!(config.matches(&nmea_sentence(command)).count(), 2, "{command}");
+        }
+        assert_eq!(info_recv.try_recv(), Ok(EventCode::GpsReconfigured.into()));
+    }
+
+    #[test]
+    fn gps_altitude_is_above_mean_sea_level() {
+        let output = [
+            "GPGSV,1,1,04,01,40,083,46,02,17,308,41,12,07,344,39,14,22,228,45",
+            "GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,",
+        ].map(nmea_sentence).concat();
+        let (mut gps, requests) = ScriptedGps::new(&output, None);
+        let (data, fix) = watch::channel(None);
+        let (info_send, _info_recv) = mpsc::channel();
+
+        read_gps(&mut gps, &data, &requests, &info_send);
+
+        // The GGA altitude is sent unchanged, with the separation alongside
+        // it for finding the height above the ellipsoid
+        let fix = fix.borrow().unwrap();
+        assert_eq!(fix.altitude, 545.4);
+        assert_eq!(fix.geoid_separation, Some(46.9));
+        assert!((fix.ellipsoidal_altitude().unwrap() - 592.3).abs() < 0.01);
+    }
+
+    /// A serial port whose writes can be read back by the test.
+    #[derive(Clone, Default)]
+    struct SharedPort(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedPort {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A GPS which never outputs anything, timing out like the real port.
+    struct SilentGps;
+
+    impl std::io::Read for SilentGps {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            std::thread::sleep(Duration::from_millis(5));
+            Ok(0)
+        }
+    }
+
+    impl Write for SilentGps {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn recovery_boot_starts_only_gps_and_beacons() {
+        let rfd = SharedPort::default();
+        let beacons = tokio::spawn(beacon_loop(rfd.clone(), Some(SilentGps), "F1".to_string(), SystemClock));
+        sleep(Duration::from_millis(100)).await;
+
+        // The beacon task and the GPS task it started, and nothing else
+        assert_eq!(tokio::runtime::Handle::current().metrics().num_alive_tasks(), 2);
+        let received = FrameScanner::new().push(&rfd.0.lock().unwrap());
+        let [Ok((0, beacon)), ..] = &received[..] else {
+            panic!("expected a beacon, got {received:?}");
+        };
+        assert!(beacon.safe_mode);
+        assert_eq!(beacon.flight_id.as_deref(), Some("F1"));
+
+        beacons.abort();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn recovery_boot_beacons_without_gps() {
+        let rfd = SharedPort::default();
+        let beacons = tokio::spawn(beacon_loop(rfd.clone(), None::<SilentGps>, "F1".to_string(), SystemClock));
+        sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(tokio::runtime::Handle::current().metrics().num_alive_tasks(), 1);
+        let received = FrameScanner::new().push(&rfd.0.lock().unwrap());
+        assert!(matches!(&received[..], [Ok((0, beacon)), ..] if beacon.gps.is_none()));
+
+        beacons.abort();
+    }
+
+    #[tokio::test]
+    async fn short_writes_send_whole_frames() {
+        let prefix = std::env::temp_dir().join(format!("arowss_short_writes_{}", std::process::id()));
+        let prefix = prefix.to_str().unwrap().to_string();
+        let mut recorder = Recorder::open(prefix.clone(), RecordingLimits::default()).await;
+        let udp = local_udp().await;
+        let mut pacer = LinkPacer::new(RFD_BYTES_PER_SECOND, RFD_BURST);
+        let mut port = ChunkedPort::default();
+
+        let mut frames = Vec::new();
+        for seq in 0..4 {
+            let packet = TelemetryPacket {
+                info: VecDeque::from([format!("Frame {seq}")]),
+                ..TelemetryPacket::default()
+            };
+            let now = Instant::now().into_std() + RFD_BURST * u32::from(seq);
+            frames.extend(write_data(seq, &packet, Some(&mut port), &mut pacer, &udp, &mut recorder, now).await);
+        }
+
+        // Every frame went out whole, one after the other
+        assert_eq!(port.written, frames);
+        let received = FrameScanner::new().push(&port.written);
+        assert_eq!(received.len(), 4);
+        for (frame, seq) in received.into_iter().zip(0..) {
+            let (decoded_seq, decoded) = frame.unwrap();
+            assert_eq!(decoded_seq, seq);
+            assert_eq!(decoded.info, [format!("Frame {seq}")]);
+        }
+
+        drop(recorder);
+        std::fs::remove_file(segment_path(&prefix, 0)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn every_udp_target_receives_the_frame() {
+        let prefix = std::env::temp_dir().join(format!("arowss_udp_targets_{}", std::process::id()));
+        let prefix = prefix.to_str().unwrap().to_string();
+        let mut recorder = Recorder::open(prefix.clone(), RecordingLimits::default()).await;
+        let mut pacer = LinkPacer::new(RFD_BYTES_PER_SECOND, RFD_BURST);
+
+        let mut receivers = Vec::new();
+        for _ in 0..3 {
+            receivers.push(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        }
+
+        // An IPv6 target can't be reached from an IPv4 socket, so sending to
+        // it fails straight away
+        let mut udp = local_udp().await;
+        udp.targets.push("[::1]:3939".parse().unwrap());
+        for receiver in &receivers {
+            udp.targets.push(receiver.local_addr().unwrap());
+        }
+
+        let packet = TelemetryPacket::default();
+        let frame = write_data(7, &packet, None::<&mut Vec<u8>>, &mut pacer, &udp, &mut recorder, Instant::now().into_std()).await;
+
+        for receiver in &receivers {
+            let mut buf = [0; 1500];
+            let len = tokio::time::timeout(Duration::from_secs(1), receiver.recv(&mut buf)).await.unwrap().unwrap();
+            assert_eq!(&buf[..len], frame);
+        }
+
+        drop(recorder);
+        std::fs::remove_file(segment_path(&prefix, 0)).unwrap();
+    }
+
+    #[test]
+    fn long_info_is_truncated_on_a_char_boundary() {
+        let mut info = InfoQueue::new(4, 4, 1_000);
+        push_info(&mut info, "short".to_string());
+        // The odd prefix puts the cut in the middle of a two byte character
+        push_info(&mut info, format!("a{}", "é".repeat(100)));
+
+        let sent = info.next_frame();
+        assert_eq!(sent[0], "short");
+        let truncated = &sent[1];
+        assert!(truncated.len() <= MAX_INFO_BYTES);
+        assert_eq!(truncated.len(), MAX_INFO_BYTES - 1);
+        let kept = truncated.strip_suffix(INFO_TRUNCATION_MARKER).unwrap();
+        assert_eq!(kept, format!("a{}", "é".repeat(40)));
+    }
+
+    #[tokio::test]
+    async fn blanking_stops_frames_but_not_recording() {
+        let prefix = std::env::temp_dir().join(format!("arowss_blanking_{}", std::process::id()));
+        let prefix = prefix.to_str().unwrap().to_string();
+        let mut recorder = Recorder::open(prefix.clone(), RecordingLimits::default()).await;
+        let udp = local_udp().await;
+        let mut pacer = LinkPacer::new(RFD_BYTES_PER_SECOND, RFD_BURST);
+        let mut rfd_send = Vec::new();
+        let packet = TelemetryPacket::default();
+
+        let start = Instant::now();
+        let (blank_send, blank_recv) = watch::channel(None);
+        blank_send.send(Some(start + TRANSMIT_BLANK_DURATION)).unwrap();
+
+        for tick in 0..4 {
+            let now = start + SEND_INTERVAL * tick;
+            let blanked = is_blanked(&blank_recv, now);
+            assert!(blanked);
+            let rfd = (!blanked).then_some(&mut rfd_send);
+            write_data(tick as u8, &packet, rfd, &mut pacer, &udp, &mut recorder, now.into_std()).await;
+        }
+        assert!(rfd_send.is_empty());
+
+        // Frames flow again once the window is over
+        let now = start + TRANSMIT_BLANK_DURATION;
+        assert!(!is_blanked(&blank_recv, now));
+        write_data(4, &packet, Some(&mut rfd_send), &mut pacer, &udp, &mut recorder, now.into_std()).await;
+        assert!(rfd_send.starts_with(&SYNC_WORD));
+
+        let recording = std::fs::read_to_string(segment_path(&prefix, 0)).unwrap();
+        assert_eq!(recording.lines().count(), 5);
+        std::fs::remove_file(segment_path(&prefix, 0)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn scenario_packet_arrives_unchanged() {
+        let packet = TelemetryPacket {
+            pressure_altitude: Some(1_234.5),
+            info: VecDeque::from(["scripted".to_string()]),
+            time: Some(1_760_000_000_000),
+            ..TelemetryPacket::default()
+        };
+        let line = serde_json::to_string(&packet).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("arowss_scenario_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("scenario.jsonl");
+        std::fs::write(&path, format!("{line}\n\n")).unwrap();
+        let mut scenario = load_scenario(&path).await;
+        assert_eq!(scenario.len(), 1);
+
+        let mut recorder = Recorder::open(dir.join("telemetry").to_str().unwrap().to_string(), RecordingLimits::default()).await;
+        let udp = local_udp().await;
+        let mut pacer = LinkPacer::new(RFD_BYTES_PER_SECOND, RFD_BURST);
+        let mut rfd_send = Vec::new();
+        let scripted = scenario.pop_front().unwrap();
+        let now = Instant::now().into_std();
+        let frame = write_data(9, &scripted, Some(&mut rfd_send), &mut pacer, &udp, &mut recorder, now).await;
+        assert_eq!(rfd_send, frame);
+
+        // The ground decodes exactly the packet in the scenario
+        let received = FrameScanner::new().push(&rfd_send);
+        let [Ok((9, decoded))] = &received[..] else {
+            panic!("expected one frame, got {received:?}");
+        };
+        assert_eq!(serde_json::to_string(decoded).unwrap(), line);
+        assert_eq!(encode_frame(9, decoded), rfd_send);
+
+        drop(recorder);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn config_summary_has_expected_fields() {
+        let summary = config_entries(&Args::default()).join("; ");
+        for expected in [RFD_PATH, GPS_PATH, UDP_PORT, "send interval 250ms", LOG_DIR, "raw false"] {
+            assert!(summary.contains(expected), "{expected:?} missing from {summary:?}");
+        }
+    }
+
+    #[test]
+    fn config_summary_reflects_loaded_values() {
+        let args = Args {
+            raw_values: true,
+            failsafe: Vec::new(),
+            log_dir: Some(PathBuf::from("/tmp/arowss_logs")),
+            ..Args::default()
+        };
+
+        let summary = config_entries(&args).join("; ");
+        for expected in ["raw true", "failsafe []", "logs /tmp/arowss_logs"] {
+            assert!(summary.contains(expected), "{expected:?} missing from {summary:?}");
+        }
+    }
+
+    #[test]
+    fn identity_frame_decodes() {
+        let args = Args {
+            raw_values: true,
+            ..Args::default()
+        };
+        let packet = TelemetryPacket {
+            flight_id: Some("1a2b3c4d".to_string()),
+            identity: Some(identity(&args)),
+            ..TelemetryPacket::default()
+        };
+
+        let (_, decoded) = arowss::decode_frame(&encode_frame(0, &packet)).unwrap();
+        assert_eq!(decoded.flight_id.as_deref(), Some("1a2b3c4d"));
+
+        let identity = decoded.identity.unwrap();
+        assert_eq!(identity.protocol, SchemaVersion::CURRENT);
+        assert_eq!(identity.firmware, format!("{}+{}", env!("CARGO_PKG_VERSION"), env!("AROWSS_GIT_HASH")));
+        assert_eq!(identity.subsystems, ["gps", "bmp581", "bno055", "hts221", "raw"]);
+    }
+
+    /// Move the paused clock forward a millisecond at a time, letting every
+    /// task woken along the way run.
+    async fn advance_millis(millis: u64) {
+        for _ in 0..millis {
+            time::advance(Duration::from_millis(1)).await;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn buzzer_pulses_then_times_out() {
+        let pattern = BuzzerPattern {
+            on: Duration::from_millis(20),
+            off: Duration::from_millis(30),
+            timeout: Duration::from_millis(125),
+        };
+        let (enable_send, enable_recv) = watch::channel(false);
+        let (info_send, info_recv) = mpsc::channel();
+        let levels = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = levels.clone();
+        let set_pin = move |high| recorded.lock().unwrap().push(high);
+        tokio::spawn(buzzer_loop(set_pin, pattern, enable_recv, info_send));
+
+        enable_send.send(true).unwrap();
+
+        // Low while waiting, then on for the first pulse
+        advance_millis(10).await;
+        assert_eq!(*levels.lock().unwrap(), [false, true]);
+        advance_millis(20).await;
+        assert_eq!(*levels.lock().unwrap(), [false, true, false]);
+        assert_eq!(info_recv.try_iter().count(), 0);
+
+        // Three pulses of on and off before the timeout, ending low
+        advance_millis(270).await;
+        assert_eq!(*levels.lock().unwrap(), [false, true, false, true, false, true, false, false]);
+        assert_eq!(info_recv.try_iter().collect::<Vec<_>>(), [EventCode::BuzzerTimedOut.into()]);
+    }
+
+    #[test]
+    fn raw_values_only_when_enabled() {
+        let bmp = Some((101_325.0, 21.5));
+        let hts = Some(HtsReading { humidity_x2: 71, raw: -4_321 });
+
+        let raw = raw_sensor_values(true, bmp, hts).unwrap();
+        assert_eq!(raw.bmp_pressure, Some(6_484_800));
+        assert_eq!(raw.bmp_temperature, Some(1_409_024));
+        assert_eq!(raw.hts_humidity, Some(-4_321));
+        let packet = TelemetryPacket { raw: Some(raw), ..TelemetryPacket::default() };
+        assert!(serde_json::to_string(&packet).unwrap().contains("\"hts_h\":-4321"));
+
+        assert_eq!(raw_sensor_values(false, bmp, hts), None);
+    }
 }
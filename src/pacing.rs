@@ -0,0 +1,176 @@
+//! Pacing of frames onto a link, so frames are never produced faster than
+//! the link can carry them.
+
+use std::time::{Duration, Instant};
+
+/// A token bucket limiting the bytes sent on a link to its throughput.
+///
+/// If frames are produced faster than the link can carry them, the radio
+/// buffers them and latency grows without bound. Instead, frames which
+/// would exceed the budget are dropped, so the frames which are sent are
+/// always fresh.
+#[derive(Debug, Clone)]
+pub struct LinkPacer {
+    bytes_per_second: f64,
+    capacity: f64,
+    available: f64,
+    last: Option<Instant>,
+}
+
+impl LinkPacer {
+    /// Create a pacer for a link carrying `bytes_per_second`, which allows
+    /// bursts of up to `burst` worth of data.
+    pub fn new(bytes_per_second: f64, burst: Duration) -> Self {
+        let capacity = bytes_per_second * burst.as_secs_f64();
+
+        Self {
+            bytes_per_second,
+            capacity,
+            available: capacity,
+            last: None,
+        }
+    }
+
+    /// Check whether a frame of `bytes` can be sent at `now`, taking it from
+    /// the budget if so.
+    ///
+    /// A frame larger than the whole burst is sent once the budget is full,
+    /// and paid back before anything else is sent.
+    pub fn try_send(&mut self, bytes: usize, now: Instant) -> bool {
+        self.refill(now);
+
+        let bytes = bytes as f64;
+        if self.available < bytes.min(self.capacity) {
+            return false;
+        }
+
+        self.available -= bytes;
+        true
+    }
+
+    /// Check whether a frame of `bytes` can be sent at `now` while leaving
+    /// at least `reserve` bytes of the budget, taking it from the budget if
+    /// so.
+    ///
+    /// This is for frames which must never delay the ones after them, so
+    /// unlike [`Self::try_send`], a frame is never sent on credit.
+    pub fn try_send_spare(&mut self, bytes: usize, reserve: usize, now: Instant) -> bool {
+        self.refill(now);
+
+        let bytes = bytes as f64;
+        if self.available - bytes < reserve as f64 {
+            return false;
+        }
+
+        self.available -= bytes;
+        true
+    }
+
+    /// Add the budget built up since the last frame.
+    fn refill(&mut self, now: Instant) {
+        if let Some(last) = self.last {
+            let elapsed = now.saturating_duration_since(last).as_secs_f64();
+            self.available = (self.available + elapsed * self.bytes_per_second).min(self.capacity);
+        }
+        self.last = Some(now);
+    }
+}
+
+/// The throughput of a radio link available to the downlink, after the
+/// radio's own framing overhead and any bandwidth kept free for the uplink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkBudget {
+    /// Baud rate of the serial connection to the radio, at 10 bits per byte
+    /// for 8N1 framing
+    pub baud: u32,
+    /// The number of telemetry packets sent per second
+    pub packets_per_second: u32,
+    /// The percentage of the link kept free for commands from the ground, as
+    /// the uplink shares the same half-duplex channel
+    pub reserved_uplink_percent: u32,
+    /// The most data the radio sends in a single over the air packet
+    pub air_packet_payload: u32,
+    /// The bytes of preamble, sync word and header the radio adds to each
+    /// over the air packet
+    pub air_packet_overhead: u32,
+}
+
+impl LinkBudget {
+    /// The budget of an RFD-900x with the SiK firmware, which sends up to
+    /// 252 bytes per over the air packet with roughly 8 bytes of overhead.
+    pub const fn rfd900x(baud: u32, packets_per_second: u32, reserved_uplink_percent: u32) -> Self {
+        Self {
+            baud,
+            packets_per_second,
+            reserved_uplink_percent,
+            air_packet_payload: 252,
+            air_packet_overhead: 8,
+        }
+    }
+
+    /// The bytes per second available to the downlink.
+    pub const fn downlink_bytes_per_second(&self) -> u32 {
+        let serial = self.baud / 10;
+        let shared = serial * (100 - self.reserved_uplink_percent) / 100;
+
+        shared * self.air_packet_payload / (self.air_packet_payload + self.air_packet_overhead)
+    }
+
+    /// The bytes each telemetry packet can use without the link falling
+    /// behind.
+    pub const fn bytes_per_packet(&self) -> usize {
+        (self.downlink_bytes_per_second() / self.packets_per_second) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spare_frames_leave_the_reserve() {
+        let start = Instant::now();
+        let mut pacer = LinkPacer::new(1000.0, Duration::from_secs(1));
+
+        assert!(pacer.try_send(400, start));
+        assert!(!pacer.try_send_spare(300, 400, start));
+        assert!(pacer.try_send_spare(200, 400, start));
+
+        // The reserve is still there for the next live frame
+        assert!(pacer.try_send(400, start));
+        assert!(!pacer.try_send(1, start));
+    }
+
+    #[test]
+    fn impossible_rate_is_paced_to_the_budget() {
+        let start = Instant::now();
+        let mut pacer = LinkPacer::new(1000.0, Duration::from_millis(500));
+
+        // 200 byte frames at 100 per second is 20 times what the link carries
+        let mut sent = 0;
+        for tick in 0..1000 {
+            if pacer.try_send(200, start + Duration::from_millis(10 * tick)) {
+                sent += 200;
+            }
+        }
+
+        // Over 10 seconds, at most the burst plus 1000 bytes per second is
+        // sent, and the link is still kept busy
+        assert!(sent <= 500 + 10 * 1000, "{sent} bytes sent");
+        assert!(sent >= 10 * 1000 - 200, "{sent} bytes sent");
+    }
+
+    #[test]
+    fn budget_matches_hand_calculation() {
+        // 57600 baud is 5760 bytes/s, 90% of which is 5184 bytes/s, and
+        // 252 of every 260 bytes sent over the air are data: 5024 bytes/s
+        let budget = LinkBudget::rfd900x(57_600, 4, 10);
+        assert_eq!(budget.downlink_bytes_per_second(), 5024);
+        assert_eq!(budget.bytes_per_packet(), 1256);
+
+        // 19200 baud is 1920 bytes/s, all for the downlink: 1860 bytes/s
+        let budget = LinkBudget::rfd900x(19_200, 2, 0);
+        assert_eq!(budget.downlink_bytes_per_second(), 1860);
+        assert_eq!(budget.bytes_per_packet(), 930);
+    }
+}
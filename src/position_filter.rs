@@ -0,0 +1,95 @@
+//! Rejection of GPS fixes which jump impossibly far from the previous one,
+//! such as from multipath or a momentary bad fix.
+
+use std::time::{Duration, Instant};
+
+use crate::coordinates::ground_track;
+
+/// Fixes implying a ground speed above this many meters per second are
+/// rejected. This is well above anything the payload can reach.
+const MAX_PLAUSIBLE_SPEED: f64 = 500.0;
+/// After this long without an accepted fix, the next fix is always
+/// accepted, as the payload may really have moved that far.
+const MAX_GAP: Duration = Duration::from_secs(10);
+/// After this many rejections in a row, the next fix is accepted anyway,
+/// in case the last accepted fix was the bad one.
+const MAX_CONSECUTIVE_REJECTIONS: u32 = 5;
+
+/// Decides whether each GPS fix is plausible given the last accepted one.
+#[derive(Debug, Clone, Default)]
+pub struct PositionFilter {
+    last: Option<(f64, f64, Instant)>,
+    last_rejected: Option<(f64, f64)>,
+    rejections: u32,
+}
+
+impl PositionFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check a fix taken at `time`, returning whether it should be used.
+    /// Accepted fixes become the reference for the next one.
+    ///
+    /// Checking the same rejected position again, such as when it is
+    /// repeated by several sentences, only counts as one rejection.
+    pub fn accept(&mut self, latitude: f64, longitude: f64, time: Instant) -> bool {
+        if let Some((last_latitude, last_longitude, last_time)) = self.last {
+            let elapsed = time.saturating_duration_since(last_time);
+
+            let speed = ground_track(
+                (last_latitude, last_longitude),
+                (latitude, longitude),
+                elapsed.as_secs_f64(),
+            )
+            .map(|(speed, _)| speed);
+
+            // A fix at the same instant as the last is only plausible if it
+            // hasn't moved
+            let implausible = match speed {
+                Some(speed) => speed > MAX_PLAUSIBLE_SPEED,
+                None => (latitude, longitude) != (last_latitude, last_longitude),
+            };
+
+            if implausible
+                && elapsed < MAX_GAP
+                && self.rejections < MAX_CONSECUTIVE_REJECTIONS
+            {
+                if self.last_rejected != Some((latitude, longitude)) {
+                    self.last_rejected = Some((latitude, longitude));
+                    self.rejections += 1;
+                }
+                return false;
+            }
+        }
+
+        self.last = Some((latitude, longitude, time));
+        self.last_rejected = None;
+        self.rejections = 0;
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn teleport_spike_is_rejected() {
+        let start = Instant::now();
+        let mut filter = PositionFilter::new();
+
+        // Walking north about 11m per second, with one fix 1° away
+        let track = [(40.0, -96.7), (40.0001, -96.7), (41.0, -96.7), (40.0003, -96.7), (40.0004, -96.7)];
+        let accepted: Vec<_> = track
+            .iter()
+            .enumerate()
+            .map(|(i, &(lat, lon))| filter.accept(lat, lon, start + Duration::from_secs(i as u64)))
+            .collect();
+        assert_eq!(accepted, [true, true, false, true, true]);
+
+        // A large jump after a long gap may be real
+        assert!(filter.accept(41.0, -96.7, start + Duration::from_secs(4) + MAX_GAP));
+    }
+}
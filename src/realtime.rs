@@ -0,0 +1,108 @@
+use std::io;
+
+use tokio::{runtime::Handle, task::JoinHandle};
+use tracing::{info, warn};
+
+/// The CPU the telemetry sending task runs on.
+pub const SEND_TASK_CPU: usize = 3;
+/// The CPU the command receiving task runs on.
+pub const COMMAND_TASK_CPU: usize = 2;
+/// The nice value of prioritized tasks. Lower is higher priority, and
+/// anything below zero requires `CAP_SYS_NICE`.
+const PRIORITIZED_NICE: i32 = -10;
+
+/// Run a task on its own thread, pinned to `cpu` and with a raised
+/// priority, so it isn't delayed by other work on a loaded system.
+///
+/// Tasks spawned by this task still run on the shared runtime. If the
+/// affinity or priority can't be set, a warning is logged and the task runs
+/// without it.
+pub fn spawn_prioritized<F>(name: &'static str, cpu: usize, task: F) -> JoinHandle<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let handle = Handle::current();
+
+    tokio::task::spawn_blocking(move || {
+        match pin_to_cpu(cpu) {
+            Ok(()) => info!("Pinned {name} task to CPU {cpu}"),
+            Err(e) => warn!("Could not pin {name} task to CPU {cpu}: {e}"),
+        }
+
+        match set_nice(PRIORITIZED_NICE) {
+            Ok(()) => info!("Set {name} task to nice {PRIORITIZED_NICE}"),
+            Err(e) => warn!("Could not raise priority of {name} task: {e}"),
+        }
+
+        handle.block_on(task);
+    })
+}
+
+/// Pin the current thread to a single CPU.
+fn pin_to_cpu(cpu: usize) -> io::Result<()> {
+    // SAFETY: `cpu_set_t` is plain data which is valid when zeroed, and is
+    // only passed by reference with its correct size.
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), &set)
+    };
+
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Set the nice value of the current thread.
+fn set_nice(nice: i32) -> io::Result<()> {
+    // SAFETY: These calls have no memory safety requirements. On Linux, a
+    // thread ID given as a process ID only affects that thread.
+    let result = unsafe { libc::setpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t, nice) };
+
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The CPUs the current thread may run on.
+    fn affinity() -> Vec<usize> {
+        // SAFETY: As in `pin_to_cpu`, and the set is only read after
+        // `sched_getaffinity` reports that it has filled it in.
+        unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            assert_eq!(libc::sched_getaffinity(0, size_of::<libc::cpu_set_t>(), &mut set), 0);
+            (0..libc::CPU_SETSIZE as usize).filter(|cpu| libc::CPU_ISSET(*cpu, &set)).collect()
+        }
+    }
+
+    /// The nice value of the current thread.
+    fn nice() -> i32 {
+        // SAFETY: As in `set_nice`.
+        unsafe { libc::getpriority(libc::PRIO_PROCESS, libc::gettid() as libc::id_t) }
+    }
+
+    #[tokio::test]
+    async fn settings_are_applied_or_skipped() {
+        let (unpinned, normal) = (affinity(), nice());
+
+        let (send, recv) = tokio::sync::oneshot::channel();
+        spawn_prioritized("test", 0, async move {
+            let _ = send.send((affinity(), nice()));
+        })
+        .await
+        .unwrap();
+
+        // Without the privileges the task still runs, just as before
+        let (cpus, priority) = recv.await.unwrap();
+        assert!(cpus == [0] || cpus == unpinned, "{cpus:?}");
+        assert!(priority == PRIORITIZED_NICE || priority == normal, "{priority}");
+    }
+}
@@ -0,0 +1,288 @@
+use std::path::PathBuf;
+
+use arowss::recording::segment_path;
+use tokio::{fs::File, io::AsyncWriteExt as _};
+use tracing::{info, warn};
+
+/// The number of segments a recording is split into by
+/// [`RecordingLimits::from_total`]. More segments lose less of the oldest
+/// data kept each time one is deleted.
+const SEGMENTS: u64 = 16;
+
+/// How much of a recording is kept, bounding the space used on the SD card
+/// during a long hold on the pad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordingLimits {
+    /// A new segment is started once the current one reaches this size
+    pub segment_bytes: u64,
+    /// Only this many of the most recent segments are kept
+    pub segments: u64,
+}
+
+impl RecordingLimits {
+    /// Keep about `total_bytes` of the most recent records.
+    pub fn from_total(total_bytes: u64) -> Self {
+        Self {
+            segment_bytes: (total_bytes / SEGMENTS).max(1),
+            segments: SEGMENTS,
+        }
+    }
+
+    /// The most bytes kept across every segment.
+    pub fn total_bytes(&self) -> u64 {
+        self.segment_bytes * self.segments
+    }
+}
+
+impl Default for RecordingLimits {
+    fn default() -> Self {
+        Self::from_total(256 * 1024 * 1024)
+    }
+}
+
+/// Records telemetry locally, rotating through a limited number of segment
+/// files so the most recent data is always kept.
+pub struct Recorder {
+    prefix: String,
+    limits: RecordingLimits,
+    file: Option<File>,
+    index: u64,
+    written: u64,
+    enabled: bool,
+}
+
+impl Recorder {
+    /// Start a recording named `prefix`, keeping only as much of it as
+    /// `limits` allows.
+    ///
+    /// If a segment can't be opened, recording is skipped until the next
+    /// segment is started.
+    pub async fn open(prefix: String, limits: RecordingLimits) -> Self {
+        let file = open_segment(&prefix, 0).await;
+
+        Self {
+            prefix,
+            limits,
+            file,
+            index: 0,
+            written: 0,
+            enabled: true,
+        }
+    }
+
+    /// Start or stop recording, to spare the SD card during long ground
+    /// testing.
+    ///
+    /// Stopping closes the current segment once everything written to it
+    /// has reached the card. Starting again begins a new segment.
+    pub async fn set_enabled(&mut self, enabled: bool) {
+        if enabled == self.enabled {
+            return;
+        }
+        self.enabled = enabled;
+
+        if enabled {
+            info!("Recording resumed");
+            self.rotate().await;
+        } else {
+            info!("Recording stopped");
+            self.close().await;
+        }
+    }
+
+    /// Close the current segment and start a new one, to mark a boundary
+    /// such as between ground testing and flight.
+    ///
+    /// Records are only written between calls, so every record before this
+    /// is in the old segment, which has reached the card, and every record
+    /// after it is in the new one. Returns the path of the new segment and
+    /// the bytes written to the old one, or `None` if recording is stopped or
+    /// the new segment couldn't be opened.
+    pub async fn restart(&mut self) -> Option<(PathBuf, u64)> {
+        if !self.enabled {
+            return None;
+        }
+
+        let written = self.written;
+        self.close().await;
+        self.rotate().await;
+        self.file.as_ref()?;
+
+        Some((segment_path(&self.prefix, self.index), written))
+    }
+
+    /// Whether records are currently reaching a file.
+    pub fn is_recording(&self) -> bool {
+        self.enabled && self.file.is_some()
+    }
+
+    /// Write a single record, followed by a newline.
+    pub async fn write(&mut self, record: &[u8]) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.written >= self.limits.segment_bytes {
+            self.rotate().await;
+        }
+
+        if let Some(file) = self.file.as_mut() {
+            let _ = file.write_all(record).await;
+            let _ = file.write_all(b"\n").await;
+            let _ = file.flush().await;
+        }
+
+        self.written += record.len() as u64 + 1;
+    }
+
+    /// Close the current segment once everything written to it has reached
+    /// the card.
+    async fn close(&mut self) {
+        if let Some(mut file) = self.file.take() {
+            let _ = file.flush().await;
+            if let Err(e) = file.sync_all().await {
+                warn!("Could not sync recording segment: {e}");
+            }
+        }
+    }
+
+    /// Start the next segment, deleting the oldest one if there are too
+    /// many.
+    async fn rotate(&mut self) {
+        self.index += 1;
+        self.written = 0;
+        self.file = open_segment(&self.prefix, self.index).await;
+
+        if let Some(oldest) = self.index.checked_sub(self.limits.segments.max(1)) {
+            let path = segment_path(&self.prefix, oldest);
+            if let Err(e) = tokio::fs::remove_file(&path).await {
+                warn!("Could not delete old recording segment {}: {e}", path.display());
+            }
+        }
+    }
+}
+
+async fn open_segment(prefix: &str, index: u64) -> Option<File> {
+    let path = segment_path(prefix, index);
+
+    match tokio::fs::OpenOptions::new().create(true).append(true).open(&path).await {
+        Ok(file) => {
+            info!("Recording telemetry to {}", path.display());
+            Some(file)
+        }
+        Err(e) => {
+            warn!("Could not open recording segment {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arowss::{encode_frame, frame::SYNC_WORD, recording::{read_records, segments}, TelemetryPacket};
+
+    use super::*;
+
+    /// A record as the sending loop writes it, for the frame numbered `seq`.
+    fn record(seq: u8) -> Vec<u8> {
+        let packet = TelemetryPacket { time: Some(seq.into()), ..TelemetryPacket::default() };
+        let frame = encode_frame(seq, &packet);
+
+        frame[SYNC_WORD.len()..frame.len() - 1].to_vec()
+    }
+
+    /// The sequence numbers of every record in the recording.
+    fn recorded(prefix: &str) -> Vec<Option<u8>> {
+        read_records(prefix).unwrap().into_iter().map(|(seq, _)| seq).collect()
+    }
+
+    /// A recording prefix in the temporary directory, unique to the test.
+    fn temp_prefix(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("arowss_recorder_{name}_{}", std::process::id()));
+        path.to_str().unwrap().to_string()
+    }
+
+    fn remove_recording(prefix: &str) {
+        for (_, path) in segments(prefix).unwrap() {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn disabled_recorder_writes_nothing() {
+        let prefix = temp_prefix("toggle");
+        let mut recorder = Recorder::open(prefix.clone(), RecordingLimits::default()).await;
+        recorder.write(&record(0)).await;
+
+        // Disabling closes the segment, which can be read straight away
+        recorder.set_enabled(false).await;
+        assert!(!recorder.is_recording());
+        recorder.write(&record(1)).await;
+        assert_eq!(recorded(&prefix), [Some(0)]);
+        assert_eq!(segments(&prefix).unwrap().len(), 1);
+
+        // Enabling again starts a new segment
+        recorder.set_enabled(true).await;
+        assert!(recorder.is_recording());
+        recorder.write(&record(2)).await;
+        assert_eq!(recorded(&prefix), [Some(0), Some(2)]);
+        assert_eq!(segments(&prefix).unwrap().len(), 2);
+
+        drop(recorder);
+        remove_recording(&prefix);
+    }
+
+    #[tokio::test]
+    async fn restart_starts_a_new_segment() {
+        let prefix = temp_prefix("restart");
+        let mut recorder = Recorder::open(prefix.clone(), RecordingLimits::default()).await;
+        recorder.write(&record(0)).await;
+        recorder.write(&record(1)).await;
+
+        let (path, written) = recorder.restart().await.unwrap();
+        recorder.write(&record(2)).await;
+
+        let segments = segments(&prefix).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[1].1, path);
+
+        // The old segment holds every record before the restart, and nothing
+        // after it
+        let old = std::fs::read(&segments[0].1).unwrap();
+        assert_eq!(old.len() as u64, written);
+        assert_eq!(old, [record(0), b"\n".to_vec(), record(1), b"\n".to_vec()].concat());
+        assert_eq!(std::fs::read(&path).unwrap(), [record(2), b"\n".to_vec()].concat());
+        assert_eq!(recorded(&prefix), [Some(0), Some(1), Some(2)]);
+
+        drop(recorder);
+        remove_recording(&prefix);
+    }
+
+    #[tokio::test]
+    async fn only_recent_records_are_kept() {
+        let prefix = temp_prefix("limit");
+
+        // Two records to a segment, and two segments kept
+        let limits = RecordingLimits {
+            segment_bytes: 2 * (record(0).len() as u64 + 1),
+            segments: 2,
+        };
+        let mut recorder = Recorder::open(prefix.clone(), limits).await;
+        for seq in 250..=255 {
+            recorder.write(&record(seq)).await;
+        }
+        assert_eq!(recorded(&prefix), [Some(252), Some(253), Some(254), Some(255)]);
+
+        // Across the wrap of the sequence numbers too
+        recorder.write(&record(0)).await;
+        assert_eq!(recorded(&prefix), [Some(254), Some(255), Some(0)]);
+        for seq in 1..3 {
+            recorder.write(&record(seq)).await;
+        }
+        assert_eq!(segments(&prefix).unwrap().len(), 2);
+        assert_eq!(recorded(&prefix), [Some(0), Some(1), Some(2)]);
+
+        drop(recorder);
+        remove_recording(&prefix);
+    }
+}
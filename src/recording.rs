@@ -0,0 +1,85 @@
+//! Reading back the local telemetry recordings made on the air side.
+//!
+//! A recording is split into numbered segments, each holding one packet per
+//! line as the frame it was sent in, without the sync word or newline. Only a limited number of segments are kept, with the
+//! oldest deleted as new ones are started, so the segments which remain must
+//! be read in order of their index rather than assumed to start at zero.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    frame::{decode_header_and_data, FrameError},
+    schema::decode_packet,
+    TelemetryPacket,
+};
+
+/// The path of a segment of the recording named `prefix`.
+pub fn segment_path(prefix: &str, index: u64) -> PathBuf {
+    PathBuf::from(format!("{prefix}.{index}.json"))
+}
+
+/// Find every remaining segment of the recording named `prefix`, oldest
+/// first.
+pub fn segments(prefix: &str) -> io::Result<Vec<(u64, PathBuf)>> {
+    let prefix = Path::new(prefix);
+    let dir = match prefix.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let Some(name) = prefix.file_name().and_then(|name| name.to_str()) else {
+        return Ok(Vec::new());
+    };
+
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let index = path.file_name().and_then(|file| file.to_str()).and_then(|file| {
+            file.strip_prefix(name)?
+                .strip_prefix('.')?
+                .strip_suffix(".json")?
+                .parse()
+                .ok()
+        });
+
+        if let Some(index) = index {
+            segments.push((index, path));
+        }
+    }
+
+    segments.sort_unstable_by_key(|(index, _)| *index);
+
+    Ok(segments)
+}
+
+/// Parse a line of a recording into the sequence number of the frame its
+/// packet was sent in, and the packet, checking it against its CRC.
+///
+/// Recordings made before frames had sequence numbers hold only the JSON of
+/// each packet, so their packets have no sequence number.
+pub fn parse_record(line: &[u8]) -> Result<(Option<u8>, TelemetryPacket), FrameError> {
+    if line.starts_with(b"{") {
+        return Ok((None, decode_packet(line)?));
+    }
+
+    let (seq, packet) = decode_header_and_data(line)?;
+    Ok((Some(seq), packet))
+}
+
+/// Read every packet remaining in the recording named `prefix`, oldest
+/// first, along with the sequence number of the frame it was sent in.
+///
+/// Packets recorded with older schema versions are upgraded. Lines which are
+/// not valid packets, such as one cut off by a loss of power, are skipped.
+pub fn read_records(prefix: &str) -> io::Result<Vec<(Option<u8>, TelemetryPacket)>> {
+    let mut records = Vec::new();
+
+    for (_, path) in segments(prefix)? {
+        let contents = fs::read_to_string(path)?;
+        records.extend(contents.lines().filter_map(|line| parse_record(line.as_bytes()).ok()));
+    }
+
+    Ok(records)
+}
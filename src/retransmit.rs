@@ -0,0 +1,218 @@
+//! Selective repeat of frames the ground missed, turning the lossy downlink
+//! into a best effort reliable channel for data which isn't realtime, such
+//! as info messages and events.
+//!
+//! The air side keeps the most recent of these frames, and the ground asks
+//! for any it missed again by their sequence numbers. Retransmissions only
+//! use link capacity left over by the live telemetry.
+
+use std::collections::VecDeque;
+
+use crate::uplink::{encode_command, COMMAND_FRAME_BYTES, REQUEST_RETRANSMIT};
+
+/// The number of recent frames the air side keeps for retransmission. This
+/// is well under the 256 sequence numbers, so every frame kept has a
+/// different one, and the ground can tell a missed frame which is still
+/// kept from one which is long gone.
+pub const RETRANSMIT_BUFFER_FRAMES: usize = 64;
+
+/// The most recent frames sent, kept so they can be sent again on request.
+#[derive(Debug, Clone, Default)]
+pub struct RetransmitBuffer {
+    frames: VecDeque<(u8, Vec<u8>)>,
+    requested: VecDeque<u8>,
+}
+
+impl RetransmitBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep a frame which has been sent, forgetting the oldest once there
+    /// are more than [`RETRANSMIT_BUFFER_FRAMES`].
+    pub fn push(&mut self, seq: u8, frame: Vec<u8>) {
+        self.frames.push_back((seq, frame));
+
+        if self.frames.len() > RETRANSMIT_BUFFER_FRAMES {
+            self.frames.pop_front();
+        }
+    }
+
+    /// Ask for frame `seq` to be sent again.
+    ///
+    /// Returns `false` if the frame isn't kept, either because it is too old
+    /// or because it held only realtime data.
+    pub fn request(&mut self, seq: u8) -> bool {
+        if !self.frames.iter().any(|(kept, _)| *kept == seq) {
+            return false;
+        }
+
+        if !self.requested.contains(&seq) {
+            self.requested.push_back(seq);
+        }
+
+        true
+    }
+
+    /// The next frame to send again, if any have been requested.
+    ///
+    /// The frame stays requested until [`Self::sent`] is called, so one
+    /// which there is no room for yet is sent later instead.
+    pub fn next_requested(&mut self) -> Option<&[u8]> {
+        // Frames can be forgotten while waiting to be sent
+        while let Some(seq) = self.requested.front() {
+            if let Some((_, frame)) = self.frames.iter().find(|(kept, _)| kept == seq) {
+                return Some(frame);
+            }
+            self.requested.pop_front();
+        }
+
+        None
+    }
+
+    /// Mark the frame returned by [`Self::next_requested`] as sent.
+    pub fn sent(&mut self) {
+        self.requested.pop_front();
+    }
+}
+
+/// Tracks which frames the ground has missed, to ask the air side for them
+/// again.
+///
+/// Frames more than half of the sequence numbers behind the latest one are
+/// taken to be retransmissions of old frames rather than new ones, so a
+/// single outage of over 127 frames isn't noticed. Requesting those would
+/// be pointless anyway, as the air side doesn't keep them.
+#[derive(Debug, Clone, Default)]
+pub struct GapTracker {
+    latest: Option<u8>,
+    missing: VecDeque<u8>,
+}
+
+impl GapTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that frame `seq` was received, whether for the first time or
+    /// as a retransmission.
+    pub fn receive(&mut self, seq: u8) {
+        let Some(latest) = self.latest else {
+            self.latest = Some(seq);
+            return;
+        };
+
+        let ahead = seq.wrapping_sub(latest);
+        if ahead == 0 {
+            return;
+        }
+
+        if ahead >= 128 {
+            self.missing.retain(|missing| *missing != seq);
+            return;
+        }
+
+        let mut skipped = latest.wrapping_add(1);
+        while skipped != seq {
+            self.missing.push_back(skipped);
+            skipped = skipped.wrapping_add(1);
+        }
+        self.latest = Some(seq);
+
+        // The air side has already forgotten frames this old
+        while let Some(oldest) = self.missing.front()
+            && usize::from(seq.wrapping_sub(*oldest)) >= RETRANSMIT_BUFFER_FRAMES
+        {
+            self.missing.pop_front();
+        }
+    }
+
+    /// The sequence numbers of the frames missed which the air side may
+    /// still have, oldest first.
+    pub fn missing(&self) -> impl Iterator<Item = u8> + '_ {
+        self.missing.iter().copied()
+    }
+
+    /// Command frames asking for every missed frame the air side may still
+    /// have, oldest first.
+    pub fn requests(&self) -> Vec<[u8; COMMAND_FRAME_BYTES]> {
+        self.missing().map(|seq| encode_command(REQUEST_RETRANSMIT, seq)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requested_frame_is_retransmitted() {
+        let mut buffer = RetransmitBuffer::new();
+        for seq in 0..10 {
+            buffer.push(seq, vec![seq; 4]);
+        }
+        assert_eq!(buffer.next_requested(), None);
+
+        assert!(buffer.request(3));
+        assert!(buffer.request(7));
+        assert_eq!(buffer.next_requested(), Some(&[3; 4][..]));
+
+        // Not sent yet, so it is offered again
+        assert_eq!(buffer.next_requested(), Some(&[3; 4][..]));
+        buffer.sent();
+        assert_eq!(buffer.next_requested(), Some(&[7; 4][..]));
+        buffer.sent();
+        assert_eq!(buffer.next_requested(), None);
+    }
+
+    #[test]
+    fn buffer_is_bounded() {
+        let mut buffer = RetransmitBuffer::new();
+        for seq in 0..=u8::MAX {
+            buffer.push(seq, vec![seq]);
+        }
+
+        let oldest = (256 - RETRANSMIT_BUFFER_FRAMES) as u8;
+        assert!(!buffer.request(oldest - 1));
+        assert!(buffer.request(oldest));
+        assert!(buffer.request(u8::MAX));
+    }
+
+    #[test]
+    fn forgotten_request_is_skipped() {
+        let mut buffer = RetransmitBuffer::new();
+        buffer.push(0, vec![0]);
+        assert!(buffer.request(0));
+
+        for seq in 1..=RETRANSMIT_BUFFER_FRAMES as u8 {
+            buffer.push(seq, vec![seq]);
+        }
+        assert_eq!(buffer.next_requested(), None);
+    }
+
+    #[test]
+    fn missed_frames_are_requested() {
+        let mut gaps = GapTracker::new();
+        for seq in [0, 1, 2, 5, 6] {
+            gaps.receive(seq);
+        }
+        assert_eq!(gaps.missing().collect::<Vec<_>>(), [3, 4]);
+        assert_eq!(gaps.requests(), [encode_command(REQUEST_RETRANSMIT, 3), encode_command(REQUEST_RETRANSMIT, 4)]);
+
+        // A retransmission fills its gap, and duplicates change nothing
+        gaps.receive(3);
+        gaps.receive(6);
+        assert_eq!(gaps.missing().collect::<Vec<_>>(), [4]);
+
+        // Until the air side has forgotten it
+        for seq in 7..=255 {
+            gaps.receive(seq);
+        }
+        assert_eq!(gaps.missing().count(), 0);
+
+        // Gaps are found across the wrap too
+        gaps.receive(1);
+        assert_eq!(gaps.missing().collect::<Vec<_>>(), [0]);
+        gaps.receive(0);
+        assert_eq!(gaps.missing().count(), 0);
+    }
+}
@@ -0,0 +1,223 @@
+//! AT commands for changing the settings of the RFD-900x radios.
+//!
+//! The radios must share an air rate to hear each other, so the ground
+//! radio is changed over the link first with `RT` commands, and the local
+//! radio only once the remote one has taken the change.
+//!
+//! A change is only kept once the ground confirms it can still hear the
+//! payload. Otherwise it is reverted after [`CONFIRM_TIMEOUT`], on both
+//! radios, so a change which severs the link undoes itself.
+
+use std::time::{Duration, Instant};
+
+/// Enters command mode, once the radio has seen no data for
+/// [`GUARD_TIME`] before and after it.
+pub const COMMAND_MODE: &[u8] = b"+++";
+/// How long the serial port must be silent around [`COMMAND_MODE`].
+pub const GUARD_TIME: Duration = Duration::from_secs(1);
+/// How long to give the radio to act on each AT command. `RT` commands
+/// have to go over the air, so this is generous.
+pub const COMMAND_DELAY: Duration = Duration::from_millis(500);
+/// How long the ground has to confirm a change before it is reverted.
+pub const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The air data rates in kbps the RFD-900x supports.
+pub const AIR_RATES: &[u32] = &[4, 8, 16, 19, 24, 32, 48, 64, 96, 128, 192, 250, 500, 750];
+/// The highest transmit power in dBm the RFD-900x supports.
+pub const MAX_TX_POWER: u32 = 30;
+
+/// The register holding the air data rate.
+const AIR_SPEED_REGISTER: u8 = 2;
+/// The register holding the transmit power.
+const TX_POWER_REGISTER: u8 = 4;
+
+/// Errors which can occur while building the commands for a setting.
+#[derive(Debug, thiserror::Error)]
+pub enum RfdError {
+    #[error("Air rate of {0}kbps is not supported")]
+    UnsupportedAirRate(u32),
+    #[error("Transmit power of {0}dBm is over the maximum of {MAX_TX_POWER}dBm")]
+    TxPowerTooHigh(u32),
+    #[error("The previous change has not been confirmed yet")]
+    ChangePending,
+}
+
+/// A setting of the radios which can be changed at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RadioSetting {
+    /// The air data rate in kbps, which both radios must share
+    AirRate(u32),
+    /// The transmit power of the local radio in dBm
+    TxPower(u32),
+}
+
+impl RadioSetting {
+    /// Check the setting is supported, returning its register and value.
+    fn register(self) -> Result<(u8, u32), RfdError> {
+        match self {
+            RadioSetting::AirRate(rate) if AIR_RATES.contains(&rate) => Ok((AIR_SPEED_REGISTER, rate)),
+            RadioSetting::AirRate(rate) => Err(RfdError::UnsupportedAirRate(rate)),
+            RadioSetting::TxPower(power) if power <= MAX_TX_POWER => Ok((TX_POWER_REGISTER, power)),
+            RadioSetting::TxPower(power) => Err(RfdError::TxPowerTooHigh(power)),
+        }
+    }
+
+    /// Whether the ground radio must be changed as well.
+    pub fn both_ends(self) -> bool {
+        matches!(self, RadioSetting::AirRate(_))
+    }
+}
+
+/// The AT commands to send in command mode to apply a setting, in order,
+/// each terminated by a carriage return.
+///
+/// Each radio saves the setting and reboots to apply it. The remote radio
+/// goes first, as once the local radio has rebooted with a new air rate the
+/// remote one can no longer be reached to change it.
+pub fn at_commands(setting: RadioSetting) -> Result<Vec<String>, RfdError> {
+    let (register, value) = setting.register()?;
+
+    let mut prefixes = Vec::new();
+    if setting.both_ends() {
+        prefixes.push("RT");
+    }
+    prefixes.push("AT");
+
+    Ok(prefixes
+        .into_iter()
+        .flat_map(|prefix| {
+            [
+                format!("{prefix}S{register}={value}\r"),
+                format!("{prefix}&W\r"),
+                format!("{prefix}Z\r"),
+            ]
+        })
+        .collect())
+}
+
+/// The settings of the radios, along with a change the ground has yet to
+/// confirm.
+#[derive(Debug, Clone)]
+pub struct RadioSettings {
+    air_rate: u32,
+    tx_power: u32,
+    /// The setting to go back to, and when to go back to it
+    revert: Option<(RadioSetting, Instant)>,
+}
+
+impl RadioSettings {
+    /// The settings the radios start with.
+    pub fn new(air_rate: u32, tx_power: u32) -> Self {
+        Self {
+            air_rate,
+            tx_power,
+            revert: None,
+        }
+    }
+
+    /// Start changing a setting at `now`, returning the AT commands which
+    /// apply it.
+    ///
+    /// Only one change can wait for confirmation at a time, so there is
+    /// always a single known setting to revert to.
+    pub fn change(&mut self, setting: RadioSetting, now: Instant) -> Result<Vec<String>, RfdError> {
+        if self.revert.is_some() {
+            return Err(RfdError::ChangePending);
+        }
+
+        let commands = at_commands(setting)?;
+        let previous = self.set(setting);
+        self.revert = Some((previous, now + CONFIRM_TIMEOUT));
+
+        Ok(commands)
+    }
+
+    /// Keep the change waiting for confirmation.
+    ///
+    /// Returns `false` if there was no change to confirm.
+    pub fn confirm(&mut self) -> bool {
+        self.revert.take().is_some()
+    }
+
+    /// The AT commands to revert the change waiting for confirmation, if it
+    /// hasn't been confirmed by `now`.
+    pub fn expired(&mut self, now: Instant) -> Option<Vec<String>> {
+        let (previous, deadline) = self.revert?;
+        if now < deadline {
+            return None;
+        }
+
+        self.revert = None;
+        self.set(previous);
+
+        // The previous setting was valid when it was changed from
+        at_commands(previous).ok()
+    }
+
+    /// Whether a change is waiting for confirmation.
+    pub fn pending(&self) -> bool {
+        self.revert.is_some()
+    }
+
+    /// Record a new setting, returning the one it replaces.
+    fn set(&mut self, setting: RadioSetting) -> RadioSetting {
+        match setting {
+            RadioSetting::AirRate(rate) => RadioSetting::AirRate(std::mem::replace(&mut self.air_rate, rate)),
+            RadioSetting::TxPower(power) => RadioSetting::TxPower(std::mem::replace(&mut self.tx_power, power)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn air_rate_commands() {
+        assert_eq!(
+            at_commands(RadioSetting::AirRate(32)).unwrap(),
+            ["RTS2=32\r", "RT&W\r", "RTZ\r", "ATS2=32\r", "AT&W\r", "ATZ\r"]
+        );
+        assert_eq!(at_commands(RadioSetting::TxPower(20)).unwrap(), ["ATS4=20\r", "AT&W\r", "ATZ\r"]);
+
+        assert!(matches!(at_commands(RadioSetting::AirRate(100)), Err(RfdError::UnsupportedAirRate(100))));
+        assert!(matches!(at_commands(RadioSetting::TxPower(31)), Err(RfdError::TxPowerTooHigh(31))));
+    }
+
+    #[test]
+    fn unconfirmed_change_is_reverted() {
+        let start = Instant::now();
+        let mut settings = RadioSettings::new(64, 30);
+
+        settings.change(RadioSetting::AirRate(32), start).unwrap();
+        assert!(matches!(
+            settings.change(RadioSetting::TxPower(20), start),
+            Err(RfdError::ChangePending)
+        ));
+
+        assert_eq!(settings.expired(start + CONFIRM_TIMEOUT / 2), None);
+        assert_eq!(
+            settings.expired(start + CONFIRM_TIMEOUT),
+            Some(at_commands(RadioSetting::AirRate(64)).unwrap())
+        );
+        assert!(!settings.pending());
+        assert!(!settings.confirm());
+    }
+
+    #[test]
+    fn confirmed_change_is_kept() {
+        let start = Instant::now();
+        let mut settings = RadioSettings::new(64, 30);
+
+        settings.change(RadioSetting::TxPower(20), start).unwrap();
+        assert!(settings.confirm());
+        assert_eq!(settings.expired(start + CONFIRM_TIMEOUT), None);
+
+        // The next change reverts to the confirmed setting
+        settings.change(RadioSetting::TxPower(10), start).unwrap();
+        assert_eq!(
+            settings.expired(start + CONFIRM_TIMEOUT),
+            Some(at_commands(RadioSetting::TxPower(20)).unwrap())
+        );
+    }
+}
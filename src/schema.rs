@@ -0,0 +1,81 @@
+//! Versioning of the JSON schema of [`TelemetryPacket`], so recordings of
+//! past flights can still be read as fields are added.
+//!
+//! The versions are:
+//!  1. The original schema, which had no version tag.
+//!  2. Adds the `v` version tag and the required `safe` field. Every other
+//!     field added since is optional.
+
+use serde::{de::Error as _, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::TelemetryPacket;
+
+/// The version of the schema a packet was serialized with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SchemaVersion(pub u8);
+
+impl SchemaVersion {
+    /// The version of packets without a version tag.
+    pub const V1: Self = Self(1);
+    /// The version of packets created by this code.
+    pub const CURRENT: Self = Self(2);
+
+    pub(crate) fn untagged() -> Self {
+        Self::V1
+    }
+}
+
+impl Default for SchemaVersion {
+    fn default() -> Self {
+        Self::CURRENT
+    }
+}
+
+/// Deserialize the JSON of a packet of any schema version, upgrading it to
+/// the current version.
+pub fn decode_packet(json: &[u8]) -> Result<TelemetryPacket, serde_json::Error> {
+    let mut value: Value = serde_json::from_slice(json)?;
+
+    let version = match value.get("v").and_then(Value::as_u64) {
+        Some(v) => {
+            let v = u8::try_from(v).map_err(|_| serde_json::Error::custom(format!("Schema version {v} is invalid")))?;
+            SchemaVersion(v)
+        }
+        None => SchemaVersion::V1,
+    };
+
+    if version < SchemaVersion(2) {
+        migrate_v1(&mut value);
+    }
+
+    serde_json::from_value(value)
+}
+
+/// Upgrade a version 1 packet to version 2.
+fn migrate_v1(value: &mut Value) {
+    if let Some(packet) = value.as_object_mut() {
+        packet.entry("safe").or_insert(Value::Bool(false));
+        packet.insert("v".to_string(), Value::from(2));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v1_packet_is_migrated() {
+        let packet = decode_packet(br#"{"gps":null,"p_alt":12.5,"env":null,"imu":null,"info":["hi"]}"#).unwrap();
+        assert_eq!(packet.schema_version, SchemaVersion::CURRENT);
+        assert!(!packet.safe_mode);
+        assert_eq!(packet.pressure_altitude, Some(12.5));
+        assert_eq!(packet.info, ["hi"]);
+    }
+
+    #[test]
+    fn out_of_range_version_is_rejected() {
+        assert!(decode_packet(br#"{"v":258,"gps":null,"p_alt":null,"env":null,"imu":null,"info":[],"safe":false}"#).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+//! Detection of the payload sitting still, so behavior meant for the pad
+//! isn't triggered by the payload being carried around.
+
+use std::collections::VecDeque;
+
+/// The number of updates of pressure altitude which are checked for
+/// stability. At the normal send interval this is 5 seconds.
+const ALTITUDE_WINDOW: usize = 20;
+/// The pressure altitude may vary by at most this many meters of standard
+/// deviation while stationary.
+const MAX_ALTITUDE_DEVIATION: f64 = 1.0;
+/// The GPS speed may be at most this many meters per second while
+/// stationary. GPS speed is noisy, so this is generous.
+const MAX_GPS_SPEED: f32 = 1.0;
+/// The number of consecutive stationary updates before the payload is
+/// considered stationary. At the normal send interval this is 10 seconds.
+const REQUIRED_STATIONARY_UPDATES: u32 = 40;
+
+/// Decides whether the payload is stationary from the stability of its
+/// pressure altitude and, when available, its GPS speed.
+#[derive(Debug, Clone, Default)]
+pub struct StationaryDetector {
+    altitudes: VecDeque<f64>,
+    stationary_updates: u32,
+}
+
+impl StationaryDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the latest pressure altitude and GPS speed, returning whether the
+    /// payload has been stationary for long enough.
+    ///
+    /// Without a GPS speed, such as when the GPS has no fix, only the
+    /// pressure altitude is used.
+    pub fn update(&mut self, altitude: Option<f64>, gps_speed: Option<f32>) -> bool {
+        let Some(altitude) = altitude else {
+            // Without the barometer stability can't be judged at all
+            self.altitudes.clear();
+            self.stationary_updates = 0;
+            return false;
+        };
+
+        self.altitudes.push_back(altitude);
+        if self.altitudes.len() > ALTITUDE_WINDOW {
+            self.altitudes.pop_front();
+        }
+
+        let baro_still = self.altitudes.len() == ALTITUDE_WINDOW
+            && deviation(&self.altitudes) <= MAX_ALTITUDE_DEVIATION;
+        let gps_still = gps_speed.is_none_or(|speed| speed <= MAX_GPS_SPEED);
+
+        if baro_still && gps_still {
+            self.stationary_updates = self.stationary_updates.saturating_add(1);
+        } else {
+            self.stationary_updates = 0;
+        }
+
+        self.is_stationary()
+    }
+
+    /// Whether the payload has been stationary for long enough.
+    pub fn is_stationary(&self) -> bool {
+        self.stationary_updates >= REQUIRED_STATIONARY_UPDATES
+    }
+}
+
+/// The standard deviation of some values.
+fn deviation(values: &VecDeque<f64>) -> f64 {
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+
+    variance.sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The first update of the detector is made once the altitude window is
+    /// full, so this many are needed to become stationary.
+    const UPDATES_TO_STATIONARY: usize = ALTITUDE_WINDOW - 1 + REQUIRED_STATIONARY_UPDATES as usize;
+
+    /// A pad altitude with a little noise from the barometer.
+    fn pad_altitude(update: usize) -> f64 {
+        if update.is_multiple_of(2) { 400.3 } else { 399.7 }
+    }
+
+    #[test]
+    fn stationary_then_moving() {
+        let mut detector = StationaryDetector::new();
+        for update in 0..UPDATES_TO_STATIONARY - 1 {
+            assert!(!detector.update(Some(pad_altitude(update)), Some(0.3)), "update {update}");
+        }
+        assert!(detector.update(Some(pad_altitude(0)), Some(0.3)));
+
+        // Carried off at walking pace, with the altitude still steady
+        assert!(!detector.update(Some(pad_altitude(1)), Some(1.5)));
+        assert!(!detector.is_stationary());
+
+        // Set down again, it takes the full time to count as stationary
+        for update in 0..REQUIRED_STATIONARY_UPDATES as usize - 1 {
+            assert!(!detector.update(Some(pad_altitude(update)), Some(0.3)), "update {update}");
+        }
+        assert!(detector.update(Some(pad_altitude(0)), Some(0.3)));
+    }
+
+    #[test]
+    fn baro_only_without_gps() {
+        let mut detector = StationaryDetector::new();
+        for update in 0..UPDATES_TO_STATIONARY - 1 {
+            assert!(!detector.update(Some(pad_altitude(update)), None), "update {update}");
+        }
+        assert!(detector.update(Some(pad_altitude(0)), None));
+
+        // Climbing 2m every update soon spreads the window too far
+        for update in 1..=5 {
+            detector.update(Some(400.0 + 2.0 * f64::from(update)), None);
+        }
+        assert!(!detector.is_stationary());
+
+        // And nothing can be judged without the barometer
+        assert!(!detector.update(None, Some(0.0)));
+    }
+}
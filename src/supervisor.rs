@@ -0,0 +1,177 @@
+use tokio::time::{Duration, Instant};
+
+/// How long a subsystem can go without producing new data before it is
+/// considered to have failed.
+const SUBSYSTEM_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Safe mode is entered when fewer than this many subsystems are healthy.
+const SAFE_MODE_MIN_HEALTHY: usize = 2;
+
+/// How long after boot the subsystems have to start producing data before
+/// safe mode can be entered.
+const STARTUP_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// The data producing subsystems of the payload which are monitored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsystem {
+    Gps,
+    Bmp,
+    Bno,
+    Hts,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 4] = [
+        Subsystem::Gps,
+        Subsystem::Bmp,
+        Subsystem::Bno,
+        Subsystem::Hts,
+    ];
+}
+
+/// Keeps track of when each subsystem last produced data, and decides when
+/// the payload should be in safe mode.
+///
+/// Safe mode is a single, prominent signal to the ground that several
+/// subsystems have failed at once (for example, the whole I²C bus dying),
+/// rather than a trickle of individual errors.
+#[derive(Debug)]
+pub struct Supervisor {
+    started: Instant,
+    last_update: [Option<Instant>; Subsystem::ALL.len()],
+    safe_mode: bool,
+}
+
+impl Supervisor {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            started: now,
+            last_update: [None; Subsystem::ALL.len()],
+            safe_mode: false,
+        }
+    }
+
+    /// Record that a subsystem has produced new data.
+    pub fn report(&mut self, subsystem: Subsystem, now: Instant) {
+        self.last_update[subsystem as usize] = Some(now);
+    }
+
+    /// Whether a subsystem has produced data recently.
+    pub fn is_healthy(&self, subsystem: Subsystem, now: Instant) -> bool {
+        self.last_update[subsystem as usize]
+            .is_some_and(|last| now.saturating_duration_since(last) < SUBSYSTEM_TIMEOUT)
+    }
+
+    /// The number of subsystems which have produced data recently.
+    pub fn healthy_count(&self, now: Instant) -> usize {
+        Subsystem::ALL
+            .iter()
+            .filter(|s| self.is_healthy(**s, now))
+            .count()
+    }
+
+    /// Re-evaluate whether the payload should be in safe mode.
+    ///
+    /// Subsystems which are still starting up don't count against the
+    /// payload until [`STARTUP_GRACE_PERIOD`] has passed, so a normal boot
+    /// doesn't enter safe mode. Returns `true` if the safe mode state
+    /// changed.
+    pub fn update(&mut self, now: Instant) -> bool {
+        let starting = now.saturating_duration_since(self.started) < STARTUP_GRACE_PERIOD;
+        let unhealthy = Subsystem::ALL
+            .iter()
+            .filter(|s| !self.is_healthy(**s, now))
+            .filter(|s| !starting || self.last_update[**s as usize].is_some())
+            .count();
+        let safe_mode = Subsystem::ALL.len() - unhealthy < SAFE_MODE_MIN_HEALTHY;
+        let changed = safe_mode != self.safe_mode;
+        self.safe_mode = safe_mode;
+
+        changed
+    }
+
+    /// Whether the payload is currently in safe mode.
+    pub fn safe_mode(&self) -> bool {
+        self.safe_mode
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arowss::clock::{Clock, FakeClock};
+
+    use super::*;
+
+    #[test]
+    fn boot_is_not_safe_mode() {
+        let start = Instant::now();
+        let mut supervisor = Supervisor::new(start);
+
+        assert!(!supervisor.update(start));
+        assert!(!supervisor.update(start + Duration::from_secs(10)));
+        assert!(!supervisor.safe_mode());
+
+        // Nothing has started by the end of the grace period
+        assert!(supervisor.update(start + STARTUP_GRACE_PERIOD));
+        assert!(supervisor.safe_mode());
+    }
+
+    #[test]
+    fn multi_sensor_failure_enters_safe_mode() {
+        let start = Instant::now();
+        let mut supervisor = Supervisor::new(start);
+
+        let mut now = start;
+        for _ in 0..10 {
+            now += Duration::from_secs(1);
+            for subsystem in Subsystem::ALL {
+                supervisor.report(subsystem, now);
+            }
+            supervisor.update(now);
+        }
+        assert!(!supervisor.safe_mode());
+
+        // The I²C bus dies, leaving only the GPS
+        let failed = now;
+        while now < failed + SUBSYSTEM_TIMEOUT {
+            now += Duration::from_secs(1);
+            supervisor.report(Subsystem::Gps, now);
+            supervisor.update(now);
+        }
+        assert!(supervisor.safe_mode());
+        assert_eq!(supervisor.healthy_count(now), 1);
+
+        // One sensor coming back is enough to leave safe mode
+        supervisor.report(Subsystem::Bmp, now);
+        assert!(supervisor.update(now));
+        assert!(!supervisor.safe_mode());
+    }
+
+    #[test]
+    fn early_failure_counts_during_grace_period() {
+        let start = Instant::now();
+        let mut supervisor = Supervisor::new(start);
+
+        for subsystem in [Subsystem::Bmp, Subsystem::Bno, Subsystem::Hts] {
+            supervisor.report(subsystem, start);
+        }
+        let now = start + SUBSYSTEM_TIMEOUT;
+        assert!(supervisor.update(now));
+        assert!(supervisor.safe_mode());
+    }
+
+    #[test]
+    fn fake_clock_drives_staleness() {
+        let clock = FakeClock::new(1_760_000_000_000);
+        let now = |clock: &FakeClock| Instant::from_std(clock.now());
+        let mut supervisor = Supervisor::new(now(&clock));
+        supervisor.report(Subsystem::Gps, now(&clock));
+
+        clock.advance(SUBSYSTEM_TIMEOUT - Duration::from_millis(1));
+        assert!(supervisor.is_healthy(Subsystem::Gps, now(&clock)));
+
+        clock.advance(Duration::from_millis(1));
+        assert!(!supervisor.is_healthy(Subsystem::Gps, now(&clock)));
+        assert_eq!(clock.unix_millis(), 1_760_000_000_000 + SUBSYSTEM_TIMEOUT.as_millis() as i64);
+    }
+}
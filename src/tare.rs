@@ -0,0 +1,144 @@
+//! Baselines captured on the pad, against which the height and orientation
+//! of the payload are reported.
+
+use bno055::mint::{Quaternion, Vector3};
+
+use crate::altitude::pressure_altitude;
+
+/// Reasons the sensors can't be tared.
+#[derive(Debug, thiserror::Error)]
+pub enum TareError {
+    #[error("No valid pressure to tare to")]
+    NoPressure,
+    #[error("No valid orientation to tare to")]
+    NoOrientation,
+    #[error("Payload is not stationary on the pad")]
+    Moving,
+}
+
+/// The pressure and orientation captured when the sensors were last tared.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tare {
+    pressure: Option<f64>,
+    orientation: Option<Quaternion<f32>>,
+}
+
+impl Tare {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Capture the current pressure in pascals and orientation as the
+    /// baselines.
+    ///
+    /// Taring is refused unless both readings are valid and the payload is
+    /// stationary, so a tare sent by mistake in flight can't move the
+    /// baselines. The previous baselines are kept if it is refused.
+    pub fn capture(
+        &mut self,
+        pressure: Option<f64>,
+        orientation: Option<Quaternion<f32>>,
+        stationary: bool,
+    ) -> Result<(), TareError> {
+        if !stationary {
+            return Err(TareError::Moving);
+        }
+
+        let pressure = pressure
+            .filter(|p| p.is_finite() && *p > 0.0)
+            .ok_or(TareError::NoPressure)?;
+        let orientation = orientation
+            .filter(|q| quaternion_norm(q) > 0.5)
+            .ok_or(TareError::NoOrientation)?;
+
+        self.pressure = Some(pressure);
+        self.orientation = Some(orientation);
+        Ok(())
+    }
+
+    /// The pressure baseline in pascals, if the sensors have been tared.
+    pub fn pressure(&self) -> Option<f64> {
+        self.pressure
+    }
+
+    /// The height in meters above where the sensors were tared.
+    pub fn height(&self, pressure: f64) -> Option<f64> {
+        self.pressure.map(|baseline| pressure_altitude(pressure, baseline))
+    }
+
+    /// The rotation of the payload since the sensors were tared.
+    pub fn relative_orientation(&self, orientation: Quaternion<f32>) -> Option<Quaternion<f32>> {
+        let baseline = self.orientation?;
+
+        // The inverse of the unit baseline is its conjugate
+        let conjugate = Quaternion {
+            v: Vector3 {
+                x: -baseline.v.x,
+                y: -baseline.v.y,
+                z: -baseline.v.z,
+            },
+            s: baseline.s,
+        };
+
+        Some(multiply(conjugate, orientation))
+    }
+}
+
+fn quaternion_norm(q: &Quaternion<f32>) -> f32 {
+    (q.s * q.s + q.v.x * q.v.x + q.v.y * q.v.y + q.v.z * q.v.z).sqrt()
+}
+
+/// The Hamilton product `a * b`.
+fn multiply(a: Quaternion<f32>, b: Quaternion<f32>) -> Quaternion<f32> {
+    Quaternion {
+        v: Vector3 {
+            x: a.s * b.v.x + a.v.x * b.s + a.v.y * b.v.z - a.v.z * b.v.y,
+            y: a.s * b.v.y - a.v.x * b.v.z + a.v.y * b.s + a.v.z * b.v.x,
+            z: a.s * b.v.z + a.v.x * b.v.y - a.v.y * b.v.x + a.v.z * b.s,
+        },
+        s: a.s * b.s - a.v.x * b.v.x - a.v.y * b.v.y - a.v.z * b.v.z,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An orientation tilted 30° about the X axis.
+    fn tilted() -> Quaternion<f32> {
+        let half = 30f32.to_radians() / 2.0;
+        Quaternion { v: Vector3 { x: half.sin(), y: 0.0, z: 0.0 }, s: half.cos() }
+    }
+
+    #[test]
+    fn tared_values_are_zero_at_baseline() {
+        let mut tare = Tare::new();
+        assert_eq!(tare.height(97_000.0), None);
+        assert!(tare.relative_orientation(tilted()).is_none());
+
+        tare.capture(Some(97_000.0), Some(tilted()), true).unwrap();
+
+        let height = tare.height(97_000.0).unwrap();
+        assert!(height.abs() < 1e-6, "{height}");
+
+        // With no rotation since the tare, the relative orientation is the
+        // identity
+        let relative = tare.relative_orientation(tilted()).unwrap();
+        assert!((relative.s - 1.0).abs() < 1e-6, "{relative:?}");
+        assert!(relative.v.x.abs() < 1e-6 && relative.v.y.abs() < 1e-6 && relative.v.z.abs() < 1e-6, "{relative:?}");
+
+        // Moving off the baseline reads as a height above it
+        assert!(tare.height(96_000.0).unwrap() > 80.0);
+    }
+
+    #[test]
+    fn invalid_tare_is_refused() {
+        let mut tare = Tare::new();
+        tare.capture(Some(97_000.0), Some(tilted()), true).unwrap();
+
+        assert!(matches!(tare.capture(Some(90_000.0), Some(tilted()), false), Err(TareError::Moving)));
+        assert!(matches!(tare.capture(Some(f64::NAN), Some(tilted()), true), Err(TareError::NoPressure)));
+        assert!(matches!(tare.capture(Some(90_000.0), None, true), Err(TareError::NoOrientation)));
+        assert_eq!(tare.pressure(), Some(97_000.0));
+    }
+}
@@ -0,0 +1,80 @@
+//! Estimation of the offset between the air and ground clocks, for when no
+//! GPS time is available.
+//!
+//! The ground sends a `Ping` command and records when it did so. The air
+//! side replies with an info message created by [`format_pong`] holding the
+//! time it received the ping, and the packet carrying that message is
+//! stamped with the time it was sent. Together with the time the ground
+//! received the packet, this gives the four timestamps of an NTP style
+//! exchange.
+//!
+//! All times are in milliseconds since the Unix epoch.
+
+/// The prefix of the info message sent in reply to a ping.
+const PONG_PREFIX: &str = "PONG ";
+
+/// The result of a ping exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSync {
+    /// How far the air clock is ahead of the ground clock
+    pub offset_ms: i64,
+    /// Time spent on the link in both directions, excluding the time the
+    /// reply spent waiting on the air side
+    pub round_trip_ms: i64,
+}
+
+/// Estimate the clock offset from the four timestamps of a ping exchange.
+///
+/// The RFD-900x link is half-duplex and the reply waits for the next
+/// packet, so the time between `air_received` and `air_sent` is removed
+/// from the round trip rather than assumed to be symmetric link delay.
+pub fn estimate_clock_offset(
+    ground_sent: i64,
+    air_received: i64,
+    air_sent: i64,
+    ground_received: i64,
+) -> ClockSync {
+    ClockSync {
+        offset_ms: ((air_received - ground_sent) + (air_sent - ground_received)) / 2,
+        round_trip_ms: (ground_received - ground_sent) - (air_sent - air_received),
+    }
+}
+
+/// Create the info message replying to a ping received at `air_received`.
+pub fn format_pong(air_received: i64) -> String {
+    format!("{PONG_PREFIX}{air_received}")
+}
+
+/// Read the time a ping was received from an info message created by
+/// [`format_pong`].
+pub fn parse_pong(info: &str) -> Option<i64> {
+    info.strip_prefix(PONG_PREFIX)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_from_known_timestamps() {
+        // The air clock is 1s ahead, each direction takes 40ms, and the
+        // reply waits 200ms on the air side for the next packet
+        let ground_sent = 10_000;
+        let air_received = ground_sent + 40 + 1_000;
+        let air_sent = air_received + 200;
+        let ground_received = air_sent - 1_000 + 40;
+
+        let sync = estimate_clock_offset(ground_sent, air_received, air_sent, ground_received);
+        assert_eq!(sync, ClockSync { offset_ms: 1_000, round_trip_ms: 80 });
+    }
+
+    #[test]
+    fn pong_round_trip() {
+        let pong = format_pong(1_767_225_600_123);
+        assert_eq!(pong, "PONG 1767225600123");
+        assert_eq!(parse_pong(&pong), Some(1_767_225_600_123));
+
+        assert_eq!(parse_pong("PONG"), None);
+        assert_eq!(parse_pong("PING 1767225600123"), None);
+    }
+}
@@ -0,0 +1,26 @@
+//! The framing of commands sent from the ground to the rocket.
+//!
+//! Every command frame is [`COMMAND_FRAME_BYTES`] long:
+//!  1. [`COMMAND_MAGIC`]
+//!  2. Command
+//!  3. Argument, which is 0 for commands without one
+//!  4. Checksum of the magic byte, command and argument
+//!  5. Space b' '
+
+use crate::utils::crc8;
+
+/// Every command frame starts with this byte, which is also covered by the
+/// checksum, so random noise is very unlikely to form a valid frame.
+pub const COMMAND_MAGIC: u8 = 0xA5;
+
+/// The number of bytes in a command frame.
+pub const COMMAND_FRAME_BYTES: usize = 5;
+
+/// The command asking for a frame to be sent again, with the sequence
+/// number of the frame as the argument.
+pub const REQUEST_RETRANSMIT: u8 = 121;
+
+/// Encode a command and its argument into a frame to send to the rocket.
+pub fn encode_command(command: u8, arg: u8) -> [u8; COMMAND_FRAME_BYTES] {
+    [COMMAND_MAGIC, command, arg, crc8(&[COMMAND_MAGIC, command, arg]), b' ']
+}
@@ -1,3 +1,86 @@
+/// Calculate the checksum of an NMEA sentence, which is the XOR of every byte
+/// between the `$` and `*`.
+#[must_use]
+pub fn nmea_checksum(body: &[u8]) -> u8 {
+    body.iter().fold(0, |checksum, byte| checksum ^ byte)
+}
+
+/// Create a complete NMEA sentence, including its checksum and line ending,
+/// from the body of the sentence between the `$` and `*`.
+#[must_use]
+pub fn nmea_sentence(body: &str) -> String {
+    format!("${body}*{:02X}\r\n", nmea_checksum(body.as_bytes()))
+}
+
+/// Sentences longer than this are discarded. The NMEA limit is 82 bytes,
+/// but some receivers exceed it.
+const MAX_NMEA_SENTENCE_BYTES: usize = 128;
+
+/// Splits a stream of bytes from a GPS into NMEA sentences.
+///
+/// Sentences may end with `\r\n`, `\n` or a bare `\r`. A `$` always
+/// starts a new sentence, so a sentence which lost its line ending doesn't
+/// corrupt the one after it, and any bytes before the first `$` are ignored.
+#[derive(Debug, Default)]
+pub struct NmeaLines {
+    buffer: Vec<u8>,
+}
+
+impl NmeaLines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether no sentence is partially received.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Add a byte received from the GPS, returning the sentence it
+    /// completed, if any, without its line ending.
+    pub fn push(&mut self, byte: u8) -> Option<String> {
+        match byte {
+            b'$' => {
+                self.buffer.clear();
+                self.buffer.push(byte);
+            }
+            b'\r' | b'\n' if !self.buffer.is_empty() => {
+                let sentence = String::from_utf8_lossy(&self.buffer).into_owned();
+                self.buffer.clear();
+                return Some(sentence);
+            }
+            _ if !self.buffer.is_empty() => {
+                self.buffer.push(byte);
+                if self.buffer.len() > MAX_NMEA_SENTENCE_BYTES {
+                    self.buffer.clear();
+                }
+            }
+            _ => (),
+        }
+
+        None
+    }
+}
+
+/// The standard input which CRCs are checked against.
+pub const CRC_CHECK_INPUT: &[u8] = b"123456789";
+/// The [`crc8`] of [`CRC_CHECK_INPUT`], which is the CRC-8/DVB-S2 check
+/// value, as used by the ground station.
+pub const CRC8_CHECK_VALUE: u8 = 0xBC;
+/// The body of a commonly quoted example NMEA sentence.
+pub const NMEA_CHECK_SENTENCE: &str = "GPGLL,5057.970,N,00146.110,E,142451,A";
+/// The [`nmea_checksum`] of [`NMEA_CHECK_SENTENCE`].
+pub const NMEA_CHECK_VALUE: u8 = 0x27;
+
+/// Check [`crc8`] and [`nmea_checksum`] against their reference values, so
+/// an accidental change to either is caught before anything is sent.
+#[must_use]
+pub fn checksums_match_reference() -> bool {
+    crc8(CRC_CHECK_INPUT) == CRC8_CHECK_VALUE
+        && nmea_checksum(NMEA_CHECK_SENTENCE.as_bytes()) == NMEA_CHECK_VALUE
+}
+
 /// Calculate the CRC for some arbitrary data.
 #[must_use]
 pub fn crc8(arr: &[u8]) -> u8 {
@@ -14,3 +97,42 @@ pub fn crc8(arr: &[u8]) -> u8 {
     }
     crc
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every sentence completed by a stream chopped into `reads`.
+    fn sentences<'a>(lines: &mut NmeaLines, reads: impl IntoIterator<Item = &'a [u8]>) -> Vec<String> {
+        reads.into_iter().flatten().filter_map(|byte| lines.push(*byte)).collect()
+    }
+
+    #[test]
+    fn mixed_line_endings() {
+        let mut lines = NmeaLines::new();
+        let stream: &[u8] = b"$GPGGA,1*00\r\n$GPRMC,2*00\n$GPGSA,3*00\r$GPGSV,4*00\r\n";
+
+        assert_eq!(sentences(&mut lines, [stream]), ["$GPGGA,1*00", "$GPRMC,2*00", "$GPGSA,3*00", "$GPGSV,4*00"]);
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn resyncs_on_dollar() {
+        let mut lines = NmeaLines::new();
+
+        // Garbage before the first sentence, and a sentence cut off by the
+        // start of the next one
+        let stream: &[u8] = b"\x00\xFFgarbage\n$GPGGA,cut$GPRMC,2*00\r\n";
+        assert_eq!(sentences(&mut lines, [stream]), ["$GPRMC,2*00"]);
+    }
+
+    #[test]
+    fn sentence_split_across_reads() {
+        let mut lines = NmeaLines::new();
+
+        assert!(sentences(&mut lines, [&b"$GPGGA,12"[..]]).is_empty());
+        assert!(!lines.is_empty());
+        assert_eq!(sentences(&mut lines, [&b"34*00\r"[..], &b"\n$GP"[..]]), ["$GPGGA,1234*00"]);
+        assert_eq!(sentences(&mut lines, [&b"RMC,2*00\n"[..]]), ["$GPRMC,2*00"]);
+    }
+}
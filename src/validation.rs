@@ -0,0 +1,155 @@
+//! Plausibility checks for decoded packets.
+//!
+//! A corrupted frame can still pass the 8 bit CRC, so after decoding, the
+//! ground can check that the values in a packet are physically possible.
+
+use std::ops::RangeInclusive;
+
+use crate::{decode_frame, FrameError, TelemetryPacket};
+
+const LATITUDE_RANGE: RangeInclusive<f64> = -90.0..=90.0;
+const LONGITUDE_RANGE: RangeInclusive<f64> = -180.0..=180.0;
+/// Altitudes in meters, from below the Dead Sea to well above any flight.
+const ALTITUDE_RANGE: RangeInclusive<f64> = -500.0..=50_000.0;
+/// Pressure in pascals, from a little below the pressure at the top of
+/// [`ALTITUDE_RANGE`]. No pressure is sent while the sensor is unavailable,
+/// so zero is never plausible.
+const PRESSURE_RANGE: RangeInclusive<f64> = 50.0..=120_000.0;
+/// Temperature in degrees Celsius.
+const TEMPERATURE_RANGE: RangeInclusive<f64> = -60.0..=100.0;
+/// Relative humidity in percent.
+const HUMIDITY_RANGE: RangeInclusive<f64> = 0.0..=100.0;
+
+/// How to treat packets which pass their CRC but contain implausible values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValidationMode {
+    /// Reject implausible packets entirely
+    #[default]
+    Strict,
+    /// Keep implausible packets, but report which values were implausible
+    Lenient,
+}
+
+/// A value in a packet which is outside of its plausible range.
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum PlausibilityError {
+    #[error("Latitude {0} is out of range")]
+    Latitude(f64),
+    #[error("Longitude {0} is out of range")]
+    Longitude(f64),
+    #[error("GPS altitude {0} is out of range")]
+    GpsAltitude(f32),
+    #[error("Pressure altitude {0} is out of range")]
+    PressureAltitude(f64),
+    #[error("Pressure {0} is out of range")]
+    Pressure(f64),
+    #[error("Temperature {0} is out of range")]
+    Temperature(f64),
+    #[error("Humidity {0} is out of range")]
+    Humidity(f64),
+}
+
+impl TelemetryPacket {
+    /// Check that every value in this packet is physically plausible,
+    /// returning each one which is not.
+    pub fn check_plausibility(&self) -> Vec<PlausibilityError> {
+        let mut errors = Vec::new();
+
+        if let Some(gps) = self.gps {
+            if !LATITUDE_RANGE.contains(&gps.latitude) {
+                errors.push(PlausibilityError::Latitude(gps.latitude));
+            }
+            if !LONGITUDE_RANGE.contains(&gps.longitude) {
+                errors.push(PlausibilityError::Longitude(gps.longitude));
+            }
+            if !ALTITUDE_RANGE.contains(&f64::from(gps.altitude)) {
+                errors.push(PlausibilityError::GpsAltitude(gps.altitude));
+            }
+        }
+
+        if let Some(altitude) = self.pressure_altitude
+            && !ALTITUDE_RANGE.contains(&altitude)
+        {
+            errors.push(PlausibilityError::PressureAltitude(altitude));
+        }
+
+        if let Some(env) = self.environmental_info {
+            if !PRESSURE_RANGE.contains(&env.pressure) {
+                errors.push(PlausibilityError::Pressure(env.pressure));
+            }
+            if !TEMPERATURE_RANGE.contains(&env.temperature) {
+                errors.push(PlausibilityError::Temperature(env.temperature));
+            }
+            if !HUMIDITY_RANGE.contains(&env.humidity) {
+                errors.push(PlausibilityError::Humidity(env.humidity));
+            }
+        }
+
+        errors
+    }
+}
+
+/// Decode a frame with [`decode_frame`], then check it for plausibility.
+///
+/// In [`ValidationMode::Strict`] an implausible packet is rejected with
+/// [`FrameError::Implausible`]. In [`ValidationMode::Lenient`] it is returned
+/// along with its sequence number and the list of implausible values.
+pub fn decode_frame_validated(
+    frame: &[u8],
+    mode: ValidationMode,
+) -> Result<(u8, TelemetryPacket, Vec<PlausibilityError>), FrameError> {
+    let (seq, packet) = decode_frame(frame)?;
+    let errors = packet.check_plausibility();
+
+    if mode == ValidationMode::Strict && !errors.is_empty() {
+        return Err(FrameError::Implausible(errors));
+    }
+
+    Ok((seq, packet, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode_frame, GpsInfo};
+
+    /// A frame holding a fix at `latitude`.
+    fn frame(latitude: f64) -> Vec<u8> {
+        let packet = TelemetryPacket {
+            gps: Some(GpsInfo {
+                latitude,
+                longitude: -96.705_654,
+                altitude: 1234.5,
+                satellites: 12,
+                hdop: None,
+                geoid_separation: None,
+                speed: None,
+                course: None,
+            }),
+            ..TelemetryPacket::default()
+        };
+
+        encode_frame(5, &packet)
+    }
+
+    #[test]
+    fn strict_rejects_out_of_range_latitude() {
+        let (seq, _, errors) = decode_frame_validated(&frame(40.82), ValidationMode::Strict).unwrap();
+        assert_eq!(seq, 5);
+        assert!(errors.is_empty());
+
+        let result = decode_frame_validated(&frame(140.82), ValidationMode::Strict);
+        assert!(matches!(
+            result,
+            Err(FrameError::Implausible(errors)) if errors == [PlausibilityError::Latitude(140.82)]
+        ));
+    }
+
+    #[test]
+    fn lenient_flags_out_of_range_latitude() {
+        let (seq, packet, errors) = decode_frame_validated(&frame(140.82), ValidationMode::Lenient).unwrap();
+        assert_eq!(seq, 5);
+        assert_eq!(packet.gps.unwrap().latitude, 140.82);
+        assert_eq!(errors, [PlausibilityError::Latitude(140.82)]);
+    }
+}
@@ -0,0 +1,170 @@
+//! Values computed from the real sensors, such as density altitude, which
+//! can be added to telemetry without changing the sending loop.
+
+use std::collections::BTreeMap;
+
+use crate::GpsInfo;
+
+/// The specific gas constant of dry air, in J/(kg·K).
+const DRY_AIR_GAS_CONSTANT: f64 = 287.05;
+/// The density of air at sea level in the International Standard
+/// Atmosphere, in kg/m³.
+const STANDARD_SEA_LEVEL_DENSITY: f64 = 1.225;
+/// The bytes each computed value adds to a packet beyond its name and
+/// number, for the quotes around both, the colon and the comma.
+const VALUE_OVERHEAD_BYTES: usize = 6;
+
+/// The latest sensor values, from which virtual sensors compute their
+/// values.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TelemetrySnapshot {
+    /// Pressure in pascals
+    pub pressure: Option<f64>,
+    /// Temperature in degrees Celsius
+    pub temperature: Option<f64>,
+    /// Relative humidity in percent
+    pub humidity: Option<f64>,
+    /// Pressure altitude in meters
+    pub pressure_altitude: Option<f64>,
+    pub gps: Option<GpsInfo>,
+}
+
+/// A value computed from the real sensors each packet.
+pub trait VirtualSensor {
+    /// Compute the name and value to send, or `None` if the values it
+    /// needs aren't available.
+    fn compute(&self, snapshot: &TelemetrySnapshot) -> Option<(String, f64)>;
+}
+
+/// Errors which can occur while adding a virtual sensor.
+#[derive(Debug, thiserror::Error)]
+pub enum VirtualSensorError {
+    #[error("No more than {0} virtual sensors can be added")]
+    TooMany(usize),
+}
+
+/// The virtual sensors evaluated for every packet.
+///
+/// Both the number of sensors and the bytes their values take up are
+/// bounded, so they can't crowd out the real sensor data.
+pub struct VirtualSensors {
+    sensors: Vec<Box<dyn VirtualSensor + Send>>,
+    max_sensors: usize,
+    max_bytes: usize,
+}
+
+impl VirtualSensors {
+    pub fn new(max_sensors: usize, max_bytes: usize) -> Self {
+        Self {
+            sensors: Vec::new(),
+            max_sensors,
+            max_bytes,
+        }
+    }
+
+    /// Add a sensor, which is evaluated after those already added.
+    pub fn add(&mut self, sensor: impl VirtualSensor + Send + 'static) -> Result<(), VirtualSensorError> {
+        if self.sensors.len() >= self.max_sensors {
+            return Err(VirtualSensorError::TooMany(self.max_sensors));
+        }
+
+        self.sensors.push(Box::new(sensor));
+        Ok(())
+    }
+
+    /// Compute the value of every sensor, in the order they were added.
+    ///
+    /// Values which would go over the byte budget, or aren't finite, are
+    /// left out.
+    pub fn compute(&self, snapshot: &TelemetrySnapshot) -> BTreeMap<String, f64> {
+        let mut values = BTreeMap::new();
+        let mut bytes = 0;
+
+        for (name, value) in self.sensors.iter().filter_map(|sensor| sensor.compute(snapshot)) {
+            // Values are sent to hundredths, as a string
+            let cost = name.len() + format!("{value:.2}").len() + VALUE_OVERHEAD_BYTES;
+            if !value.is_finite() || bytes + cost > self.max_bytes {
+                continue;
+            }
+
+            bytes += cost;
+            values.insert(name, value);
+        }
+
+        values
+    }
+}
+
+/// The altitude in the International Standard Atmosphere with the same air
+/// density as the payload is in, from the pressure and temperature.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DensityAltitude;
+
+impl VirtualSensor for DensityAltitude {
+    fn compute(&self, snapshot: &TelemetrySnapshot) -> Option<(String, f64)> {
+        let pressure = snapshot.pressure.filter(|p| *p > 0.0)?;
+        let kelvin = snapshot.temperature? + 273.15;
+
+        let density = pressure / (DRY_AIR_GAS_CONSTANT * kelvin);
+        let altitude = 44_330.8 * (1.0 - (density / STANDARD_SEA_LEVEL_DENSITY).powf(0.234_969));
+
+        Some(("dens_alt".to_string(), altitude))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TelemetryPacket;
+
+    fn snapshot(pressure: f64, temperature: f64) -> TelemetrySnapshot {
+        TelemetrySnapshot {
+            pressure: Some(pressure),
+            temperature: Some(temperature),
+            ..TelemetrySnapshot::default()
+        }
+    }
+
+    #[test]
+    fn density_altitude() {
+        let altitude = |pressure, temperature| DensityAltitude.compute(&snapshot(pressure, temperature)).unwrap().1;
+
+        // The standard atmosphere at sea level and at 1000m
+        assert!(altitude(101_325.0, 15.0).abs() < 1.0);
+        assert!((altitude(89_874.6, 8.5) - 1000.0).abs() < 1.0);
+
+        // A hot day at sea level flies like almost 700m up
+        assert!((altitude(101_325.0, 35.0) - 693.4).abs() < 1.0);
+
+        assert_eq!(DensityAltitude.compute(&TelemetrySnapshot::default()), None);
+    }
+
+    /// A sensor which always gives the same value.
+    struct Constant(&'static str, f64);
+
+    impl VirtualSensor for Constant {
+        fn compute(&self, _: &TelemetrySnapshot) -> Option<(String, f64)> {
+            Some((self.0.to_string(), self.1))
+        }
+    }
+
+    #[test]
+    fn values_fit_the_byte_budget() {
+        let mut sensors = VirtualSensors::new(4, 45);
+        for name in ["a", "b", "c", "d"] {
+            sensors.add(Constant(name, -1234.5)).unwrap();
+        }
+        assert!(sensors.add(Constant("e", 0.0)).is_err());
+
+        // Each value takes 15 bytes, so only three fit
+        let values = sensors.compute(&TelemetrySnapshot::default());
+        assert_eq!(values.keys().collect::<Vec<_>>(), ["a", "b", "c"]);
+
+        // And the estimate is exact, less the comma after the last value
+        let packet = TelemetryPacket { computed: values, ..TelemetryPacket::default() };
+        let json = serde_json::to_string(&packet).unwrap();
+        let cmp = r#"{"a":"-1234.50","b":"-1234.50","c":"-1234.50"}"#;
+        assert!(json.contains(cmp), "{json}");
+        assert_eq!(cmp.len() - 2, 3 * 15 - 1);
+    }
+}